@@ -6,14 +6,26 @@ use std::collections::HashMap;
 use pm::DeviceInfo;
 use serde_json;
 
-use config::ConfigDeviceInfo;
+use config::{ConfigDeviceInfo, TempoControl};
+use looper::{Metronome, ArpeggiatorParameter};
 use error::Result;
 
 #[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     pub last_input_port: Option<ConfigDeviceInfo>,
     pub last_output_port: Option<ConfigDeviceInfo>,
-    pub keyboard_layout: HashMap<u64, u8>
+    pub keyboard_layout: HashMap<u64, u8>,
+    #[serde(default)]
+    pub metronome: Metronome,
+    #[serde(default)]
+    pub tempo_control: TempoControl,
+    /// Channel and key of the note that taps the tempo
+    #[serde(default)]
+    pub tap_tempo_note: Option<(u8, u8)>,
+    /// Control change numbers learned for the parameters of the
+    /// arpeggiator
+    #[serde(default)]
+    pub arpeggiator_controls: HashMap<u8, ArpeggiatorParameter>,
 }
 
 impl Default for Config {
@@ -22,6 +34,10 @@ impl Default for Config {
             last_input_port: None,
             last_output_port: None,
             keyboard_layout: HashMap::new(),
+            metronome: Metronome::default(),
+            tempo_control: TempoControl::default(),
+            tap_tempo_note: None,
+            arpeggiator_controls: HashMap::new(),
         }
     }
 }
@@ -49,7 +65,11 @@ impl Config {
         Config {
             last_input_port: Some(ConfigDeviceInfo::new(&input_port)),
             last_output_port: Some(ConfigDeviceInfo::new(&output_port)),
-            keyboard_layout: self.keyboard_layout
+            keyboard_layout: self.keyboard_layout,
+            metronome: self.metronome,
+            tempo_control: self.tempo_control,
+            tap_tempo_note: self.tap_tempo_note,
+            arpeggiator_controls: self.arpeggiator_controls,
         }
     }
 }
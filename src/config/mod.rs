@@ -1,4 +1,6 @@
 mod config;
 mod config_device_info;
+mod tempo_control;
 pub use self::config::Config;
 pub use self::config_device_info::ConfigDeviceInfo;
+pub use self::tempo_control::TempoControl;
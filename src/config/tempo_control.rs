@@ -0,0 +1,53 @@
+use hardcode::*;
+
+/// Maps the values of the tempo control change onto the range of
+/// tempos
+#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TempoControl {
+    pub number: u8,
+    pub min_bpm: u32,
+    pub max_bpm: u32,
+}
+
+impl Default for TempoControl {
+    fn default() -> TempoControl {
+        TempoControl {
+            number: TEMPO_CHANGE_CONTROL_NUMBER,
+            min_bpm: DEFAULT_TEMPO_CONTROL_MIN_BPM,
+            max_bpm: DEFAULT_TEMPO_CONTROL_MAX_BPM,
+        }
+    }
+}
+
+impl TempoControl {
+    pub fn tempo_bpm(&self, value: u8) -> u32 {
+        if self.max_bpm > self.min_bpm {
+            self.min_bpm + (self.max_bpm - self.min_bpm) * value as u32 / 127
+        } else {
+            self.min_bpm
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TempoControl;
+
+    #[test]
+    fn test_default_tempo_control_range() {
+        let tempo_control = TempoControl::default();
+
+        assert_eq!(90, tempo_control.tempo_bpm(0));
+        assert_eq!(150, tempo_control.tempo_bpm(60));
+        assert_eq!(217, tempo_control.tempo_bpm(127));
+    }
+
+    #[test]
+    fn test_custom_tempo_control_range() {
+        let tempo_control = TempoControl { min_bpm: 60, max_bpm: 187, .. TempoControl::default() };
+
+        assert_eq!(60, tempo_control.tempo_bpm(0));
+        assert_eq!(187, tempo_control.tempo_bpm(127));
+    }
+}
@@ -0,0 +1,20 @@
+use looper::Looper;
+use midi::{AbsMidiEvent, MidiNoteTracker};
+
+/// The part of the front-end that lives on the engine thread next to
+/// the looper, so the MIDI input is handled without waiting for the
+/// UI.
+pub trait Controller: Send + 'static {
+    type NoteTracker: MidiNoteTracker + Send + 'static;
+    /// What the controller reports back to the UI thread
+    type Reply: Send + 'static;
+
+    fn handle_midi_event(&mut self,
+                         looper: &mut Looper<Self::NoteTracker>,
+                         event: &AbsMidiEvent) -> Option<Self::Reply>;
+
+    /// Called by the engine thread every time the looper is updated
+    fn update(&mut self,
+              looper: &mut Looper<Self::NoteTracker>,
+              delta_time: u32) -> Option<Self::Reply>;
+}
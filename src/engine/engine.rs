@@ -0,0 +1,238 @@
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::mem;
+
+use libc;
+use pm::InputPort;
+
+use engine::Controller;
+use engine::queue::{self, Producer, Consumer};
+use engine::triple_buffer::{self, Writer, Reader};
+use looper::{Looper, LooperSnapshot};
+use midi;
+use hardcode::*;
+use traits::Updatable;
+use error::Result;
+
+type Command<C> = Box<FnMut(&mut C, &mut Looper<<C as Controller>::NoteTracker>)
+                            -> Option<<C as Controller>::Reply> + Send>;
+
+/// Runs the looper on its own thread with its own clock, so a slow
+/// frame of the UI never delays the MIDI. The UI talks to the looper
+/// only through the lock-free queues: it sends the commands and
+/// receives the replies of the controller, and it reads the latest
+/// snapshot of the looper state.
+pub struct Engine<C: Controller> {
+    commands: Option<Producer<Command<C>>>,
+    /// The commands performed by the engine thread come back to be
+    /// freed here, so the engine thread does not wait for the
+    /// allocator
+    finished_commands: Consumer<Command<C>>,
+    replies: Consumer<C::Reply>,
+    snapshot: Reader<LooperSnapshot>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<C: Controller> Engine<C> {
+    pub fn spawn(looper: Looper<C::NoteTracker>,
+                 controller: C,
+                 midi_input_port: Option<InputPort>) -> Result<Engine<C>> {
+        let (command_producer, command_consumer) = queue::queue(ENGINE_QUEUE_SIZE);
+        let (finished_producer, finished_consumer) = queue::queue(ENGINE_QUEUE_SIZE);
+        let (reply_producer, reply_consumer) = queue::queue(ENGINE_QUEUE_SIZE);
+        let (snapshot_writer, snapshot_reader) = triple_buffer::triple_buffer(looper.snapshot());
+
+        let thread = try!(thread::Builder::new()
+            .name(String::from("engine"))
+            .spawn(move || {
+                run(looper, controller, midi_input_port,
+                    command_consumer, finished_producer, reply_producer, snapshot_writer)
+            }));
+
+        Ok(Engine {
+            commands: Some(command_producer),
+            finished_commands: finished_consumer,
+            replies: reply_consumer,
+            snapshot: snapshot_reader,
+            thread: Some(thread),
+        })
+    }
+
+    /// Performs the command on the engine thread
+    pub fn execute<F>(&self, command: F)
+        where F: FnMut(&mut C, &mut Looper<C::NoteTracker>) -> Option<C::Reply> + Send + 'static {
+        if let Some(ref commands) = self.commands {
+            let mut command: Command<C> = Box::new(command);
+            // The engine thread takes the commands every millisecond,
+            // so the queue stays full only for a moment
+            while let Err(rejected) = commands.push(command) {
+                // The engine thread is gone only if it panicked
+                assert!(!commands.is_abandoned(), "The engine thread has stopped");
+                command = rejected;
+                thread::yield_now();
+            }
+        }
+    }
+
+    pub fn take_reply(&self) -> Option<C::Reply> {
+        self.replies.pop()
+    }
+
+    /// Takes the latest of the snapshots published by the engine
+    /// thread
+    pub fn update_snapshot(&mut self) {
+        self.snapshot.update();
+        while let Some(_) = self.finished_commands.pop() {}
+    }
+
+    pub fn snapshot(&self) -> &LooperSnapshot {
+        self.snapshot.read()
+    }
+}
+
+impl<C: Controller> Drop for Engine<C> {
+    fn drop(&mut self) {
+        // The engine thread performs the commands sent so far and
+        // stops as soon as it finds out nobody sends them anymore
+        self.commands.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().unwrap();
+        }
+    }
+}
+
+fn run<C: Controller>(mut looper: Looper<C::NoteTracker>,
+                      mut controller: C,
+                      mut midi_input_port: Option<InputPort>,
+                      commands: Consumer<Command<C>>,
+                      finished_commands: Producer<Command<C>>,
+                      replies: Producer<C::Reply>,
+                      mut snapshots: Writer<LooperSnapshot>) {
+    raise_thread_priority();
+
+    // The UI takes the replies every frame. If it stalls, the replies
+    // that don't fit are dropped rather than delaying the MIDI.
+    let reply = |reply: Option<C::Reply>| {
+        if let Some(reply) = reply {
+            replies.push(reply).ok();
+        }
+    };
+
+    let mut previous_time = Instant::now();
+    let mut snapshot_age = 0;
+
+    loop {
+        loop {
+            match commands.pop() {
+                Some(mut command) => {
+                    reply(command(&mut controller, &mut looper));
+                    finished_commands.push(command).ok();
+                },
+                None if commands.is_abandoned() => return,
+                None => break,
+            }
+        }
+
+        if let Some(ref mut midi_input_port) = midi_input_port {
+            // The events are read one by one, so no buffer is allocated
+            // for them
+            for _ in 0..ENGINE_MIDI_READ_LIMIT {
+                match midi_input_port.read() {
+                    Ok(Some(raw_event)) => if let Some(event) = midi::parse_midi_event(&raw_event) {
+                        reply(controller.handle_midi_event(&mut looper, &event));
+                    },
+                    _ => break,
+                }
+            }
+        }
+
+        // Only the whole milliseconds are taken, the rest is left
+        // for the next iteration
+        let elapsed = previous_time.elapsed();
+        let delta_time = (elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1000000) as u32;
+        previous_time += Duration::from_millis(delta_time as u64);
+
+        looper.update(delta_time);
+        reply(controller.update(&mut looper, delta_time));
+
+        snapshot_age += delta_time;
+        if snapshot_age >= ENGINE_SNAPSHOT_PERIOD {
+            snapshot_age = 0;
+            looper.update_snapshot(snapshots.back());
+            snapshots.publish();
+        }
+
+        thread::sleep(Duration::from_millis(ENGINE_SLEEP_TIMEOUT));
+    }
+}
+
+/// Asks the OS to schedule the engine thread before the others. That
+/// usually requires privileges, so the engine just keeps the normal
+/// priority if it is not allowed.
+#[cfg(unix)]
+fn raise_thread_priority() {
+    unsafe {
+        let mut param: libc::sched_param = mem::zeroed();
+        param.sched_priority = libc::sched_get_priority_max(libc::SCHED_FIFO);
+        if libc::pthread_setschedparam(libc::pthread_self(), libc::SCHED_FIFO, &param) != 0 {
+            println!("[WARNING] Cannot raise the priority of the engine thread");
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn raise_thread_priority() {}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::Engine;
+    use engine::Controller;
+    use looper::{Looper, Metronome};
+    use midi::{DummyMidiNoteTracker, AbsMidiEvent};
+
+    struct DummyController;
+
+    impl Controller for DummyController {
+        type NoteTracker = DummyMidiNoteTracker;
+        type Reply = bool;
+
+        fn handle_midi_event(&mut self, _: &mut Looper<DummyMidiNoteTracker>, _: &AbsMidiEvent) -> Option<bool> {
+            None
+        }
+
+        fn update(&mut self, _: &mut Looper<DummyMidiNoteTracker>, _: u32) -> Option<bool> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_execute() {
+        let looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let mut engine = Engine::spawn(looper, DummyController, None).unwrap();
+        assert!(!engine.snapshot().recording);
+
+        engine.execute(|_, looper| {
+            looper.toggle_recording();
+            Some(looper.is_recording())
+        });
+        let start = Instant::now();
+        let mut reply = None;
+        while reply.is_none() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            thread::yield_now();
+            reply = engine.take_reply();
+        }
+        assert_eq!(Some(true), reply);
+
+        // The snapshots published before the command still show the
+        // looper not recording
+        while !engine.snapshot().recording {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            thread::yield_now();
+            engine.update_snapshot();
+        }
+    }
+}
@@ -0,0 +1,7 @@
+mod engine;
+mod controller;
+mod queue;
+mod triple_buffer;
+
+pub use self::engine::Engine;
+pub use self::controller::Controller;
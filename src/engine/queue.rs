@@ -0,0 +1,176 @@
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The slots shared by the two ends of the queue. `head` is the
+/// amount of the values taken so far and `tail` the amount of the
+/// values put, so only the consumer moves the head and only the
+/// producer moves the tail.
+struct Buffer<T> {
+    slots: Vec<UnsafeCell<Option<T>>>,
+    head: AtomicUsize,
+    tail: AtomicUsize,
+    /// One of the ends is dropped
+    abandoned: AtomicBool,
+}
+
+unsafe impl<T: Send> Sync for Buffer<T> {}
+
+impl<T> Buffer<T> {
+    fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire) == self.tail.load(Ordering::Acquire)
+    }
+}
+
+/// Puts the values to the queue. It is not `Sync`, so only one thread
+/// puts them.
+pub struct Producer<T> {
+    buffer: Arc<Buffer<T>>,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+/// Takes the values from the queue. It is not `Sync`, so only one
+/// thread takes them.
+pub struct Consumer<T> {
+    buffer: Arc<Buffer<T>>,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+/// Bounded single producer single consumer queue. Neither end ever
+/// waits for a lock or allocates, so the engine thread uses it to talk
+/// to the UI.
+pub fn queue<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let buffer = Arc::new(Buffer {
+        slots: (0..capacity).map(|_| UnsafeCell::new(None)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        abandoned: AtomicBool::new(false),
+    });
+
+    (Producer { buffer: buffer.clone(), _not_sync: PhantomData },
+     Consumer { buffer: buffer, _not_sync: PhantomData })
+}
+
+impl<T> Producer<T> {
+    /// Puts the value to the end of the queue. Gives the value back if
+    /// the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let buffer = &*self.buffer;
+        let tail = buffer.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(buffer.head.load(Ordering::Acquire)) == buffer.slots.len() {
+            return Err(value);
+        }
+
+        // The consumer does not touch the slot until the tail is moved
+        // past it
+        unsafe {
+            *buffer.slots[tail % buffer.slots.len()].get() = Some(value);
+        }
+        buffer.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// The consumer is dropped, so nobody takes the values anymore
+    pub fn is_abandoned(&self) -> bool {
+        self.buffer.abandoned.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Takes the value from the beginning of the queue
+    pub fn pop(&self) -> Option<T> {
+        let buffer = &*self.buffer;
+        let head = buffer.head.load(Ordering::Relaxed);
+        if head == buffer.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // The producer does not touch the slot until the head is moved
+        // past it
+        let value = unsafe {
+            (*buffer.slots[head % buffer.slots.len()].get()).take()
+        };
+        buffer.head.store(head.wrapping_add(1), Ordering::Release);
+        value
+    }
+
+    /// The producer is dropped and all of its values are taken
+    pub fn is_abandoned(&self) -> bool {
+        self.buffer.abandoned.load(Ordering::Acquire) && self.buffer.is_empty()
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.buffer.abandoned.store(true, Ordering::Release);
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.buffer.abandoned.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::queue;
+
+    #[test]
+    fn test_bounded_fifo() {
+        let (producer, consumer) = queue(2);
+
+        assert_eq!(Ok(()), producer.push(1));
+        assert_eq!(Ok(()), producer.push(2));
+        assert_eq!(Err(3), producer.push(3));
+        assert_eq!(Some(1), consumer.pop());
+        assert_eq!(Ok(()), producer.push(3));
+        assert_eq!(Some(2), consumer.pop());
+        assert_eq!(Some(3), consumer.pop());
+        assert_eq!(None, consumer.pop());
+    }
+
+    #[test]
+    fn test_abandoned() {
+        let (producer, consumer) = queue(2);
+
+        producer.push(1).unwrap();
+        drop(producer);
+        assert!(!consumer.is_abandoned());
+        assert_eq!(Some(1), consumer.pop());
+        assert!(consumer.is_abandoned());
+
+        let (producer, consumer) = queue::<u32>(2);
+        drop(consumer);
+        assert!(producer.is_abandoned());
+    }
+
+    #[test]
+    fn test_across_threads() {
+        let (producer, consumer) = queue(4);
+        let thread = thread::spawn(move || {
+            for value in 0..1000 {
+                let mut value = value;
+                while let Err(rejected) = producer.push(value) {
+                    value = rejected;
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut expected = 0;
+        while expected < 1000 {
+            match consumer.pop() {
+                Some(value) => {
+                    assert_eq!(expected, value);
+                    expected += 1;
+                },
+                None => thread::yield_now(),
+            }
+        }
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,102 @@
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The middle slot holds a value the reader has not seen yet
+const FRESH: usize = 4;
+
+/// Three slots: the writer owns one, the reader owns another and the
+/// third one is exchanged between them. `middle` is the index of the
+/// exchanged slot with the `FRESH` flag.
+struct Slots<T> {
+    slots: [UnsafeCell<T>; 3],
+    middle: AtomicUsize,
+}
+
+unsafe impl<T: Send> Sync for Slots<T> {}
+
+/// Publishes the values for the reader
+pub struct Writer<T> {
+    slots: Arc<Slots<T>>,
+    back: usize,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+/// Reads the latest of the published values
+pub struct Reader<T> {
+    slots: Arc<Slots<T>>,
+    front: usize,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+/// The slot holding only the latest value. The writer never waits for
+/// the reader and the slots are reused, so the writer can fill them in
+/// place without allocating.
+pub fn triple_buffer<T: Clone>(value: T) -> (Writer<T>, Reader<T>) {
+    let slots = Arc::new(Slots {
+        slots: [UnsafeCell::new(value.clone()), UnsafeCell::new(value.clone()), UnsafeCell::new(value)],
+        middle: AtomicUsize::new(1),
+    });
+
+    (Writer { slots: slots.clone(), back: 2, _not_sync: PhantomData },
+     Reader { slots: slots, front: 0, _not_sync: PhantomData })
+}
+
+impl<T> Writer<T> {
+    /// The slot to fill before publishing it. It holds one of the
+    /// values published earlier.
+    pub fn back(&mut self) -> &mut T {
+        // The slot is owned by the writer until it is published
+        unsafe { &mut *self.slots.slots[self.back].get() }
+    }
+
+    pub fn publish(&mut self) {
+        let previous = self.slots.middle.swap(self.back | FRESH, Ordering::AcqRel);
+        self.back = previous & !FRESH;
+    }
+}
+
+impl<T> Reader<T> {
+    /// Takes the latest published value if there is a new one
+    pub fn update(&mut self) -> bool {
+        if self.slots.middle.load(Ordering::Acquire) & FRESH == 0 {
+            return false;
+        }
+
+        let previous = self.slots.middle.swap(self.front, Ordering::AcqRel);
+        self.front = previous & !FRESH;
+        true
+    }
+
+    pub fn read(&self) -> &T {
+        // The slot is owned by the reader until it takes another one
+        unsafe { &*self.slots.slots[self.front].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::triple_buffer;
+
+    #[test]
+    fn test_latest_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert!(!reader.update());
+        assert_eq!(0, *reader.read());
+
+        *writer.back() = 1;
+        writer.publish();
+        *writer.back() = 2;
+        writer.publish();
+        assert_eq!(0, *reader.read());
+        assert!(reader.update());
+        assert_eq!(2, *reader.read());
+        assert!(!reader.update());
+
+        *writer.back() = 3;
+        writer.publish();
+        assert!(reader.update());
+        assert_eq!(3, *reader.read());
+    }
+}
@@ -1,14 +1,92 @@
+use measure::NoteCondition;
+
 pub const CONTROL_CHANNEL_NUMBER: u8 = 9;
 
 pub const DEFAULT_TEMPO_BPM: u32 = 120;
+pub const MIN_TEMPO_BPM: u32 = 30;
+pub const MAX_TEMPO_BPM: u32 = 300;
+
+/// Lengths of the tempo ramps in bars. Zero changes the tempo at once.
+pub const TEMPO_RAMP_BARS: [u32; 6] = [0, 1, 2, 4, 8, 16];
+pub const MIDI_CLOCKS_PER_QUARTER_NOTE: u32 = 24;
+
+pub const TAP_TEMPO_TIMEOUT: u32 = 2000;
+pub const TAP_TEMPO_MAX_TAPS: usize = 8;
+pub const TAP_TEMPO_TOLERANCE_PERCENT: u32 = 20;
 pub const DEFAULT_MEASURE_SIZE_BPM: u32 = 4;
-pub const DEFAULT_QUANTATION_LEVEL: u32 = 2;
+pub const DEFAULT_BEAT_UNIT: u32 = 4;
+pub const DEFAULT_BEAT_DIVISION: u32 = 4;
+/// Divisible by every one of the `BEAT_DIVISIONS`
+pub const QUANTS_PER_BEAT: u32 = 24;
+
+pub const BEAT_DIVISIONS: [u32; 6] = [1, 2, 3, 4, 6, 8];
+/// Resolution of the microtiming of the recorded events
+pub const TICKS_PER_QUANT: u32 = 20;
+pub const TICKS_PER_BEAT: u32 = QUANTS_PER_BEAT * TICKS_PER_QUANT;
+pub const QUANTIZE_STRENGTH_STEP: u32 = 10;
+/// Amount of the sample edits that can be undone
+pub const EDIT_HISTORY_LIMIT: usize = 32;
+/// Key ranges the drum samples are split by: kicks, snares and claps,
+/// hi-hats. The rest of the kit goes to a separate sample.
+pub const SPLIT_KEY_RANGES: [(u8, u8); 3] = [(35, 36), (37, 40), (42, 46)];
+/// Conditions of the sample notes in the order they are switched in
+pub const NOTE_CONDITIONS: [NoteCondition; 8] = [
+    NoteCondition::Always,
+    NoteCondition::Probability(75),
+    NoteCondition::Probability(50),
+    NoteCondition::Probability(25),
+    NoteCondition::Cycle { cycle: 1, period: 2 },
+    NoteCondition::Cycle { cycle: 2, period: 2 },
+    NoteCondition::Cycle { cycle: 1, period: 4 },
+    NoteCondition::Cycle { cycle: 4, period: 4 },
+];
+pub const NOTE_CONDITION_SEED: [u32; 4] = [0x193A6754, 0xA8A7D469, 0x97830E05, 0x113BA7BB];
+
+pub const SWING_BEAT_DIVISION: u32 = 2;
+pub const SWING_PERCENT_STEP: u32 = 5;
+pub const MAX_SWING_PERCENT: u32 = 75;
+pub const GROOVE_BEAT_DIVISION: u32 = 4;
+
+pub const DEFAULT_ARPEGGIATOR_GATE_PERCENT: u32 = 50;
+pub const ARPEGGIATOR_GATE_PERCENT_STEP: u32 = 10;
+pub const ARPEGGIATOR_MAX_OCTAVES: u32 = 4;
+pub const ARPEGGIATOR_RANDOM_SEED: u32 = 0x2545F491;
+
+/// Hits per beat: eighths, eighth triplets, sixteenths, sixteenth
+/// triplets, thirty-seconds and thirty-second triplets
+pub const NOTE_REPEAT_RATES: [u32; 6] = [2, 3, 4, 6, 8, 12];
+pub const DEFAULT_NOTE_REPEAT_RATE: u32 = 4;
+
+/// Major triad
+pub const DEFAULT_CHORD_SHAPE: [u8; 3] = [0, 4, 7];
+
+pub const STEP_SEQUENCER_ROWS: usize = 8;
+/// Rows of a new pattern: the kick, the snare, the closed and the
+/// open hi-hat, the clap, the low and the high tom and the crash of
+/// the General MIDI drums
+pub const STEP_SEQUENCER_DEFAULT_KEYS: [u8; STEP_SEQUENCER_ROWS] = [36, 38, 42, 46, 39, 45, 48, 49];
+pub const DEFAULT_STEP_VELOCITY: u8 = 100;
+pub const STEP_VELOCITY_STEP: u8 = 8;
 
 pub const BEAT_KEY_NUMBER: u8 = 62;
+/// Version of the format of the saved compositions. The compositions
+/// without it may have the metronome as their first sample.
+pub const COMPOSITION_FORMAT_VERSION: u32 = 1;
 pub const BEAT_VELOCITY: u8 = 60;
 pub const BEAT_ACCENT_VELOCITY: u8 = 100;
+pub const BEAT_SUBDIVISION_VELOCITY: u8 = 30;
+
+pub const TIME_SIGNATURES: [(u32, u32); 6] = [(4, 4), (3, 4), (5, 4), (6, 8), (7, 8), (12, 8)];
 
 pub const EVENT_LOOP_SLEEP_TIMEOUT: u64 = 3;
+pub const ENGINE_SLEEP_TIMEOUT: u64 = 1;
+/// How often the engine publishes the state of the looper for the UI
+pub const ENGINE_SNAPSHOT_PERIOD: u32 = 16;
+/// Capacity of the queues between the engine thread and the UI
+pub const ENGINE_QUEUE_SIZE: usize = 256;
+/// How many MIDI events the engine reads at once before updating the
+/// looper
+pub const ENGINE_MIDI_READ_LIMIT: usize = 1024;
 pub const CONTROL_KEY_NUMBER: u8 = 51;
 
 pub const RATIO_WIDTH: u32 = 16;
@@ -17,8 +95,14 @@ pub const RATIO_FACTOR: u32 = 90;
 
 pub const AMOUNT_OF_MIDI_CHANNELS: usize = 16;
 pub const AMOUNT_OF_MIDI_KEYS: usize = 128;
+pub const AMOUNT_OF_MIDI_CONTROLS: u8 = 128;
+pub const PITCH_BEND_CENTER: u16 = 8192;
+pub const PITCH_BEND_MAX: u16 = 16383;
 
 pub const TEMPO_CHANGE_CONTROL_NUMBER: u8 = 21;
+pub const DEFAULT_TEMPO_CONTROL_MIN_BPM: u32 = 90;
+pub const DEFAULT_TEMPO_CONTROL_MAX_BPM: u32 = 217;
+pub const GRID_CHANGE_CONTROL_NUMBER: u8 = 22;
 
 pub const TTF_FONT_PATH: &'static str = "fonts/minoru.ttf";
 pub const POPUP_FADEOUT_TIME: u32 = 500;
@@ -37,3 +121,26 @@ pub const KEYBOARD_MESSAGE_CHANNEL: u8 = 2;
 pub const VIRTUAL_KEY_WIDTH: u32 = 100;
 pub const VIRTUAL_KEY_HEIGHT: u32 = 100;
 pub const VIRTUAL_KEY_SPACING: i32 = 10;
+
+/// Width of the column showing the keys of the step sequencer rows
+/// in pixels
+pub const STEP_SEQUENCER_KEY_WIDTH: u32 = 40;
+
+/// The narrowest step of the step sequencer in pixels. The steps that
+/// do not fit are shown page by page.
+pub const STEP_SEQUENCER_MIN_STEP_WIDTH: u32 = 12;
+
+/// Height of the automation lane under the piano roll in pixels
+pub const AUTOMATION_LANE_HEIGHT: u32 = 120;
+/// The modulation wheel
+pub const DEFAULT_AUTOMATION_CONTROL_NUMBER: u8 = 1;
+
+pub const CLIP_LAUNCHER_CHANNEL: u8 = 9;
+pub const CLIP_LAUNCHER_FIRST_KEY: u8 = 36;
+pub const CLIP_LAUNCHER_COLUMNS: usize = 4;
+pub const CLIP_LAUNCHER_ROWS: usize = 3;
+
+pub const CLIP_LED_EMPTY_VELOCITY: u8 = 0;
+pub const CLIP_LED_STOPPED_VELOCITY: u8 = 15;
+pub const CLIP_LED_PLAYING_VELOCITY: u8 = 60;
+pub const CLIP_LED_QUEUED_VELOCITY: u8 = 62;
@@ -0,0 +1,24 @@
+// FIXME(#139): Deny warnings only on CI
+// #![deny(warnings)]
+
+//! The looper engine: the state machine of the looper, the samples,
+//! the measure and the MIDI. It knows nothing about SDL, so front-ends
+//! render its state on their own.
+
+extern crate portmidi as pm;
+extern crate num;
+extern crate libc;
+extern crate rand;
+
+extern crate serde;
+extern crate serde_json;
+#[macro_use]
+extern crate serde_derive;
+
+pub mod looper;
+pub mod engine;
+pub mod traits;
+pub mod midi;
+pub mod hardcode;
+pub mod measure;
+pub mod error;
@@ -0,0 +1,156 @@
+use looper::CompositionData;
+
+/// The moment when a scheduled action is performed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LaunchQuantization {
+    Immediate,
+    NextBeat,
+    NextBar,
+    /// At the given measure bar counting from the next one
+    AfterBars(u32),
+    /// At the end of the given amount of the looper cycles
+    AfterLoops(u32),
+}
+
+impl LaunchQuantization {
+    /// The next quantization in the order they are switched by the
+    /// user
+    pub fn next(&self) -> LaunchQuantization {
+        match *self {
+            LaunchQuantization::Immediate => LaunchQuantization::NextBeat,
+            LaunchQuantization::NextBeat => LaunchQuantization::NextBar,
+            LaunchQuantization::NextBar | LaunchQuantization::AfterBars(_) => LaunchQuantization::AfterLoops(1),
+            LaunchQuantization::AfterLoops(_) => LaunchQuantization::Immediate,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Action {
+    StartRecording,
+    StopRecording,
+    UndoLastRecording,
+    ToggleLastSampleMute,
+    ChangeTempo(u32),
+    /// Changes the tempo gradually over the given amount of bars
+    RampTempo(u32, u32),
+    SwitchScene(usize),
+    LoadComposition(CompositionData),
+    LaunchSample(usize),
+    StopSample(usize),
+}
+
+/// The action without its payload, all the front-end needs to show
+/// it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActionKind {
+    StartRecording,
+    StopRecording,
+    UndoLastRecording,
+    ToggleLastSampleMute,
+    ChangeTempo,
+    RampTempo,
+    SwitchScene(usize),
+    LoadComposition,
+    LaunchSample(usize),
+    StopSample(usize),
+}
+
+impl Action {
+    pub fn kind(&self) -> ActionKind {
+        match *self {
+            Action::StartRecording => ActionKind::StartRecording,
+            Action::StopRecording => ActionKind::StopRecording,
+            Action::UndoLastRecording => ActionKind::UndoLastRecording,
+            Action::ToggleLastSampleMute => ActionKind::ToggleLastSampleMute,
+            Action::ChangeTempo(_) => ActionKind::ChangeTempo,
+            Action::RampTempo(..) => ActionKind::RampTempo,
+            Action::SwitchScene(scene) => ActionKind::SwitchScene(scene),
+            Action::LoadComposition(_) => ActionKind::LoadComposition,
+            Action::LaunchSample(index) => ActionKind::LaunchSample(index),
+            Action::StopSample(index) => ActionKind::StopSample(index),
+        }
+    }
+
+    /// Whether the action makes the pending `other` action pointless,
+    /// e.g. only the last of the several tempo changes matters.
+    pub fn supersedes(&self, other: &Action) -> bool {
+        match (self, other) {
+            (&Action::StartRecording, &Action::StartRecording) |
+            (&Action::StopRecording, &Action::StopRecording) |
+            (&Action::ChangeTempo(_), &Action::ChangeTempo(_)) |
+            (&Action::ChangeTempo(_), &Action::RampTempo(..)) |
+            (&Action::RampTempo(..), &Action::ChangeTempo(_)) |
+            (&Action::RampTempo(..), &Action::RampTempo(..)) |
+            (&Action::SwitchScene(_), &Action::SwitchScene(_)) |
+            (&Action::LoadComposition(_), &Action::LoadComposition(_)) => true,
+            (&Action::LaunchSample(a), &Action::LaunchSample(b)) |
+            (&Action::LaunchSample(a), &Action::StopSample(b)) |
+            (&Action::StopSample(a), &Action::LaunchSample(b)) |
+            (&Action::StopSample(a), &Action::StopSample(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PendingAction {
+    pub action: Action,
+    pub quantization: LaunchQuantization,
+}
+
+impl PendingAction {
+    /// Checks whether the action should be launched at the beat
+    /// boundary. Counts down the loops of `AfterLoops` quantization.
+    pub fn on_beat(&mut self, measure_bar: bool, loop_end: bool) -> bool {
+        match self.quantization {
+            LaunchQuantization::Immediate | LaunchQuantization::NextBeat => true,
+            LaunchQuantization::NextBar => measure_bar,
+            LaunchQuantization::AfterBars(bars) => {
+                if measure_bar && bars > 1 {
+                    self.quantization = LaunchQuantization::AfterBars(bars - 1);
+                    false
+                } else {
+                    measure_bar
+                }
+            }
+            LaunchQuantization::AfterLoops(loops) => {
+                if loop_end && loops > 1 {
+                    self.quantization = LaunchQuantization::AfterLoops(loops - 1);
+                    false
+                } else {
+                    loop_end
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, PendingAction, LaunchQuantization};
+
+    #[test]
+    fn test_after_loops_countdown() {
+        let mut pending_action = PendingAction {
+            action: Action::UndoLastRecording,
+            quantization: LaunchQuantization::AfterLoops(2),
+        };
+
+        assert!(!pending_action.on_beat(true, false));
+        assert!(!pending_action.on_beat(true, true));
+        assert!(!pending_action.on_beat(false, false));
+        assert!(pending_action.on_beat(true, true));
+    }
+
+    #[test]
+    fn test_supersedes() {
+        assert!(Action::ChangeTempo(100).supersedes(&Action::ChangeTempo(120)));
+        assert!(Action::SwitchScene(1).supersedes(&Action::SwitchScene(0)));
+        assert!(!Action::UndoLastRecording.supersedes(&Action::UndoLastRecording));
+        assert!(!Action::ChangeTempo(100).supersedes(&Action::SwitchScene(0)));
+        assert!(Action::RampTempo(140, 8).supersedes(&Action::ChangeTempo(120)));
+        assert!(Action::LaunchSample(1).supersedes(&Action::StopSample(1)));
+        assert!(!Action::LaunchSample(1).supersedes(&Action::StopSample(2)));
+    }
+}
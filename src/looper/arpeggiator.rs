@@ -0,0 +1,389 @@
+use std::cmp;
+
+use hardcode::*;
+use midi::TypedMidiMessage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArpeggiatorMode {
+    Up,
+    Down,
+    UpDown,
+    Random,
+}
+
+const ARPEGGIATOR_MODES: [ArpeggiatorMode; 4] = [ArpeggiatorMode::Up,
+                                                 ArpeggiatorMode::Down,
+                                                 ArpeggiatorMode::UpDown,
+                                                 ArpeggiatorMode::Random];
+
+impl ArpeggiatorMode {
+    pub fn next(&self) -> ArpeggiatorMode {
+        ARPEGGIATOR_MODES[(self.number() + 1) % ARPEGGIATOR_MODES.len()]
+    }
+
+    /// Position of the mode in the order they are switched in
+    pub fn number(&self) -> usize {
+        ARPEGGIATOR_MODES.iter().position(|mode| mode == self).unwrap()
+    }
+}
+
+/// Setting of the arpeggiator a MIDI control can be learned for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArpeggiatorParameter {
+    Enabled,
+    Mode,
+    Rate,
+    Octaves,
+    Gate,
+    Latch,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct HeldNote {
+    channel: u8,
+    key: u8,
+    velocity: u8,
+}
+
+/// Plays the held notes one by one in steps of the quant grid. The
+/// notes played to the looper while it is enabled are taken by the
+/// arpeggiator and the looper plays and records the notes generated by
+/// it instead.
+pub struct Arpeggiator {
+    pub enabled: bool,
+    pub mode: ArpeggiatorMode,
+    /// Amount of steps per beat, one of `BEAT_DIVISIONS`
+    pub rate: u32,
+    /// Amount of octaves the held notes are repeated over
+    pub octaves: u32,
+    /// Length of the generated notes in percent of the step
+    pub gate: u32,
+    /// Keep playing the notes after they are released until a new
+    /// chord is played
+    pub latch: bool,
+
+    /// Notes that are arpeggiated sorted by their keys
+    notes: Vec<HeldNote>,
+    /// Keys that are actually held
+    pressed_keys: Vec<(u8, u8)>,
+    step: usize,
+    sounding_note: Option<HeldNote>,
+    /// Ticks left until the sounding note is released
+    gate_ticks_left: u32,
+    random_state: u32,
+}
+
+impl Default for Arpeggiator {
+    fn default() -> Arpeggiator {
+        Arpeggiator {
+            enabled: false,
+            mode: ArpeggiatorMode::Up,
+            rate: DEFAULT_BEAT_DIVISION,
+            octaves: 1,
+            gate: DEFAULT_ARPEGGIATOR_GATE_PERCENT,
+            latch: false,
+            notes: Vec::new(),
+            pressed_keys: Vec::new(),
+            step: 0,
+            sounding_note: None,
+            gate_ticks_left: 0,
+            random_state: ARPEGGIATOR_RANDOM_SEED,
+        }
+    }
+}
+
+impl Arpeggiator {
+    /// Takes the note played to the looper. Returns false if the
+    /// message is not for the arpeggiator.
+    pub fn handle_message(&mut self, message: &TypedMidiMessage) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        match message.normalized() {
+            TypedMidiMessage::NoteOn { channel, key, velocity } => {
+                // The first key of a new chord replaces the latched
+                // notes
+                if self.latch && self.pressed_keys.is_empty() {
+                    self.notes.clear();
+                }
+
+                self.pressed_keys.push((channel, key));
+                let note = HeldNote { channel: channel, key: key, velocity: velocity };
+                match self.notes.binary_search_by_key(&key, |note| note.key) {
+                    Ok(index) => self.notes[index] = note,
+                    Err(index) => self.notes.insert(index, note),
+                }
+                true
+            },
+
+            TypedMidiMessage::NoteOff { channel, key, .. } => {
+                self.pressed_keys.retain(|&pressed_key| pressed_key != (channel, key));
+                if !self.latch {
+                    self.notes.retain(|note| note.key != key);
+                }
+                true
+            },
+
+            _ => false,
+        }
+    }
+
+    /// Turns the arpeggiator on or off. The note it is playing is
+    /// released on the next advance.
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.notes.clear();
+        self.pressed_keys.clear();
+    }
+
+    pub fn toggle_latch(&mut self) {
+        self.latch = !self.latch;
+        if !self.latch {
+            let pressed_keys = &self.pressed_keys;
+            self.notes.retain(|note| pressed_keys.contains(&(note.channel, note.key)));
+        }
+    }
+
+    pub fn switch_rate(&mut self) -> u32 {
+        let next = BEAT_DIVISIONS.iter()
+            .position(|rate| *rate == self.rate)
+            .map_or(0, |index| (index + 1) % BEAT_DIVISIONS.len());
+        self.rate = BEAT_DIVISIONS[next];
+        self.rate
+    }
+
+    pub fn set_octaves(&mut self, octaves: u32) {
+        self.octaves = cmp::max(1, cmp::min(octaves, ARPEGGIATOR_MAX_OCTAVES));
+    }
+
+    pub fn set_gate(&mut self, gate: u32) {
+        self.gate = cmp::max(ARPEGGIATOR_GATE_PERCENT_STEP, cmp::min(gate, 100));
+    }
+
+    /// Sets the parameter to the value of the MIDI control and
+    /// returns the resulting value of the parameter
+    pub fn set_parameter(&mut self, parameter: ArpeggiatorParameter, value: u8) -> u32 {
+        let on = value >= 64;
+        match parameter {
+            ArpeggiatorParameter::Enabled => {
+                if self.enabled != on {
+                    self.toggle();
+                }
+                self.enabled as u32
+            },
+            ArpeggiatorParameter::Mode => {
+                self.mode = ARPEGGIATOR_MODES[value as usize * ARPEGGIATOR_MODES.len() / 128];
+                self.mode.number() as u32
+            },
+            ArpeggiatorParameter::Rate => {
+                self.rate = BEAT_DIVISIONS[value as usize * BEAT_DIVISIONS.len() / 128];
+                self.rate
+            },
+            ArpeggiatorParameter::Octaves => {
+                self.set_octaves(1 + value as u32 * ARPEGGIATOR_MAX_OCTAVES / 128);
+                self.octaves
+            },
+            ArpeggiatorParameter::Gate => {
+                self.set_gate(value as u32 * 100 / 127);
+                self.gate
+            },
+            ArpeggiatorParameter::Latch => {
+                if self.latch != on {
+                    self.toggle_latch();
+                }
+                self.latch as u32
+            },
+        }
+    }
+
+    fn step_ticks(&self) -> u32 {
+        QUANTS_PER_BEAT / self.rate * TICKS_PER_QUANT
+    }
+
+    /// Generates the messages for the looper moving from the tick
+    /// `from` (exclusively) to the tick `to` (inclusively). The steps
+    /// are aligned to the beats.
+    pub fn advance(&mut self, from: u32, to: u32) -> Vec<TypedMidiMessage> {
+        if !self.enabled {
+            return self.release();
+        }
+
+        let mut messages = Vec::new();
+
+        if self.sounding_note.is_some() {
+            if self.gate_ticks_left <= to - from {
+                messages.extend(self.release());
+            } else {
+                self.gate_ticks_left -= to - from;
+            }
+        }
+
+        let step_ticks = self.step_ticks();
+        if from / step_ticks != to / step_ticks {
+            messages.extend(self.release());
+
+            if let Some(note) = self.next_note() {
+                messages.push(TypedMidiMessage::NoteOn {
+                    channel: note.channel,
+                    key: note.key,
+                    velocity: note.velocity,
+                });
+                self.sounding_note = Some(note);
+                self.gate_ticks_left = cmp::max(1, step_ticks * self.gate / 100).saturating_sub(to % step_ticks);
+            }
+        }
+
+        messages
+    }
+
+    fn release(&mut self) -> Vec<TypedMidiMessage> {
+        self.sounding_note.take().map(|note| {
+            TypedMidiMessage::NoteOff {
+                channel: note.channel,
+                key: note.key,
+                velocity: 0,
+            }
+        }).into_iter().collect()
+    }
+
+    fn next_note(&mut self) -> Option<HeldNote> {
+        let length = self.notes.len() * self.octaves as usize;
+        if length == 0 {
+            return None;
+        }
+
+        let index = match self.mode {
+            ArpeggiatorMode::Up => self.step % length,
+            ArpeggiatorMode::Down => length - 1 - self.step % length,
+            ArpeggiatorMode::UpDown => {
+                // The highest and the lowest notes are not repeated
+                // on the turns
+                let period = cmp::max(1, 2 * length - 2);
+                let position = self.step % period;
+                if position < length { position } else { period - position }
+            },
+            ArpeggiatorMode::Random => self.random() as usize % length,
+        };
+        self.step += 1;
+
+        let mut note = self.notes[index % self.notes.len()];
+        let octave = (index / self.notes.len()) as u8;
+        note.key = cmp::min(127, note.key as u32 + 12 * octave as u32) as u8;
+        Some(note)
+    }
+
+    /// Xorshift is good enough to pick the notes
+    fn random(&mut self) -> u32 {
+        self.random_state ^= self.random_state << 13;
+        self.random_state ^= self.random_state >> 17;
+        self.random_state ^= self.random_state << 5;
+        self.random_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arpeggiator, ArpeggiatorMode, ArpeggiatorParameter};
+    use hardcode::*;
+    use midi::TypedMidiMessage;
+
+    fn note_on(key: u8) -> TypedMidiMessage {
+        TypedMidiMessage::NoteOn { channel: 0, key: key, velocity: 100 }
+    }
+
+    fn note_off(key: u8) -> TypedMidiMessage {
+        TypedMidiMessage::NoteOff { channel: 0, key: key, velocity: 0 }
+    }
+
+    /// Keys of the notes played within the given amount of steps
+    fn play_steps(arpeggiator: &mut Arpeggiator, steps: u32) -> Vec<u8> {
+        let step_ticks = arpeggiator.step_ticks();
+        let mut keys = Vec::new();
+        for tick in 0..steps * step_ticks {
+            for message in arpeggiator.advance(tick, tick + 1) {
+                if let TypedMidiMessage::NoteOn { key, .. } = message {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut arpeggiator = Arpeggiator::default();
+        arpeggiator.toggle();
+        for key in &[64, 60, 67] {
+            assert!(arpeggiator.handle_message(&note_on(*key)));
+        }
+
+        assert_eq!(vec![60, 64, 67, 60], play_steps(&mut arpeggiator, 4));
+
+        arpeggiator.mode = ArpeggiatorMode::Down;
+        arpeggiator.step = 0;
+        assert_eq!(vec![67, 64, 60, 67], play_steps(&mut arpeggiator, 4));
+
+        arpeggiator.mode = ArpeggiatorMode::UpDown;
+        arpeggiator.step = 0;
+        arpeggiator.set_octaves(2);
+        assert_eq!(vec![60, 64, 67, 72, 76, 79, 76, 72, 67, 64, 60],
+                   play_steps(&mut arpeggiator, 11));
+    }
+
+    #[test]
+    fn test_gate() {
+        let mut arpeggiator = Arpeggiator::default();
+        arpeggiator.toggle();
+        arpeggiator.set_gate(50);
+        arpeggiator.handle_message(&note_on(60));
+
+        let step_ticks = arpeggiator.step_ticks();
+        assert_eq!(vec![note_on(60)], arpeggiator.advance(step_ticks - 1, step_ticks));
+        assert!(arpeggiator.advance(step_ticks, step_ticks + step_ticks / 2 - 1).is_empty());
+        assert_eq!(vec![note_off(60)],
+                   arpeggiator.advance(step_ticks + step_ticks / 2 - 1, step_ticks + step_ticks / 2));
+    }
+
+    #[test]
+    fn test_latch() {
+        let mut arpeggiator = Arpeggiator::default();
+        arpeggiator.toggle();
+        arpeggiator.set_parameter(ArpeggiatorParameter::Latch, 127);
+
+        arpeggiator.handle_message(&note_on(60));
+        arpeggiator.handle_message(&note_off(60));
+        assert_eq!(vec![60, 60], play_steps(&mut arpeggiator, 2));
+
+        // A new chord replaces the latched one
+        arpeggiator.handle_message(&note_on(62));
+        arpeggiator.handle_message(&note_on(65));
+        arpeggiator.handle_message(&note_off(62));
+        arpeggiator.handle_message(&note_off(65));
+        arpeggiator.step = 0;
+        assert_eq!(vec![62, 65], play_steps(&mut arpeggiator, 2));
+
+        arpeggiator.toggle_latch();
+        assert!(play_steps(&mut arpeggiator, 2).is_empty());
+    }
+
+    #[test]
+    fn test_note_on_without_velocity_releases_note() {
+        let mut arpeggiator = Arpeggiator::default();
+        arpeggiator.toggle();
+
+        arpeggiator.handle_message(&note_on(60));
+        arpeggiator.handle_message(&note_on(64));
+        arpeggiator.handle_message(&TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: 0 });
+        assert_eq!(vec![64, 64], play_steps(&mut arpeggiator, 2));
+    }
+
+    #[test]
+    fn test_rate_follows_grid() {
+        let mut arpeggiator = Arpeggiator::default();
+        arpeggiator.set_parameter(ArpeggiatorParameter::Rate, 0);
+        assert_eq!(TICKS_PER_BEAT, arpeggiator.step_ticks());
+        assert_eq!(2, arpeggiator.switch_rate());
+        assert_eq!(TICKS_PER_BEAT / 2, arpeggiator.step_ticks());
+    }
+}
@@ -0,0 +1,114 @@
+use hardcode::*;
+use measure::Quant;
+use midi::TypedMidiMessage;
+
+/// Controller whose recorded values are shown and drawn in the
+/// automation lane under the piano roll
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AutomationLane {
+    ControlChange(u8),
+    PitchBend,
+}
+
+impl Default for AutomationLane {
+    fn default() -> AutomationLane {
+        AutomationLane::ControlChange(DEFAULT_AUTOMATION_CONTROL_NUMBER)
+    }
+}
+
+/// Value of the controller at the quant of the sample
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutomationPoint {
+    pub quant: Quant,
+    pub value: u16,
+}
+
+impl AutomationLane {
+    /// The lane the message belongs to and the value it sets
+    pub fn of_message(message: &TypedMidiMessage) -> Option<(AutomationLane, u16)> {
+        match *message {
+            TypedMidiMessage::ControlChange { number, value, .. } =>
+                Some((AutomationLane::ControlChange(number), value as u16)),
+            TypedMidiMessage::PitchBend { value, .. } => Some((AutomationLane::PitchBend, value)),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, message: &TypedMidiMessage) -> bool {
+        AutomationLane::of_message(message).map_or(false, |(lane, _)| lane == *self)
+    }
+
+    pub fn max_value(&self) -> u16 {
+        match *self {
+            AutomationLane::ControlChange(_) => 127,
+            AutomationLane::PitchBend => PITCH_BEND_MAX,
+        }
+    }
+
+    /// The message setting the controller to the value
+    pub fn message(&self, channel: u8, value: u16) -> TypedMidiMessage {
+        match *self {
+            AutomationLane::ControlChange(number) => TypedMidiMessage::ControlChange {
+                channel: channel,
+                number: number,
+                value: value as u8,
+            },
+            AutomationLane::PitchBend => TypedMidiMessage::PitchBend {
+                channel: channel,
+                value: value,
+            },
+        }
+    }
+
+    /// Control change number of the lane. The pitch bend goes right
+    /// after the control changes.
+    pub fn number(&self) -> u32 {
+        match *self {
+            AutomationLane::ControlChange(number) => number as u32,
+            AutomationLane::PitchBend => AMOUNT_OF_MIDI_CONTROLS as u32,
+        }
+    }
+
+    fn from_number(number: u32) -> AutomationLane {
+        if number < AMOUNT_OF_MIDI_CONTROLS as u32 {
+            AutomationLane::ControlChange(number as u8)
+        } else {
+            AutomationLane::PitchBend
+        }
+    }
+
+    pub fn next(&self) -> AutomationLane {
+        AutomationLane::from_number((self.number() + 1) % (AMOUNT_OF_MIDI_CONTROLS as u32 + 1))
+    }
+
+    pub fn previous(&self) -> AutomationLane {
+        AutomationLane::from_number((self.number() + AMOUNT_OF_MIDI_CONTROLS as u32) % (AMOUNT_OF_MIDI_CONTROLS as u32 + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AutomationLane;
+    use midi::{TypedMidiMessage, parse_midi_message};
+
+    #[test]
+    fn test_pitch_bend_message() {
+        let message = AutomationLane::PitchBend.message(3, 12345);
+        assert_eq!(Some(message), parse_midi_message(&message.into()));
+        assert_eq!(Some((AutomationLane::PitchBend, 12345)), AutomationLane::of_message(&message));
+        assert!(!AutomationLane::ControlChange(1).contains(&message));
+    }
+
+    #[test]
+    fn test_lane_order() {
+        assert_eq!(AutomationLane::PitchBend, AutomationLane::ControlChange(127).next());
+        assert_eq!(AutomationLane::ControlChange(0), AutomationLane::PitchBend.next());
+        assert_eq!(AutomationLane::PitchBend, AutomationLane::ControlChange(0).previous());
+        assert_eq!(AutomationLane::ControlChange(1),
+                   AutomationLane::of_message(&TypedMidiMessage::ControlChange {
+                       channel: 0,
+                       number: 1,
+                       value: 64,
+                   }).unwrap().0);
+    }
+}
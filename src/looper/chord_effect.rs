@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use hardcode::*;
+use midi::TypedMidiMessage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChordMode {
+    /// The notes are played as they are
+    Off,
+    /// Every note plays the learned chord shape from its key
+    Memory,
+    /// Every note plays the diatonic third and fifth above it
+    Harmonizer,
+}
+
+const CHORD_MODES: [ChordMode; 3] = [ChordMode::Off, ChordMode::Memory, ChordMode::Harmonizer];
+
+impl ChordMode {
+    pub fn next(&self) -> ChordMode {
+        CHORD_MODES[(self.number() + 1) % CHORD_MODES.len()]
+    }
+
+    /// Position of the mode in the order they are switched in
+    pub fn number(&self) -> usize {
+        CHORD_MODES.iter().position(|mode| mode == self).unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    Major,
+    NaturalMinor,
+    HarmonicMinor,
+}
+
+const SCALES: [Scale; 3] = [Scale::Major, Scale::NaturalMinor, Scale::HarmonicMinor];
+
+impl Scale {
+    pub fn next(&self) -> Scale {
+        SCALES[(self.number() + 1) % SCALES.len()]
+    }
+
+    /// Position of the scale in the order they are switched in
+    pub fn number(&self) -> usize {
+        SCALES.iter().position(|scale| scale == self).unwrap()
+    }
+
+    /// Semitones of the degrees of the scale above its root
+    pub fn degrees(&self) -> [u8; 7] {
+        match *self {
+            Scale::Major => [0, 2, 4, 5, 7, 9, 11],
+            Scale::NaturalMinor => [0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => [0, 2, 3, 5, 7, 8, 11],
+        }
+    }
+}
+
+/// Turns the single notes played to the looper into chords before
+/// they are recorded. The keys every held note has sounded are
+/// remembered, so its NoteOff releases exactly them even if the
+/// effect is reconfigured in between, and a key shared by several
+/// held chords is only released with the last of them.
+pub struct ChordEffect {
+    pub mode: ChordMode,
+    /// Pitch class of the root of the harmonizer key
+    pub root: u8,
+    pub scale: Scale,
+
+    /// Semitones of the learned chord above the played key
+    shape: Vec<u8>,
+    /// Keys played since the learning has started, or `None` if the
+    /// chord memory is not learning
+    learned_keys: Option<Vec<u8>>,
+    /// Keys sounded by the held notes
+    chords: HashMap<(u8, u8), Vec<u8>>,
+    /// Amount of the held notes sounding the key
+    sounding_keys: HashMap<(u8, u8), u32>,
+}
+
+impl Default for ChordEffect {
+    fn default() -> ChordEffect {
+        ChordEffect {
+            mode: ChordMode::Off,
+            root: 0,
+            scale: Scale::Major,
+            shape: DEFAULT_CHORD_SHAPE.to_vec(),
+            learned_keys: None,
+            chords: HashMap::new(),
+            sounding_keys: HashMap::new(),
+        }
+    }
+}
+
+impl ChordEffect {
+    /// Makes the next notes played together the chord shape of the
+    /// chord memory. The learning ends when one of them is released.
+    pub fn learn_shape(&mut self) {
+        self.learned_keys = Some(Vec::new());
+    }
+
+    pub fn is_learning(&self) -> bool {
+        self.learned_keys.is_some()
+    }
+
+    /// Moves the root of the harmonizer key by a semitone
+    pub fn change_root(&mut self, increase: bool) -> u8 {
+        self.root = if increase {
+            (self.root + 1) % 12
+        } else {
+            (self.root + 11) % 12
+        };
+        self.root
+    }
+
+    /// Transforms the message played to the looper into the messages
+    /// of the chord
+    pub fn transform(&mut self, message: TypedMidiMessage) -> Vec<TypedMidiMessage> {
+        match message {
+            TypedMidiMessage::NoteOn { channel, key, velocity } => {
+                // The note pressed again replaces its chord
+                let mut messages = self.release(channel, key);
+
+                let keys = if self.is_learning() {
+                    vec![key]
+                } else {
+                    self.chord_keys(key)
+                };
+                if let Some(ref mut learned_keys) = self.learned_keys {
+                    learned_keys.push(key);
+                }
+
+                for &chord_key in &keys {
+                    let count = self.sounding_keys.entry((channel, chord_key)).or_insert(0);
+                    *count += 1;
+                    if *count == 1 {
+                        messages.push(TypedMidiMessage::NoteOn {
+                            channel: channel,
+                            key: chord_key,
+                            velocity: velocity,
+                        });
+                    }
+                }
+                self.chords.insert((channel, key), keys);
+
+                messages
+            },
+
+            TypedMidiMessage::NoteOff { channel, key, .. } => {
+                if self.learned_keys.as_ref().map_or(false, |keys| keys.contains(&key)) {
+                    let mut learned_keys = self.learned_keys.take().unwrap();
+                    learned_keys.sort();
+                    learned_keys.dedup();
+                    let lowest_key = learned_keys[0];
+                    self.shape = learned_keys.iter().map(|key| key - lowest_key).collect();
+                }
+
+                if self.chords.contains_key(&(channel, key)) {
+                    self.release(channel, key)
+                } else {
+                    vec![message]
+                }
+            },
+
+            _ => vec![message],
+        }
+    }
+
+    fn release(&mut self, channel: u8, key: u8) -> Vec<TypedMidiMessage> {
+        let mut messages = Vec::new();
+
+        for chord_key in self.chords.remove(&(channel, key)).unwrap_or_default() {
+            let released = match self.sounding_keys.get_mut(&(channel, chord_key)) {
+                Some(count) => {
+                    *count -= 1;
+                    *count == 0
+                },
+                None => false,
+            };
+
+            if released {
+                self.sounding_keys.remove(&(channel, chord_key));
+                messages.push(TypedMidiMessage::NoteOff {
+                    channel: channel,
+                    key: chord_key,
+                    velocity: 0,
+                });
+            }
+        }
+
+        messages
+    }
+
+    /// Keys of the chord the key plays in the current mode. The keys
+    /// above the MIDI range are left out.
+    fn chord_keys(&self, key: u8) -> Vec<u8> {
+        let intervals = match self.mode {
+            ChordMode::Off => vec![0],
+            ChordMode::Memory => self.shape.clone(),
+            ChordMode::Harmonizer => {
+                let third = self.diatonic_interval(key, 2);
+                let fifth = self.diatonic_interval(key, 4);
+                vec![0, third, fifth]
+            },
+        };
+
+        intervals.iter()
+            .map(|interval| key as u32 + *interval as u32)
+            .filter(|chord_key| *chord_key < AMOUNT_OF_MIDI_KEYS as u32)
+            .map(|chord_key| chord_key as u8)
+            .collect()
+    }
+
+    /// Semitones between the key and the key the given amount of the
+    /// scale steps above it. The keys out of the scale are harmonized
+    /// like the closest scale key below them.
+    fn diatonic_interval(&self, key: u8, steps: usize) -> u8 {
+        let degrees = self.scale.degrees();
+        let pitch_class = (key + 12 - self.root) % 12;
+        let degree = degrees.iter().rposition(|semitones| *semitones <= pitch_class).unwrap();
+        let target = degree + steps;
+        let target_semitones = degrees[target % degrees.len()] + 12 * (target / degrees.len()) as u8;
+        target_semitones - degrees[degree]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChordEffect, ChordMode, Scale};
+    use midi::TypedMidiMessage;
+
+    fn note_on(key: u8) -> TypedMidiMessage {
+        TypedMidiMessage::NoteOn { channel: 0, key: key, velocity: 100 }
+    }
+
+    fn note_off(key: u8) -> TypedMidiMessage {
+        TypedMidiMessage::NoteOff { channel: 0, key: key, velocity: 0 }
+    }
+
+    #[test]
+    fn test_chord_memory() {
+        let mut chord_effect = ChordEffect::default();
+        chord_effect.mode = ChordMode::Memory;
+        chord_effect.learn_shape();
+
+        // The learned notes are played as they are
+        assert_eq!(vec![note_on(62)], chord_effect.transform(note_on(62)));
+        assert_eq!(vec![note_on(65)], chord_effect.transform(note_on(65)));
+        assert_eq!(vec![note_on(69)], chord_effect.transform(note_on(69)));
+        assert_eq!(vec![note_off(65)], chord_effect.transform(note_off(65)));
+        assert!(!chord_effect.is_learning());
+        assert_eq!(vec![note_off(62)], chord_effect.transform(note_off(62)));
+        assert_eq!(vec![note_off(69)], chord_effect.transform(note_off(69)));
+
+        assert_eq!(vec![note_on(50), note_on(53), note_on(57)],
+                   chord_effect.transform(note_on(50)));
+        assert_eq!(vec![note_off(50), note_off(53), note_off(57)],
+                   chord_effect.transform(note_off(50)));
+    }
+
+    #[test]
+    fn test_harmonizer() {
+        let mut chord_effect = ChordEffect::default();
+        chord_effect.mode = ChordMode::Harmonizer;
+
+        assert_eq!(vec![60, 64, 67], chord_effect.chord_keys(60));
+        assert_eq!(vec![62, 65, 69], chord_effect.chord_keys(62));
+        assert_eq!(vec![71, 74, 77], chord_effect.chord_keys(71));
+        // Out of the scale
+        assert_eq!(vec![61, 65, 68], chord_effect.chord_keys(61));
+
+        chord_effect.root = 9;
+        chord_effect.scale = Scale::HarmonicMinor;
+        assert_eq!(vec![57, 60, 64], chord_effect.chord_keys(57));
+        assert_eq!(vec![64, 68, 71], chord_effect.chord_keys(64));
+
+        // Above the MIDI range
+        assert_eq!(vec![124], chord_effect.chord_keys(124));
+    }
+
+    #[test]
+    fn test_note_offs_are_paired() {
+        let mut chord_effect = ChordEffect::default();
+        chord_effect.mode = ChordMode::Harmonizer;
+
+        assert_eq!(vec![note_on(60), note_on(64), note_on(67)],
+                   chord_effect.transform(note_on(60)));
+        // The shared keys are not played again
+        assert_eq!(vec![note_on(71)], chord_effect.transform(note_on(64)));
+
+        // Reconfiguring doesn't change the keys to release
+        chord_effect.mode = ChordMode::Off;
+        assert_eq!(vec![note_off(60)], chord_effect.transform(note_off(60)));
+        assert_eq!(vec![note_off(64), note_off(67), note_off(71)],
+                   chord_effect.transform(note_off(64)));
+
+        // The notes played before are passed through
+        assert_eq!(vec![note_off(48)], chord_effect.transform(note_off(48)));
+    }
+}
@@ -0,0 +1,10 @@
+/// State of a sample in a slot of the clip launcher
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClipState {
+    Stopped,
+    Playing,
+    /// The sample is stopped, but scheduled to be launched
+    Launching,
+    /// The sample is playing, but scheduled to be stopped
+    Stopping,
+}
@@ -1,13 +1,44 @@
-use looper::{SampleData};
+use std::fs;
+use std::io::prelude::*;
+use std::path::Path;
+
+use serde_json;
+
+use looper::{SampleData, Scene, TempoAutomation};
 use measure::Measure;
+use error::Result;
 
 /// The purpose of this struct is to be serialized or deserialized by
 /// serde without implementing custom Deserialize trait, because doing
 /// that comparing to rustc_serialize is more difficult.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CompositionData {
     pub samples: Vec<SampleData>,
     pub measure: Measure,
+    #[serde(default)]
+    pub scenes: Vec<Scene>,
+    #[serde(default)]
+    pub tempo_automation: TempoAutomation,
+    /// Zero for the compositions saved before the version was saved
+    #[serde(default)]
+    pub format_version: u32,
+}
+
+impl CompositionData {
+    pub fn load(file_path: &Path) -> Result<CompositionData> {
+        let mut serialized_composition = String::new();
+        let mut file = try!(fs::File::open(file_path));
+        try!(file.read_to_string(&mut serialized_composition));
+        let composition: CompositionData = try!(serde_json::from_str(&serialized_composition));
+        Ok(composition)
+    }
+
+    pub fn save(&self, file_path: &Path) -> Result<()> {
+        let serialized_composition: String = try!(serde_json::to_string(&self));
+        let mut file = try!(fs::File::create(file_path));
+        try!(file.write_all(serialized_composition.as_bytes()));
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -15,15 +46,20 @@ mod tests {
     use super::CompositionData;
     use hardcode::*;
     use measure::Measure;
-    use looper::Sample;
+    use looper::{Sample, Scene, TempoAutomation};
+    use looper::tempo_automation::TempoPoint;
     use serde_json;
     use midi::{AbsMidiEvent, TypedMidiMessage};
 
-    const DEFAULT_MEASURE: Measure = Measure {
-        tempo_bpm: DEFAULT_TEMPO_BPM,
-        measure_size_bpm: DEFAULT_MEASURE_SIZE_BPM,
-        quantation_level: DEFAULT_QUANTATION_LEVEL,
-    };
+    fn default_measure() -> Measure {
+        Measure {
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            measure_size_bpm: DEFAULT_MEASURE_SIZE_BPM,
+            beat_unit: DEFAULT_BEAT_UNIT,
+            beat_grouping: Vec::new(),
+            beat_division: DEFAULT_BEAT_DIVISION,
+        }
+    }
 
     macro_rules! test_sample_data {
         (
@@ -53,21 +89,43 @@ mod tests {
     fn test_composition_serialization() {
         let expected_amount_of_measures = 2;
         let buffer = test_sample_data! [
-            [0, 0, DEFAULT_MEASURE.measure_size_millis() * expected_amount_of_measures]
+            [0, 0, default_measure().measure_size_millis() * expected_amount_of_measures]
         ];
 
         let samples = vec![
-            Sample::new(buffer, &DEFAULT_MEASURE, 0),
-            Sample::new(buffer, &DEFAULT_MEASURE, 0)
+            Sample::new(buffer, &default_measure(), 0),
+            Sample::new(buffer, &default_measure(), 0)
         ];
 
         let composition = CompositionData {
-            measure: DEFAULT_MEASURE,
+            measure: default_measure(),
             samples: samples.iter().map(|sample| sample.as_sample_data()).collect(),
+            scenes: vec![Scene::new(String::from("Verse"), vec![0]),
+                         Scene::new(String::from("Chorus"), vec![0, 1])],
+            tempo_automation: TempoAutomation {
+                points: vec![TempoPoint { tick: 0, tempo_bpm: 120 },
+                             TempoPoint { tick: 960, tempo_bpm: 140 }],
+            },
+            format_version: COMPOSITION_FORMAT_VERSION,
         };
         let massaged_composition: CompositionData =
             serde_json::from_str(&serde_json::to_string(&composition).unwrap()).unwrap();
 
-        assert_eq!(composition.measure, massaged_composition.measure)
+        assert_eq!(composition.measure, massaged_composition.measure);
+        assert_eq!(composition.scenes, massaged_composition.scenes);
+        assert_eq!(composition.tempo_automation, massaged_composition.tempo_automation);
+        assert_eq!(COMPOSITION_FORMAT_VERSION, massaged_composition.format_version);
+    }
+
+    #[test]
+    fn test_composition_without_scenes_deserialization() {
+        let massaged_composition: CompositionData = serde_json::from_str(r#"{
+            "measure": {"tempo_bpm": 120, "measure_size_bpm": 4, "quantation_level": 2},
+            "samples": []
+        }"#).unwrap();
+
+        assert!(massaged_composition.scenes.is_empty());
+        assert!(massaged_composition.tempo_automation.is_empty());
+        assert_eq!(0, massaged_composition.format_version);
     }
 }
@@ -0,0 +1,121 @@
+use hardcode::*;
+use measure::{Quant, QuantMidiEvent};
+use midi::TypedMidiMessage;
+
+/// Timing feel of a sample. Every step of the groove is delayed (or
+/// hurried up) by its offset and the quants between the steps are
+/// stretched evenly, so the order of the events never changes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Groove {
+    /// Size of the step of the groove in quants
+    pub step_quants: u32,
+    /// Offsets of the steps in quants. The pattern of the offsets is
+    /// repeated over and over again.
+    pub offsets: Vec<i32>,
+}
+
+impl Groove {
+    /// Delays every second step of `QUANTS_PER_BEAT /
+    /// SWING_BEAT_DIVISION` quants by `percent` of the step.
+    pub fn swing(percent: u32) -> Groove {
+        let step_quants = QUANTS_PER_BEAT / SWING_BEAT_DIVISION;
+        Groove {
+            step_quants: step_quants,
+            offsets: vec![0, (step_quants * percent / 100) as i32],
+        }
+    }
+
+    /// Measures how far the notes of the buffer are from the steps of
+    /// `QUANTS_PER_BEAT / GROOVE_BEAT_DIVISION` quants. The recorded
+    /// timing of the notes is taken, not the grid they are snapped to.
+    /// The offsets of the notes on the same step of the measure are
+    /// averaged.
+    pub fn extract(buffer: &[QuantMidiEvent], quants_per_measure: Quant) -> Groove {
+        let step_quants = QUANTS_PER_BEAT / GROOVE_BEAT_DIVISION;
+        let step_ticks = (step_quants * TICKS_PER_QUANT) as i64;
+        let steps = (quants_per_measure.as_u32() / step_quants) as i64;
+        let mut offset_sums = vec![0; steps as usize];
+        let mut note_counts = vec![0; steps as usize];
+
+        for event in buffer {
+            if let TypedMidiMessage::NoteOn { .. } = event.message {
+                let tick = event.tick();
+                // The notes played early before the beginning of the
+                // sample belong to its first step
+                let step = (tick + step_ticks / 2 + step_ticks * steps) / step_ticks - steps;
+                let index = ((step % steps + steps) % steps) as usize;
+                offset_sums[index] += tick - step * step_ticks;
+                note_counts[index] += 1;
+            }
+        }
+
+        let ticks_per_quant = TICKS_PER_QUANT as i64;
+        Groove {
+            step_quants: step_quants,
+            offsets: offset_sums.iter().zip(note_counts.iter()).map(|(&sum, &count)| {
+                if count > 0 {
+                    // Rounded to the closest quant
+                    let offset = sum / count;
+                    ((offset + offset.signum() * ticks_per_quant / 2) / ticks_per_quant) as i32
+                } else {
+                    0
+                }
+            }).collect(),
+        }
+    }
+
+    fn offset(&self, step: i64) -> i64 {
+        if self.offsets.is_empty() {
+            0
+        } else {
+            self.offsets[step as usize % self.offsets.len()] as i64
+        }
+    }
+
+    /// Moves the quant according to the groove. The result may get out
+    /// of the sample, so it is up to the caller to wrap it.
+    pub fn apply(&self, Quant(quant): Quant) -> i64 {
+        let step_quants = self.step_quants as i64;
+        if step_quants == 0 {
+            return quant as i64;
+        }
+
+        let step = quant as i64 / step_quants;
+        let start = step * step_quants + self.offset(step);
+        let end = (step + 1) * step_quants + self.offset(step + 1);
+
+        start + (end - start) * (quant as i64 % step_quants) / step_quants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Groove;
+    use measure::{Quant, QuantMidiEvent, NoteCondition};
+    use midi::TypedMidiMessage;
+
+    #[test]
+    fn test_swing() {
+        let groove = Groove::swing(50);
+        let step = groove.step_quants as i64;
+
+        assert_eq!(0, groove.apply(Quant(0)));
+        assert_eq!(step + step / 2, groove.apply(Quant(step as u32)));
+        assert_eq!(2 * step, groove.apply(Quant(2 * step as u32)));
+        assert_eq!(3 * step / 4, groove.apply(Quant(step as u32 / 2)));
+    }
+
+    #[test]
+    fn test_extract() {
+        let note_on = |quant| QuantMidiEvent {
+            message: TypedMidiMessage::NoteOn { channel: 0, key: 0, velocity: 100 },
+            quant: Quant(quant),
+            offset: 0,
+            condition: NoteCondition::Always,
+        };
+        let groove = Groove::extract(&[note_on(0), note_on(8), note_on(17)], Quant(24));
+
+        assert_eq!(vec![0, 2, 0, -1], groove.offsets);
+        assert_eq!(8, groove.apply(Quant(6)));
+    }
+}
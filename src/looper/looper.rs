@@ -1,21 +1,18 @@
-use std::{path, fs};
-use std::io::prelude::*;
+use std::cmp;
 
 use midi::*;
 use hardcode::*;
 use num::integer::lcm;
-use serde_json;
 use looper::CompositionData;
-use error::Result;
 
-use traits::{Updatable, Renderable};
-use graphics_primitives::CircleRenderer;
+use traits::Updatable;
 use measure::*;
-use looper::Sample;
-
-use sdl2::render::Renderer;
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
+use looper::{Sample, PlaybackRate, Scene, Groove, TempoRamp, TempoAutomation};
+use looper::{Action, PendingAction, LaunchQuantization, ClipState, Metronome};
+use looper::{LooperSnapshot, SampleSnapshot, SampleEdit};
+use looper::{Arpeggiator, ArpeggiatorMode, ArpeggiatorParameter};
+use looper::{ChordEffect, ChordMode, Scale, NoteRepeat, AutomationLane, StepPattern};
+use looper::SampleData;
 
 #[derive(PartialEq)]
 enum State {
@@ -24,16 +21,73 @@ enum State {
     Pause,
 }
 
+fn is_legacy_metronome(sample_data: &SampleData) -> bool {
+    !sample_data.buffer.is_empty() && sample_data.buffer.iter().all(|event| {
+        match event.message {
+            TypedMidiMessage::NoteOn { channel, key, .. } |
+            TypedMidiMessage::NoteOff { channel, key, .. } =>
+                channel == CONTROL_CHANNEL_NUMBER && key == BEAT_KEY_NUMBER,
+            _ => false,
+        }
+    })
+}
+
+/// The quant of the replay of the sample seen at the position of the
+/// bar in the piano roll, from 0.0 to 1.0
+fn window_replay_quant(sample: &Sample, bar: u32, position: f32) -> Quant {
+    let Quant(quants_per_measure) = sample.quants_per_measure();
+    let quant_in_measure = cmp::min((position * quants_per_measure as f32) as u32, quants_per_measure - 1);
+    sample.replay_quant(Quant(bar * quants_per_measure + quant_in_measure))
+}
+
 pub struct Looper<NoteTracker: MidiNoteTracker> {
     state: State,
-    next_state: Option<State>,
+
+    pending_actions: Vec<PendingAction>,
+    launch_quantization: LaunchQuantization,
 
     composition: Vec<Sample>,
     record_buffer: Vec<AbsMidiEvent>,
 
+    scenes: Vec<Scene>,
+    current_scene: usize,
+    song_mode: bool,
+
     note_tracker: NoteTracker,
+    metronome: Metronome,
+    chord_effect: ChordEffect,
+    arpeggiator: Arpeggiator,
+    note_repeat: NoteRepeat,
+    /// The last note played to the looper, the note conditions of the
+    /// last sample are edited for its key
+    last_played_note: Option<(u8, u8)>,
+    /// Time of the MIDI input the notes generated by the looper are
+    /// recorded at. Follows the timestamps of the incoming events.
+    timestamp: u32,
 
-    time_cursor: u32,
+    /// Groove extracted from one sample to be applied to the others
+    groove_template: Option<Groove>,
+    /// The edits of the samples to be undone, the latest is the last
+    edit_history: Vec<SampleEdit>,
+    /// Controller shown in the automation lane and drawn into the last
+    /// sample
+    automation_lane: AutomationLane,
+
+    tempo_ramp: Option<TempoRamp>,
+    /// Length of the tempo ramps started by the tempo changes. Zero
+    /// changes the tempo at once.
+    tempo_ramp_bars: u32,
+    tempo_automation: TempoAutomation,
+    recording_tempo: bool,
+    /// The tempo follows the recorded automation. Changing the tempo
+    /// by hand suspends it.
+    following_tempo: bool,
+    midi_clock: bool,
+
+    /// Musical position of the looper in ticks
+    tick_cursor: u32,
+    /// Fractions of the tick left from the previous update
+    tick_remainder: u64,
     amount_of_measures: u32,
 
     measure: Measure,
@@ -42,96 +96,52 @@ pub struct Looper<NoteTracker: MidiNoteTracker> {
 impl<NoteTracker: MidiNoteTracker> Updatable for Looper<NoteTracker> {
     fn update(&mut self, delta_time: u32) {
         if self.state != State::Pause {
-            let current_measure_bar = self.measure.timestamp_to_measure(self.time_cursor);
-            let current_quant = self.measure.timestamp_to_quant(self.time_cursor);
-
-            let next_time_cursor = self.time_cursor + delta_time;
-            let next_measure_bar = self.measure.timestamp_to_measure(next_time_cursor);
-            let next_quant = self.measure.timestamp_to_quant(next_time_cursor);
-
-            if current_measure_bar < next_measure_bar {
-                self.on_measure_bar();
-            }
-
-            if current_quant < next_quant {
-                for sample in &mut self.composition {
-                    // FIXME(#140): make Quants range iterable
-                    let Quant(start) = current_quant;
-                    let Quant(end) = next_quant;
-                    for q in start + 1..end + 1 {
-                        sample.replay_quant(Quant(q), &mut self.note_tracker);
-                    }
-                }
-            }
-
-            self.time_cursor = next_time_cursor % (self.measure.measure_size_millis() * self.amount_of_measures);
-        }
-    }
-}
-
-impl<NoteTracker: MidiNoteTracker> Renderable for Looper<NoteTracker> {
-    fn render(&self, renderer: &mut Renderer) {
-        let window_width = renderer.viewport().width();
-        let window_height = renderer.viewport().height();
-        let measure_size_millis = self.measure.measure_size_millis();
-        let beat_size_millis = self.measure.beat_size_millis();
-
-        for sample in &self.composition {
-            sample.render(self.measure.timestamp_to_measure(self.time_cursor), renderer);
-        }
-
-        let draw_time_cursor = |time_cursor: u32, renderer: &mut Renderer| {
-            let x = ((time_cursor as f32) /
-                     measure_size_millis as f32 *
-                     (window_width as f32 - 10.0) + 5.0) as i32;
-            renderer.draw_line(Point::from((x, 0)),
-                               Point::from((x, window_height as i32))).unwrap();
-        };
-
-        // FIXME(#148): Separate Looper::render into several functions.
-        // If you need separate comments like this, you need separate
-        // functions
-
-        // Time Cursor
-        renderer.set_draw_color(Color::RGB(255, 255, 255));
-        draw_time_cursor(self.time_cursor % measure_size_millis, renderer);
-
-        // Measure Beats
-        for i in 0 .. self.measure.measure_size_bpm {
-            renderer.set_draw_color(Color::RGB(50, 50, 50));
-            draw_time_cursor(i * beat_size_millis, renderer);
-        }
-
-        { // Circle
-            let r = 15;
-            let p = 25;
-            let x = window_width as i32 - r - 2 * p;
-            let y = r + p;
-            renderer.set_draw_color(Color::RGB(255, 0, 0));
-
-            if let State::Recording = self.state {
-                renderer.fill_circle(x, y, r);
-            } else {
-                renderer.draw_circle(x, y, r);
+            // The tempo may change with every tick, so it is integrated
+            // millisecond by millisecond
+            for _ in 0..delta_time {
+                self.timestamp = self.timestamp.wrapping_add(1);
+                self.advance_time(1);
             }
         }
     }
 }
 
 impl<NoteTracker: MidiNoteTracker> Looper<NoteTracker> {
-    pub fn new(note_tracker: NoteTracker) -> Looper<NoteTracker> {
+    pub fn new(note_tracker: NoteTracker, metronome: Metronome) -> Looper<NoteTracker> {
         let mut looper = Looper {
             state: State::Looping,
-            next_state: None,
+            pending_actions: Vec::new(),
+            launch_quantization: LaunchQuantization::NextBar,
             composition: Vec::new(),
             record_buffer: Vec::new(),
+            scenes: Vec::new(),
+            current_scene: 0,
+            song_mode: false,
             note_tracker: note_tracker,
+            metronome: metronome,
+            chord_effect: ChordEffect::default(),
+            arpeggiator: Arpeggiator::default(),
+            note_repeat: NoteRepeat::default(),
+            last_played_note: None,
+            timestamp: 0,
+            groove_template: None,
+            edit_history: Vec::new(),
+            automation_lane: AutomationLane::default(),
+            tempo_ramp: None,
+            tempo_ramp_bars: 0,
+            tempo_automation: TempoAutomation::default(),
+            recording_tempo: false,
+            following_tempo: false,
+            midi_clock: false,
             amount_of_measures: 1,
-            time_cursor: 0,
+            tick_cursor: 0,
+            tick_remainder: 0,
             measure: Measure {
                 tempo_bpm: DEFAULT_TEMPO_BPM,
                 measure_size_bpm: DEFAULT_MEASURE_SIZE_BPM,
-                quantation_level: DEFAULT_QUANTATION_LEVEL,
+                beat_unit: DEFAULT_BEAT_UNIT,
+                beat_grouping: Vec::new(),
+                beat_division: DEFAULT_BEAT_DIVISION,
             },
         };
         looper.reset();
@@ -139,30 +149,114 @@ impl<NoteTracker: MidiNoteTracker> Looper<NoteTracker> {
     }
 
     pub fn reset(&mut self) {
-        let beats = self.make_metronome();
-
         self.state = State::Looping;
         self.composition.clear();
-        self.composition.push(beats);
         self.record_buffer.clear();
 
+        self.scenes = vec![Scene::new(String::from("Scene 1"), Vec::new())];
+        self.current_scene = 0;
+        self.pending_actions.clear();
+        self.edit_history.clear();
+
         self.amount_of_measures = 1;
-        self.time_cursor = self.amount_of_measures * self.measure.measure_size_millis() - 1;
+        self.tick_cursor = self.loop_size_ticks() - 1;
+
+        self.tempo_ramp = None;
+        self.tempo_automation.clear();
+        self.recording_tempo = false;
+        self.following_tempo = false;
 
         self.note_tracker.close_opened_notes();
     }
 
+    pub fn is_recording(&self) -> bool {
+        self.state == State::Recording
+    }
+
+    /// Copies the state the front-end needs to render the looper
+    pub fn snapshot(&self) -> LooperSnapshot {
+        let mut snapshot = LooperSnapshot {
+            recording: false,
+            measure: self.measure.clone(),
+            tick_cursor: 0,
+            samples: Vec::new(),
+            automation_lane: self.automation_lane,
+            amount_of_scenes: 0,
+            current_scene: 0,
+            current_scene_name: String::new(),
+            pending_actions: Vec::new(),
+            launch_quantization: self.launch_quantization,
+            clip_states: Vec::new(),
+        };
+        self.update_snapshot(&mut snapshot);
+        snapshot
+    }
+
+    /// Refills the snapshot published earlier. Its vectors are reused,
+    /// so the engine thread allocates only when the snapshot grows.
+    pub fn update_snapshot(&self, snapshot: &mut LooperSnapshot) {
+        let bar = self.measure.musical_position(self.tick_cursor).bar;
+        let scene = &self.scenes[self.current_scene];
+
+        snapshot.recording = self.is_recording();
+        if snapshot.measure != self.measure {
+            snapshot.measure = self.measure.clone();
+        }
+        snapshot.tick_cursor = self.tick_cursor;
+        snapshot.automation_lane = self.automation_lane;
+
+        let mut amount_of_samples = 0;
+        for (_, sample) in self.composition.iter().enumerate().filter(|&(index, _)| scene.contains(index)) {
+            if amount_of_samples == snapshot.samples.len() {
+                snapshot.samples.push(SampleSnapshot {
+                    notes: Vec::new(),
+                    automation: Vec::new(),
+                    selected_notes: Vec::new(),
+                    window_size: Quant(0),
+                });
+            }
+
+            let sample_snapshot = &mut snapshot.samples[amount_of_samples];
+            let window_size = sample.quants_per_measure();
+            let window_start = Quant(bar) * window_size;
+            sample.window_notes(window_start, window_size, &mut sample_snapshot.notes);
+            sample.window_selected_notes(window_start, window_size, &mut sample_snapshot.selected_notes);
+            sample.window_automation(self.automation_lane, window_start, window_size, &mut sample_snapshot.automation);
+            sample_snapshot.window_size = window_size;
+            amount_of_samples += 1;
+        }
+        snapshot.samples.truncate(amount_of_samples);
+
+        snapshot.amount_of_scenes = self.scenes.len();
+        snapshot.current_scene = self.current_scene;
+        snapshot.current_scene_name.clone_from(&scene.name);
+        snapshot.pending_actions.clear();
+        snapshot.pending_actions.extend(self.pending_actions.iter().map(|pending_action| pending_action.action.kind()));
+        snapshot.launch_quantization = self.launch_quantization;
+        self.write_clip_states(&mut snapshot.clip_states);
+    }
+
     pub fn toggle_recording(&mut self) {
         match self.state {
             State::Recording => {
-                self.next_state = Some(State::Looping);
+                let quantization = self.recording_quantization();
+                self.schedule_with_quantization(Action::StopRecording, quantization);
             }
 
-            State::Looping => {
-                self.state = State::Recording;
-                self.record_buffer.clear();
+            State::Looping if self.is_counting_in() => {
+                self.pending_actions.retain(|pending_action| {
+                    !Action::StartRecording.supersedes(&pending_action.action)
+                });
             }
 
+            State::Looping if self.metronome.count_in_measures > 0 => {
+                let count_in_measures = self.metronome.count_in_measures;
+                self.schedule_with_quantization(Action::StartRecording,
+                                                LaunchQuantization::AfterBars(count_in_measures));
+            }
+
+            State::Looping => self.start_recording(),
+
             _ => (),
         }
 
@@ -179,142 +273,1656 @@ impl<NoteTracker: MidiNoteTracker> Looper<NoteTracker> {
         }
     }
 
+    /// Schedules the action according to the current launch
+    /// quantization
+    pub fn schedule(&mut self, action: Action) {
+        let quantization = self.launch_quantization;
+        self.schedule_with_quantization(action, quantization);
+    }
+
+    pub fn schedule_with_quantization(&mut self, action: Action, quantization: LaunchQuantization) {
+        self.pending_actions.retain(|pending_action| !action.supersedes(&pending_action.action));
+
+        if let LaunchQuantization::Immediate = quantization {
+            self.perform(action);
+        } else {
+            self.pending_actions.push(PendingAction {
+                action: action,
+                quantization: quantization,
+            });
+        }
+    }
+
+    pub fn switch_launch_quantization(&mut self) -> LaunchQuantization {
+        self.launch_quantization = self.launch_quantization.next();
+        self.launch_quantization
+    }
+
+    fn launch_pending_actions(&mut self) {
+        let beat_boundary = self.tick_cursor + 1;
+        let measure_bar = self.measure.musical_position(beat_boundary).is_bar_start();
+        let loop_end = beat_boundary % self.loop_size_ticks() == 0;
+
+        let mut due_actions = Vec::new();
+        let mut pending_actions = Vec::new();
+        for mut pending_action in self.pending_actions.drain(..) {
+            if pending_action.on_beat(measure_bar, loop_end) {
+                due_actions.push(pending_action.action);
+            } else {
+                pending_actions.push(pending_action);
+            }
+        }
+        self.pending_actions = pending_actions;
+
+        for action in due_actions {
+            self.perform(action);
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::StartRecording => self.start_recording(),
+            Action::StopRecording => self.stop_recording(),
+            Action::UndoLastRecording => self.undo_last_recording(),
+            Action::ToggleLastSampleMute => self.toggle_last_sample_mute(),
+            Action::ChangeTempo(tempo_bpm) => self.update_tempo_bpm(tempo_bpm),
+            Action::RampTempo(tempo_bpm, bars) => self.start_tempo_ramp(tempo_bpm, bars),
+            Action::SwitchScene(scene) => self.switch_scene(scene),
+            Action::LoadComposition(composition) => self.load_composition(composition),
+            Action::LaunchSample(index) => self.set_sample_muted(index, false),
+            Action::StopSample(index) => self.set_sample_muted(index, true),
+        }
+    }
+
+    fn advance_time(&mut self, delta_time: u32) {
+        let mut delta_ticks = self.measure.millis_to_ticks(delta_time, &mut self.tick_remainder);
+
+        // Pending actions are launched right before the beat
+        // boundary, so the tick of the boundary is already
+        // replayed with the actions applied
+        while !self.pending_actions.is_empty() {
+            let ticks_to_beat = TICKS_PER_BEAT - self.tick_cursor % TICKS_PER_BEAT;
+
+            if ticks_to_beat > delta_ticks {
+                break;
+            }
+
+            self.advance_tick_cursor(ticks_to_beat - 1);
+            self.launch_pending_actions();
+            self.advance_tick_cursor(1);
+            delta_ticks -= ticks_to_beat;
+        }
+
+        self.advance_tick_cursor(delta_ticks);
+    }
+
+    fn advance_tick_cursor(&mut self, delta_ticks: u32) {
+        if delta_ticks == 0 {
+            return;
+        }
+
+        let next_tick_cursor = self.tick_cursor + delta_ticks;
+
+        if self.is_metronome_audible() {
+            self.metronome.replay(&self.measure, self.tick_cursor, next_tick_cursor, &mut self.note_tracker);
+        }
+
+        if self.midi_clock {
+            let ticks_per_midi_clock = self.measure.ticks_per_midi_clock();
+            for _ in self.tick_cursor / ticks_per_midi_clock..next_tick_cursor / ticks_per_midi_clock {
+                // FIXME(#141): Handle result of the sink message feeding
+                self.note_tracker.feed_untracked(TypedMidiMessage::TimingClock).unwrap();
+            }
+        }
+
+        {
+            let scene = &self.scenes[self.current_scene];
+            for (index, sample) in self.composition.iter_mut().enumerate() {
+                if scene.contains(index) {
+                    sample.replay(self.tick_cursor, next_tick_cursor, &mut self.note_tracker);
+                }
+            }
+        }
+
+        for message in self.arpeggiator.advance(self.tick_cursor, next_tick_cursor) {
+            self.play(message);
+        }
+
+        for message in self.note_repeat.advance(self.tick_cursor, next_tick_cursor) {
+            self.play(message);
+        }
+
+        self.tick_cursor = next_tick_cursor % self.loop_size_ticks();
+        self.follow_tempo(delta_ticks);
+    }
+
+    /// Moves the tempo along the current ramp or the recorded tempo
+    /// automation
+    fn follow_tempo(&mut self, delta_ticks: u32) {
+        let tempo_bpm = if let Some(ref mut tempo_ramp) = self.tempo_ramp {
+            Some(tempo_ramp.advance(delta_ticks))
+        } else if self.following_tempo && !self.recording_tempo {
+            self.tempo_automation.tempo_bpm(self.tick_cursor)
+        } else {
+            None
+        };
+
+        if self.tempo_ramp.map_or(false, |tempo_ramp| tempo_ramp.is_finished()) {
+            self.tempo_ramp = None;
+        }
+
+        if let Some(tempo_bpm) = tempo_bpm {
+            self.set_tempo_bpm(tempo_bpm);
+        }
+    }
+
+    fn loop_size_ticks(&self) -> u32 {
+        self.amount_of_measures * self.measure.ticks_per_measure()
+    }
+
     pub fn undo_last_recording(&mut self) {
         if let State::Recording = self.state {
             self.record_buffer.clear();
-        } else if self.composition.len() > 1  {
-            self.composition.pop();
-            self.amount_of_measures = 1;
-            for sample in &self.composition {
-                self.amount_of_measures = lcm(self.amount_of_measures,
-                                              sample.amount_of_measures);
-            }
+        } else if !self.composition.is_empty() {
+            let last_index = self.composition.len() - 1;
+            self.remove_sample(last_index);
+            // The indices of the edited samples are not reliable
+            // anymore
+            self.edit_history.clear();
+            self.recalculate_amount_of_measures();
             self.note_tracker.close_opened_notes();
         }
     }
 
-    pub fn on_measure_bar(&mut self) {
-        if let Some(state) = self.next_state.take() {
-            self.state = state;
+    /// Adds the sample to the end of the composition and returns its
+    /// index
+    fn push_sample(&mut self, mut sample: Sample) -> usize {
+        let index = self.composition.len();
+        sample.seed_note_conditions(index);
+        self.composition.push(sample);
+        index
+    }
+
+    fn remove_sample(&mut self, index: usize) {
+        self.composition.remove(index);
+        for scene in &mut self.scenes {
+            scene.remove_sample(index);
+        }
 
-            if let State::Looping = self.state {
-                let current_measure = self.measure.timestamp_to_measure(self.time_cursor);
-                self.normalize_record_buffer();
-                // FIXME(#164): Separate Sample::amount_of_measures_in_buffer from Sample
-                let sample_amount_of_measures = self.measure.amount_of_measures_in_buffer(&self.record_buffer);
-                self.amount_of_measures = lcm(self.amount_of_measures, sample_amount_of_measures);
-                let sample = Sample::new(&self.record_buffer, &self.measure, self.amount_of_measures - current_measure - 1);
-                self.composition.push(sample);
+        // The clips queued on the launcher follow their samples
+        self.pending_actions.retain(|pending_action| {
+            match pending_action.action {
+                Action::LaunchSample(pending_index) | Action::StopSample(pending_index) => pending_index != index,
+                _ => true,
+            }
+        });
+        for pending_action in &mut self.pending_actions {
+            match pending_action.action {
+                Action::LaunchSample(ref mut pending_index) |
+                Action::StopSample(ref mut pending_index) => if *pending_index > index {
+                    *pending_index -= 1;
+                },
+                _ => (),
             }
         }
     }
 
-    pub fn on_midi_event(&mut self, event: &AbsMidiEvent) {
-        if let State::Recording = self.state {
-            self.record_buffer.push(*event);
+    fn remember_edit(&mut self, edit: SampleEdit) {
+        if self.edit_history.len() >= EDIT_HISTORY_LIMIT {
+            self.edit_history.remove(0);
+        }
+        self.edit_history.push(edit);
+    }
+
+    /// Applies the edit to the last sample remembering its previous
+    /// version, so the edit can be undone
+    fn edit_last_sample<R, F: FnOnce(&mut Sample) -> R>(&mut self, edit: F) -> Option<R> {
+        match self.composition.len() {
+            0 => None,
+            len => self.edit_sample(len - 1, edit),
+        }
+    }
+
+    /// Applies the edit to the sample remembering its previous version.
+    /// Returns `None` if there is no such sample.
+    fn edit_sample<R, F: FnOnce(&mut Sample) -> R>(&mut self, index: usize, edit: F) -> Option<R> {
+        if index >= self.composition.len() {
+            return None;
         }
 
-        self.note_tracker.feed(event.message).unwrap();
+        let previous = self.composition[index].clone();
+        let result = edit(&mut self.composition[index]);
+        self.remember_edit(SampleEdit::Changed(index, previous));
+        self.recalculate_amount_of_measures();
+        self.note_tracker.close_opened_notes();
+        Some(result)
     }
 
-    pub fn update_tempo_bpm(&mut self, tempo_bpm: u32) {
-        let new_measure = Measure { tempo_bpm: tempo_bpm, .. self.measure };
+    /// Moves the events of the last sample by the given amount of the
+    /// recording grid steps
+    pub fn nudge_last_sample(&mut self, steps: i32) {
+        let quants = steps * self.measure.grid_size_quants() as i32;
+        self.edit_last_sample(|sample| sample.nudge(quants));
+    }
+
+    pub fn nudge_last_sample_measures(&mut self, measures: i32) {
+        self.edit_last_sample(|sample| sample.nudge_measures(measures));
+    }
+
+    /// Extends the last sample by a measure or trims one off and
+    /// returns the new amount of measures rounded up.
+    pub fn resize_last_sample(&mut self, extend: bool) -> Option<u32> {
+        let quants_per_measure = self.measure.quants_per_measure();
+        self.resize_last_sample_by(quants_per_measure, extend)
+            .map(|length| (length.as_u32() + quants_per_measure.as_u32() - 1) / quants_per_measure.as_u32())
+    }
+
+    /// Extends the last sample by a beat or trims one off and returns
+    /// the new amount of beats. The beats make the lengths that do
+    /// not fill the measures, e.g. three beats over 4/4.
+    pub fn resize_last_sample_by_beat(&mut self, extend: bool) -> Option<u32> {
+        self.resize_last_sample_by(Quant(QUANTS_PER_BEAT), extend)
+            .map(|Quant(length)| (length + QUANTS_PER_BEAT - 1) / QUANTS_PER_BEAT)
+    }
+
+    fn resize_last_sample_by(&mut self, step: Quant, extend: bool) -> Option<Quant> {
+        self.edit_last_sample(|sample| {
+            let length = sample.quant_length();
+            let length = if extend {
+                length + step
+            } else if length > step {
+                length - step
+            } else {
+                length
+            };
+            sample.set_quant_length(length);
+            sample.quant_length()
+        })
+    }
+
+    /// Switches the output channel of the last sample to the next or
+    /// the previous one. The recorded channels come after the last
+    /// channel and before the first one.
+    pub fn change_last_sample_output_channel(&mut self, next: bool) -> Option<Option<u8>> {
+        self.edit_last_sample(|sample| {
+            let output_channel = match (sample.output_channel(), next) {
+                (None, true) => Some(0),
+                (None, false) => Some(15),
+                (Some(15), true) | (Some(0), false) => None,
+                (Some(channel), true) => Some(channel + 1),
+                (Some(channel), false) => Some(channel - 1),
+            };
+            sample.set_output_channel(output_channel);
+            output_channel
+        })
+    }
+
+    /// Switches the condition of the selected note of the last sample,
+    /// or of its notes with the key of the last played note if none is
+    /// selected. `forward` only applies to the selected note. Returns
+    /// `None` if there are no such notes.
+    pub fn switch_last_sample_note_condition(&mut self, forward: bool) -> Option<NoteCondition> {
+        if self.composition.last().map_or(false, |sample| sample.has_selected_note()) {
+            return self.edit_last_sample(|sample| sample.switch_selected_note_condition(forward))
+                .and_then(|condition| condition);
+        }
+
+        let (channel, key) = match self.last_played_note {
+            Some(note) => note,
+            None => return None,
+        };
+
+        // Nothing to undo if the sample has no such notes
+        if self.composition.last().map_or(false, |sample| sample.has_note(channel, key)) {
+            self.edit_last_sample(|sample| sample.switch_note_condition(channel, key)).and_then(|condition| condition)
+        } else {
+            None
+        }
+    }
+
+    /// Shows the next controller recorded in the samples of the current
+    /// scene in the automation lane
+    pub fn switch_automation_lane(&mut self) -> AutomationLane {
+        let lanes: Vec<AutomationLane> = {
+            let scene = &self.scenes[self.current_scene];
+            let mut lanes: Vec<AutomationLane> = self.composition.iter().enumerate()
+                .filter(|&(index, _)| scene.contains(index))
+                .flat_map(|(_, sample)| sample.automation_lanes())
+                .collect();
+            lanes.sort();
+            lanes.dedup();
+            lanes
+        };
+
+        let current = self.automation_lane;
+        if let Some(lane) = lanes.iter().find(|lane| **lane > current).or_else(|| lanes.first()) {
+            self.automation_lane = *lane;
+        }
+        self.automation_lane
+    }
+
+    /// Shows the next or the previous controller in the automation
+    /// lane, whether it is recorded or not, so its values can be drawn
+    pub fn change_automation_lane(&mut self, next: bool) -> AutomationLane {
+        self.automation_lane = if next {
+            self.automation_lane.next()
+        } else {
+            self.automation_lane.previous()
+        };
+        self.automation_lane
+    }
+
+    pub fn clear_last_sample_automation(&mut self) {
+        let lane = self.automation_lane;
+        self.edit_last_sample(|sample| sample.clear_automation(lane));
+    }
+
+    /// Sets the value of the controller of the automation lane in the
+    /// last sample at the position of the measure currently shown.
+    /// Both the position and the value are fractions from 0 to 1. Only
+    /// the first point of a stroke is remembered as an edit, so the
+    /// whole stroke is undone at once.
+    pub fn draw_last_sample_automation(&mut self, position: f32, value: f32, new_stroke: bool) {
+        let lane = self.automation_lane;
+        let bar = self.measure.musical_position(self.tick_cursor).bar;
+        let draw = move |sample: &mut Sample| {
+            let quant = window_replay_quant(sample, bar, position);
+            sample.draw_automation(lane, quant, (value * lane.max_value() as f32).round() as u16);
+        };
+
+        if new_stroke {
+            self.edit_last_sample(draw);
+        } else if let Some(sample) = self.composition.last_mut() {
+            draw(sample);
+        }
+    }
 
-        // FIXME(#150): Improve time cursor scaling
-        self.time_cursor =
-            self.measure.scale_time_cursor(&new_measure,
-                                           self.amount_of_measures,
-                                           self.time_cursor % (self.amount_of_measures * self.measure.measure_size_millis()));
+    /// Selects the note of the last sample with the key at the
+    /// position of the current measure seen in the piano roll, from 0.0
+    /// to 1.0, and returns its condition. Clears the selection if there
+    /// is no such note.
+    pub fn select_last_sample_note(&mut self, position: f32, key: u8) -> Option<NoteCondition> {
+        let bar = self.measure.musical_position(self.tick_cursor).bar;
+        self.composition.last_mut().and_then(|sample| {
+            let quant = window_replay_quant(sample, bar, position);
+            sample.select_note(key, quant)
+        })
+    }
+
+    pub fn double_last_sample(&mut self) {
+        self.edit_last_sample(|sample| sample.double());
+    }
+
+    /// Adds the copy of the last sample to the composition and the
+    /// current scene
+    pub fn duplicate_last_sample(&mut self) {
+        if let Some(sample) = self.composition.last().cloned() {
+            let index = self.push_sample(sample);
+            self.scenes[self.current_scene].samples.push(index);
+            self.remember_edit(SampleEdit::Added(index));
+            self.recalculate_amount_of_measures();
+            self.note_tracker.close_opened_notes();
+        }
+    }
+
+    /// The notes of the last sample on the recording grid for the step
+    /// sequencer. An empty sample of one measure is added to the
+    /// current scene first if asked or if there are no samples yet.
+    /// Returns the index of the sample with the pattern.
+    pub fn last_sample_step_pattern(&mut self, new_sample: bool) -> (usize, StepPattern) {
+        if new_sample || self.composition.is_empty() {
+            let sample = Sample::new(&[], &self.measure, 0);
+            let index = self.push_sample(sample);
+            self.scenes[self.current_scene].samples.push(index);
+            self.remember_edit(SampleEdit::Added(index));
+            self.recalculate_amount_of_measures();
+        }
+
+        let step_quants = self.measure.grid_size_quants();
+        let default_channel = self.last_played_note.map_or(0, |(channel, _)| channel);
+        // The sample has just been added if there were none
+        let index = self.composition.len() - 1;
+        (index, self.composition[index].step_pattern(step_quants, default_channel))
+    }
 
-        self.measure = new_measure;
+    /// Writes the pattern into the sample it was taken from. Returns
+    /// `false` if the sample is gone, so the pattern is not written
+    /// into a different one.
+    pub fn write_sample_step_pattern(&mut self, index: usize, pattern: &StepPattern) -> bool {
+        self.edit_sample(index, |sample| sample.write_step_pattern(pattern)).is_some()
     }
 
-    pub fn load_state_from_file(&mut self, path: &path::Path) -> Result<()> {
-        let mut file = try!(fs::File::open(path));
-        let mut serialized_composition = String::new();
-        try!(file.read_to_string(&mut serialized_composition));
-        let composition: CompositionData = try!(serde_json::from_str(&serialized_composition));
+    pub fn undo_last_edit(&mut self) {
+        match self.edit_history.pop() {
+            Some(SampleEdit::Changed(index, sample)) => {
+                if index < self.composition.len() {
+                    self.composition[index] = sample;
+                }
+            },
+            Some(SampleEdit::Added(index)) => {
+                if index + 1 == self.composition.len() {
+                    self.remove_sample(index);
+                }
+            },
+            None => return,
+        }
 
+        self.recalculate_amount_of_measures();
         self.note_tracker.close_opened_notes();
-        self.composition = composition.samples.iter().map(|sample_data| Sample::from_sample_data(sample_data)).collect();
-        self.measure = composition.measure;
-        self.time_cursor = 0;
+    }
 
-        // TODO(#186): Extract recalculation of the amount of measures.
-        //
-        // Right know Looper has a lot of duplicate code for
-        // recalculating amount of measures of the composition. We
-        // need to centralize that somehow.
-        self.amount_of_measures = 1;
-        for sample in &self.composition {
-            self.amount_of_measures = lcm(self.amount_of_measures,
-                                          sample.amount_of_measures);
+    /// Switches the playback rate of the last recorded sample to the
+    /// next faster or slower one and returns the new rate.
+    pub fn change_last_sample_playback_rate(&mut self, faster: bool) -> Option<PlaybackRate> {
+        self.edit_last_sample(|sample| {
+            let current_rate = sample.playback_rate();
+            let playback_rate = if faster { current_rate.faster() } else { current_rate.slower() };
+            sample.set_playback_rate(playback_rate);
+            playback_rate
+        })
+    }
+
+    pub fn change_last_sample_swing(&mut self, increase: bool) -> Option<u32> {
+        self.edit_last_sample(|sample| {
+            let swing = if increase {
+                sample.swing() + SWING_PERCENT_STEP
+            } else {
+                sample.swing().saturating_sub(SWING_PERCENT_STEP)
+            };
+            sample.set_swing(swing);
+            sample.swing()
+        })
+    }
+
+    pub fn change_last_sample_quantize_strength(&mut self, increase: bool) -> Option<u32> {
+        self.edit_last_sample(|sample| {
+            let quantize_strength = if increase {
+                sample.quantize_strength() + QUANTIZE_STRENGTH_STEP
+            } else {
+                sample.quantize_strength().saturating_sub(QUANTIZE_STRENGTH_STEP)
+            };
+            sample.set_quantize_strength(quantize_strength);
+            sample.quantize_strength()
+        })
+    }
+
+    /// Quantizes the last sample to the current recording grid
+    pub fn requantize_last_sample(&mut self) {
+        let measure = self.measure.clone();
+        self.edit_last_sample(|sample| sample.requantize(&measure));
+    }
+
+    /// Remembers the groove of the last sample, so it can be applied
+    /// to the samples recorded later
+    pub fn extract_groove_from_last_sample(&mut self) {
+        if let Some(sample) = self.composition.last() {
+            self.groove_template = Some(sample.extract_groove());
         }
-        self.time_cursor = self.amount_of_measures * self.measure.measure_size_millis() - 1;
+    }
 
-        Ok(())
+    /// Applies the extracted groove to the last sample or removes the
+    /// groove from it if it already has one
+    pub fn toggle_last_sample_groove(&mut self) {
+        let groove_template = self.groove_template.clone();
+        self.edit_last_sample(|sample| {
+            if sample.has_groove() {
+                sample.set_groove(None);
+            } else {
+                sample.set_groove(groove_template);
+            }
+        });
     }
 
-    pub fn save_state_to_file(&self, path: &path::Path) -> Result<()> {
-        let composition = CompositionData {
-            measure: self.measure.clone(),
-            samples: self.composition.iter().map(|sample| sample.as_sample_data()).collect(),
+    /// Replaces the samples with the single one they are bounced
+    /// to. The merged sample is added to every scene that had any of
+    /// the samples.
+    pub fn merge_samples(&mut self, indices: &[usize]) {
+        let mut indices: Vec<usize> = indices.iter()
+            .cloned()
+            .filter(|&index| index < self.composition.len())
+            .collect();
+        indices.sort();
+        indices.dedup();
+        if indices.len() < 2 {
+            return;
+        }
+
+        let merged_sample = {
+            let samples: Vec<&Sample> = indices.iter().map(|&index| &self.composition[index]).collect();
+            Sample::merge(&samples, self.measure.quants_per_measure())
         };
+        let merged_scenes: Vec<bool> = self.scenes.iter()
+            .map(|scene| indices.iter().any(|&index| scene.contains(index)))
+            .collect();
 
-        let serialized_composition: String = try!(serde_json::to_string(&composition));
-        let mut file = try!(fs::File::create(path));
-        try!(file.write_all(serialized_composition.as_bytes()));
+        for &index in indices.iter().rev() {
+            self.remove_sample(index);
+        }
+        let merged_index = self.push_sample(merged_sample);
+        for (scene, merged) in self.scenes.iter_mut().zip(merged_scenes) {
+            if merged {
+                scene.samples.push(merged_index);
+            }
+        }
 
-        Ok(())
+        // The indices of the edited samples are not reliable anymore
+        self.edit_history.clear();
+        self.recalculate_amount_of_measures();
+        self.note_tracker.close_opened_notes();
     }
 
-    fn make_metronome(&self) -> Sample {
-        let beat_size_millis = self.measure.beat_size_millis();
+    /// Merges the samples of the current scene that are heard
+    pub fn bounce_current_scene(&mut self) {
+        let indices: Vec<usize> = self.scenes[self.current_scene].samples.iter()
+            .cloned()
+            .filter(|&index| self.composition.get(index).map_or(false, |sample| !sample.is_muted()))
+            .collect();
+        self.merge_samples(&indices);
+    }
 
-        let mut buffer = Vec::new();
+    /// Replaces the last sample with its parts. The parts are added
+    /// to the scenes the sample was in. Returns the amount of the
+    /// parts.
+    fn split_last_sample<F: Fn(&TypedMidiMessage) -> usize>(&mut self, part_of: F) -> usize {
+        let parts = match self.composition.last() {
+            Some(sample) => sample.split(part_of),
+            None => return 0,
+        };
+        if parts.len() < 2 {
+            return 1;
+        }
 
-        for i in 0..self.measure.measure_size_bpm {
-            buffer.push(AbsMidiEvent {
-                message: TypedMidiMessage::NoteOn {
-                    channel: CONTROL_CHANNEL_NUMBER,
-                    key: BEAT_KEY_NUMBER,
-                    velocity: if i == 0 { BEAT_ACCENT_VELOCITY } else { BEAT_VELOCITY },
-                },
-                timestamp: i * beat_size_millis,
-            });
+        self.composition.pop();
+        let first_index = self.composition.len();
+        let amount_of_parts = parts.len();
+        for part in parts {
+            self.push_sample(part);
+        }
+        for scene in &mut self.scenes {
+            if scene.contains(first_index) {
+                scene.samples.extend(first_index + 1..first_index + amount_of_parts);
+            }
+        }
+
+        self.edit_history.clear();
+        self.recalculate_amount_of_measures();
+        self.note_tracker.close_opened_notes();
+        amount_of_parts
+    }
 
-            buffer.push(AbsMidiEvent {
-                message: TypedMidiMessage::NoteOff {
-                    channel: CONTROL_CHANNEL_NUMBER,
-                    key: BEAT_KEY_NUMBER,
-                    velocity: 0,
+    pub fn split_last_sample_by_channel(&mut self) -> usize {
+        self.split_last_sample(|message| {
+            message.channel().map_or(AMOUNT_OF_MIDI_CHANNELS, |channel| channel as usize)
+        })
+    }
+
+    /// Splits the last sample by `SPLIT_KEY_RANGES`, e.g. the kicks,
+    /// the snares and the hi-hats of a drum loop
+    pub fn split_last_sample_by_keys(&mut self) -> usize {
+        self.split_last_sample(|message| {
+            match *message {
+                TypedMidiMessage::NoteOn { key, .. } |
+                TypedMidiMessage::NoteOff { key, .. } => {
+                    SPLIT_KEY_RANGES.iter()
+                        .position(|&(lowest, highest)| lowest <= key && key <= highest)
+                        .unwrap_or(SPLIT_KEY_RANGES.len())
                 },
-                timestamp: i * beat_size_millis + 1,
-            })
+                _ => SPLIT_KEY_RANGES.len(),
+            }
+        })
+    }
+
+    pub fn toggle_last_sample_mute(&mut self) {
+        if let Some(sample) = self.composition.last_mut() {
+            sample.toggle_mute();
+            self.note_tracker.close_opened_notes();
         }
+    }
 
-        Sample::new(&buffer, &self.measure, 0)
+    fn set_sample_muted(&mut self, index: usize, muted: bool) {
+        if let Some(sample) = self.composition.get_mut(index) {
+            sample.set_muted(muted);
+        }
+        self.note_tracker.close_opened_notes();
     }
 
-    fn normalize_record_buffer(&mut self) {
-        if !self.record_buffer.is_empty() {
-            let t0 = self.record_buffer[0].timestamp;
+    /// States of the samples of the current scene in the order they
+    /// occupy the slots of the clip launcher
+    pub fn clip_states(&self) -> Vec<ClipState> {
+        let mut clip_states = Vec::new();
+        self.write_clip_states(&mut clip_states);
+        clip_states
+    }
 
-            for event in &mut self.record_buffer {
-                event.timestamp -= t0;
+    /// Fills `clip_states` with the states of the samples of the
+    /// current scene
+    fn write_clip_states(&self, clip_states: &mut Vec<ClipState>) {
+        clip_states.clear();
+        clip_states.extend(self.scenes[self.current_scene].samples.iter().filter_map(|&index| {
+            self.composition.get(index).map(|sample| {
+                let launching = self.pending_actions.iter().any(|pending_action| {
+                    match pending_action.action {
+                        Action::LaunchSample(pending_index) => pending_index == index,
+                        _ => false,
+                    }
+                });
+                let stopping = self.pending_actions.iter().any(|pending_action| {
+                    match pending_action.action {
+                        Action::StopSample(pending_index) => pending_index == index,
+                        _ => false,
+                    }
+                });
+
+                match (sample.is_muted(), launching, stopping) {
+                    (true, true, _) => ClipState::Launching,
+                    (true, _, _) => ClipState::Stopped,
+                    (false, _, true) => ClipState::Stopping,
+                    (false, _, _) => ClipState::Playing,
+                }
+            })
+        }));
+    }
+
+    /// Launches the stopped sample in the slot of the clip launcher or
+    /// stops the playing one according to the launch quantization. If
+    /// the sample is already scheduled to be launched or stopped the
+    /// scheduled action is cancelled.
+    pub fn toggle_clip(&mut self, slot: usize) {
+        let index = self.scenes[self.current_scene].samples.get(slot).cloned();
+        if let Some(index) = index {
+            let pending_actions_count = self.pending_actions.len();
+            self.pending_actions.retain(|pending_action| {
+                !Action::LaunchSample(index).supersedes(&pending_action.action)
+            });
+            if self.pending_actions.len() < pending_actions_count {
+                return;
+            }
+
+            let muted = self.composition.get(index).map(|sample| sample.is_muted());
+            match muted {
+                Some(true) => self.schedule(Action::LaunchSample(index)),
+                Some(false) => self.schedule(Action::StopSample(index)),
+                None => (),
             }
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::Looper;
-    use midi::DummyMidiNoteTracker;
+    pub fn send_feedback(&mut self, message: TypedMidiMessage) {
+        // FIXME(#141): Handle result of the sink message feeding
+        self.note_tracker.feed_untracked(message).unwrap();
+    }
 
-    #[test]
-    fn test_looper_initial_time_cursor() {
-        let looper = Looper::new(DummyMidiNoteTracker);
-        assert_eq!(looper.time_cursor, looper.amount_of_measures * looper.measure.measure_size_millis() - 1);
+    pub fn toggle_last_sample_reverse(&mut self) {
+        self.edit_last_sample(|sample| sample.toggle_reverse());
+    }
+
+    /// Adds a new empty scene to the end of the scene list and returns
+    /// its index.
+    pub fn add_scene(&mut self) -> usize {
+        let name = format!("Scene {}", self.scenes.len() + 1);
+        self.scenes.push(Scene::new(name, Vec::new()));
+        self.scenes.len() - 1
+    }
+
+    /// Adds the last sample to the current scene or removes it from
+    /// there. Returns whether the scene contains it now, `None` if
+    /// there are no samples.
+    pub fn toggle_last_sample_in_current_scene(&mut self) -> Option<bool> {
+        let index = match self.composition.len() {
+            0 => return None,
+            len => len - 1,
+        };
+
+        let assigned = {
+            let scene = &mut self.scenes[self.current_scene];
+            if scene.contains(index) {
+                scene.samples.retain(|&sample| sample != index);
+                false
+            } else {
+                scene.samples.push(index);
+                true
+            }
+        };
+        self.recalculate_amount_of_measures();
+        self.note_tracker.close_opened_notes();
+        Some(assigned)
+    }
+
+    pub fn toggle_song_mode(&mut self) {
+        self.song_mode = !self.song_mode;
+        if self.song_mode {
+            self.queue_next_song_scene();
+        } else {
+            self.pending_actions.retain(|pending_action| {
+                match pending_action.action {
+                    Action::SwitchScene(_) => false,
+                    _ => true,
+                }
+            });
+        }
+    }
+
+    /// Changes the amount of cycles the current scene is played in the
+    /// song mode and returns the new amount.
+    pub fn change_current_scene_cycles(&mut self, increase: bool) -> u32 {
+        let scene = &mut self.scenes[self.current_scene];
+        if increase {
+            scene.cycles += 1;
+        } else if scene.cycles > 1 {
+            scene.cycles -= 1;
+        }
+        scene.cycles
+    }
+
+    fn switch_scene(&mut self, scene: usize) {
+        if scene >= self.scenes.len() {
+            return;
+        }
+
+        self.current_scene = scene;
+        self.recalculate_amount_of_measures();
+        self.note_tracker.close_opened_notes();
+
+        if self.song_mode {
+            self.queue_next_song_scene();
+        }
+    }
+
+    fn queue_next_song_scene(&mut self) {
+        let next_scene = (self.current_scene + 1) % self.scenes.len();
+        let cycles = self.scenes[self.current_scene].cycles;
+        self.schedule_with_quantization(Action::SwitchScene(next_scene),
+                                        LaunchQuantization::AfterLoops(cycles));
+    }
+
+    /// The loop lasts the least common multiple of the lengths of the
+    /// samples and the measure, so the samples of any amount of beats
+    /// repeat in it and it still ends on a bar
+    fn recalculate_amount_of_measures(&mut self) {
+        let Quant(quants_per_measure) = self.measure.quants_per_measure();
+        let mut loop_quant_length = quants_per_measure;
+        let scene = &self.scenes[self.current_scene];
+        for (index, sample) in self.composition.iter().enumerate() {
+            if scene.contains(index) {
+                loop_quant_length = lcm(loop_quant_length, sample.replay_quant_length().as_u32());
+            }
+        }
+        self.amount_of_measures = loop_quant_length / quants_per_measure;
+        self.tick_cursor %= self.loop_size_ticks();
+    }
+
+    pub fn toggle_metronome(&mut self) {
+        self.metronome.toggle();
+    }
+
+    pub fn switch_chord_mode(&mut self) -> ChordMode {
+        self.chord_effect.mode = self.chord_effect.mode.next();
+        self.chord_effect.mode
+    }
+
+    /// Makes the next chord played the shape of the chord memory
+    pub fn learn_chord_shape(&mut self) {
+        self.chord_effect.learn_shape();
+    }
+
+    /// Moves the key of the harmonizer by a semitone and returns its
+    /// root pitch class
+    pub fn change_harmonizer_key(&mut self, increase: bool) -> u8 {
+        self.chord_effect.change_root(increase)
+    }
+
+    pub fn switch_harmonizer_scale(&mut self) -> Scale {
+        self.chord_effect.scale = self.chord_effect.scale.next();
+        self.chord_effect.scale
+    }
+
+    pub fn toggle_arpeggiator(&mut self) -> bool {
+        self.arpeggiator.toggle();
+        self.arpeggiator.enabled
+    }
+
+    pub fn switch_arpeggiator_mode(&mut self) -> ArpeggiatorMode {
+        self.arpeggiator.mode = self.arpeggiator.mode.next();
+        self.arpeggiator.mode
+    }
+
+    /// Switches the amount of the arpeggiator steps per beat to the
+    /// next one of `BEAT_DIVISIONS` and returns it.
+    pub fn switch_arpeggiator_rate(&mut self) -> u32 {
+        self.arpeggiator.switch_rate()
+    }
+
+    pub fn change_arpeggiator_octaves(&mut self, increase: bool) -> u32 {
+        let octaves = if increase {
+            self.arpeggiator.octaves + 1
+        } else {
+            self.arpeggiator.octaves - 1
+        };
+        self.arpeggiator.set_octaves(octaves);
+        self.arpeggiator.octaves
+    }
+
+    pub fn change_arpeggiator_gate(&mut self, increase: bool) -> u32 {
+        let gate = if increase {
+            self.arpeggiator.gate + ARPEGGIATOR_GATE_PERCENT_STEP
+        } else {
+            self.arpeggiator.gate - ARPEGGIATOR_GATE_PERCENT_STEP
+        };
+        self.arpeggiator.set_gate(gate);
+        self.arpeggiator.gate
+    }
+
+    pub fn toggle_arpeggiator_latch(&mut self) -> bool {
+        self.arpeggiator.toggle_latch();
+        self.arpeggiator.latch
+    }
+
+    /// Sets the parameter of the arpeggiator to the value of the MIDI
+    /// control and returns the resulting value of the parameter
+    pub fn set_arpeggiator_parameter(&mut self, parameter: ArpeggiatorParameter, value: u8) -> u32 {
+        self.arpeggiator.set_parameter(parameter, value)
+    }
+
+    pub fn toggle_note_repeat(&mut self) -> bool {
+        self.note_repeat.toggle();
+        self.note_repeat.enabled
+    }
+
+    /// Switches the amount of the note repeat hits per beat to the
+    /// next one of `NOTE_REPEAT_RATES` and returns it.
+    pub fn switch_note_repeat_rate(&mut self) -> u32 {
+        self.note_repeat.switch_rate()
+    }
+
+    fn is_counting_in(&self) -> bool {
+        self.pending_actions.iter().any(|pending_action| {
+            Action::StartRecording.supersedes(&pending_action.action)
+        })
+    }
+
+    fn is_metronome_audible(&self) -> bool {
+        self.metronome.enabled && (!self.metronome.recording_only ||
+                                   self.state == State::Recording ||
+                                   self.is_counting_in())
+    }
+
+    fn start_recording(&mut self) {
+        if let State::Looping = self.state {
+            self.state = State::Recording;
+            self.record_buffer.clear();
+        }
+    }
+
+    /// The recording stops on the next beat if the clips are launched
+    /// on the beats, so the samples of any amount of beats can be
+    /// recorded. Otherwise it stops on the next bar.
+    fn recording_quantization(&self) -> LaunchQuantization {
+        match self.launch_quantization {
+            LaunchQuantization::Immediate | LaunchQuantization::NextBeat => LaunchQuantization::NextBeat,
+            _ => LaunchQuantization::NextBar,
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        if let State::Recording = self.state {
+            self.state = State::Looping;
+
+            let unit = match self.recording_quantization() {
+                LaunchQuantization::NextBeat => Quant(QUANTS_PER_BEAT),
+                _ => self.measure.quants_per_measure(),
+            };
+            self.normalize_record_buffer();
+            let length = self.measure.length_of_buffer(&self.record_buffer, unit);
+
+            // The recording ends with the current beat or measure, so
+            // the sample starts over from the next one
+            let Quant(unit_length) = unit;
+            let unit_ticks = unit_length * TICKS_PER_QUANT;
+            let end = (self.tick_cursor + unit_ticks) / unit_ticks * unit_length;
+            let shift = (length.as_u32() - end % length.as_u32()) % length.as_u32();
+            let sample = Sample::with_length(&self.record_buffer, &self.measure, length, Quant(shift));
+
+            let index = self.push_sample(sample);
+            self.scenes[self.current_scene].samples.push(index);
+            self.recalculate_amount_of_measures();
+        }
+    }
+
+    pub fn on_midi_event(&mut self, event: &AbsMidiEvent) {
+        self.timestamp = event.timestamp;
+        // The effects take only NoteOffs as the releases
+        let message = event.message.normalized();
+        if let TypedMidiMessage::NoteOn { channel, key, .. } = message {
+            self.last_played_note = Some((channel, key));
+        }
+        for message in self.chord_effect.transform(message) {
+            if !self.arpeggiator.handle_message(&message) &&
+                !self.note_repeat.handle_message(&message) {
+                self.play(message);
+            }
+        }
+    }
+
+    /// Sends the message out and records it if the looper is
+    /// recording
+    fn play(&mut self, message: TypedMidiMessage) {
+        if let State::Recording = self.state {
+            self.record_buffer.push(AbsMidiEvent {
+                message: message,
+                timestamp: self.timestamp,
+            });
+        }
+
+        // FIXME(#141): Handle result of the sink message feeding
+        self.note_tracker.feed(message).unwrap();
+    }
+
+    /// Changes the tempo by hand. The tempo automation stops being
+    /// followed until it is enabled again.
+    pub fn update_tempo_bpm(&mut self, tempo_bpm: u32) {
+        self.tempo_ramp = None;
+        self.following_tempo = false;
+        self.set_tempo_bpm(tempo_bpm);
+    }
+
+    fn set_tempo_bpm(&mut self, tempo_bpm: u32) {
+        if self.recording_tempo && tempo_bpm != self.measure.tempo_bpm {
+            self.tempo_automation.record(self.tick_cursor, tempo_bpm);
+        }
+
+        // The position is musical, so it stays the same and the
+        // tempo only changes how fast it moves
+        self.measure = Measure { tempo_bpm: tempo_bpm, .. self.measure.clone() };
+    }
+
+    /// Schedules the tempo change. The tempo is ramped over
+    /// `tempo_ramp_bars` bars if they are set.
+    pub fn change_tempo(&mut self, tempo_bpm: u32) {
+        if self.tempo_ramp_bars > 0 {
+            let bars = self.tempo_ramp_bars;
+            self.schedule(Action::RampTempo(tempo_bpm, bars));
+        } else {
+            self.schedule(Action::ChangeTempo(tempo_bpm));
+        }
+    }
+
+    fn start_tempo_ramp(&mut self, tempo_bpm: u32, bars: u32) {
+        self.following_tempo = false;
+        self.tempo_ramp = Some(TempoRamp::new(self.measure.tempo_bpm,
+                                              tempo_bpm,
+                                              bars * self.measure.ticks_per_measure()));
+    }
+
+    pub fn switch_tempo_ramp_bars(&mut self) -> u32 {
+        let next = TEMPO_RAMP_BARS.iter()
+            .position(|bars| *bars == self.tempo_ramp_bars)
+            .map_or(0, |index| (index + 1) % TEMPO_RAMP_BARS.len());
+        self.tempo_ramp_bars = TEMPO_RAMP_BARS[next];
+        self.tempo_ramp_bars
+    }
+
+    /// Starts recording the tempo automation from scratch or stops
+    /// it. Returns whether the tempo is being recorded.
+    pub fn toggle_tempo_recording(&mut self) -> bool {
+        self.recording_tempo = !self.recording_tempo;
+        if self.recording_tempo {
+            self.tempo_automation.clear();
+            let (tick_cursor, tempo_bpm) = (self.tick_cursor, self.measure.tempo_bpm);
+            self.tempo_automation.record(tick_cursor, tempo_bpm);
+        }
+        // The recorded automation is played back right away
+        self.following_tempo = !self.recording_tempo;
+        self.recording_tempo
+    }
+
+    /// Enables or suspends following the recorded tempo automation.
+    /// Returns whether it is followed.
+    pub fn toggle_tempo_automation(&mut self) -> bool {
+        self.following_tempo = !self.following_tempo && !self.tempo_automation.is_empty();
+        self.following_tempo
+    }
+
+    pub fn clear_tempo_automation(&mut self) {
+        self.recording_tempo = false;
+        self.following_tempo = false;
+        self.tempo_automation.clear();
+    }
+
+    pub fn toggle_midi_clock(&mut self) {
+        self.midi_clock = !self.midi_clock;
+    }
+
+    /// Sets the tempo so the beat of the measure has the given size.
+    /// Returns the new tempo.
+    pub fn tap_tempo(&mut self, beat_size_millis: u32) -> u32 {
+        let tempo_bpm = 240000 / (cmp::max(beat_size_millis, 1) * self.measure.beat_unit);
+        let tempo_bpm = cmp::min(cmp::max(tempo_bpm, MIN_TEMPO_BPM), MAX_TEMPO_BPM);
+        self.update_tempo_bpm(tempo_bpm);
+        tempo_bpm
+    }
+
+    /// Switches the recording grid to the next of the
+    /// `BEAT_DIVISIONS`. The recorded samples are not affected.
+    pub fn switch_beat_division(&mut self) -> u32 {
+        let current = self.measure.beat_division();
+        let next = BEAT_DIVISIONS.iter()
+            .position(|beat_division| *beat_division == current)
+            .map_or(0, |index| (index + 1) % BEAT_DIVISIONS.len());
+        self.set_beat_division(BEAT_DIVISIONS[next])
+    }
+
+    pub fn set_beat_division(&mut self, beat_division: u32) -> u32 {
+        self.measure.beat_division = beat_division;
+        self.measure.beat_division()
+    }
+
+    /// Switches to the next of the `TIME_SIGNATURES`. The recorded
+    /// samples are quantized to the current measure, so it is possible
+    /// only while the composition is empty.
+    pub fn switch_time_signature(&mut self) -> Option<(u32, u32)> {
+        if !self.composition.is_empty() || self.state == State::Recording {
+            return None;
+        }
+
+        let current = (self.measure.measure_size_bpm, self.measure.beat_unit);
+        let next = TIME_SIGNATURES.iter()
+            .position(|time_signature| *time_signature == current)
+            .map_or(0, |index| (index + 1) % TIME_SIGNATURES.len());
+        let (measure_size_bpm, beat_unit) = TIME_SIGNATURES[next];
+
+        self.measure = Measure {
+            measure_size_bpm: measure_size_bpm,
+            beat_unit: beat_unit,
+            beat_grouping: Vec::new(),
+            .. self.measure.clone()
+        };
+        self.tick_cursor %= self.loop_size_ticks();
+
+        Some((measure_size_bpm, beat_unit))
+    }
+
+    /// Reads the composition from the file right away, but replaces
+    /// the current composition with it according to the launch
+    /// quantization.
+    fn load_composition(&mut self, mut composition: CompositionData) {
+        // Compositions saved before the metronome became a separate
+        // component have it as the first sample. They are the only
+        // ones without the format version.
+        if composition.format_version == 0 && composition.samples.first().map_or(false, is_legacy_metronome) {
+            composition.samples.remove(0);
+            for scene in &mut composition.scenes {
+                scene.remove_sample(0);
+            }
+        }
+
+        self.note_tracker.close_opened_notes();
+        self.edit_history.clear();
+        self.measure = composition.measure;
+        self.tempo_ramp = None;
+        self.tempo_automation = composition.tempo_automation;
+        self.recording_tempo = false;
+        self.following_tempo = !self.tempo_automation.is_empty();
+        self.composition = composition.samples.iter().enumerate().map(|(index, sample_data)| {
+            let mut sample = Sample::from_sample_data(sample_data, &self.measure);
+            sample.seed_note_conditions(index);
+            sample
+        }).collect();
+        self.tick_cursor = 0;
+
+        self.scenes = if composition.scenes.is_empty() {
+            vec![Scene::new(String::from("Scene 1"), (0..self.composition.len()).collect())]
+        } else {
+            composition.scenes
+        };
+        self.current_scene = 0;
+
+        self.recalculate_amount_of_measures();
+        self.tick_cursor = self.loop_size_ticks() - 1;
+    }
+
+    /// The composition to save. The front-end writes it to the file,
+    /// so the engine thread never waits for the disk.
+    pub fn composition_data(&self) -> CompositionData {
+        CompositionData {
+            measure: self.measure.clone(),
+            samples: self.composition.iter().map(|sample| sample.as_sample_data()).collect(),
+            scenes: self.scenes.clone(),
+            tempo_automation: self.tempo_automation.clone(),
+            format_version: COMPOSITION_FORMAT_VERSION,
+        }
+    }
+
+    fn normalize_record_buffer(&mut self) {
+        if !self.record_buffer.is_empty() {
+            let t0 = self.record_buffer[0].timestamp;
+
+            for event in &mut self.record_buffer {
+                event.timestamp -= t0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Looper, State};
+    use midi::{DummyMidiNoteTracker, AbsMidiEvent, TypedMidiMessage};
+    use looper::{Action, ActionKind, LaunchQuantization, ClipState, Metronome, CompositionData, Sample, Scene, TempoAutomation};
+    use looper::PlaybackRate;
+    use looper::ChordMode;
+    use measure::Quant;
+    use hardcode::*;
+    use traits::Updatable;
+
+    #[test]
+    fn test_looper_initial_tick_cursor() {
+        let looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        assert_eq!(looper.tick_cursor, looper.amount_of_measures * looper.measure.ticks_per_measure() - 1);
+    }
+
+    #[test]
+    fn test_scene_switch_at_measure_bar() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        let scene = looper.add_scene();
+
+        looper.update(2);
+        looper.schedule_with_quantization(Action::SwitchScene(scene), LaunchQuantization::NextBar);
+        looper.update(measure_size_millis / 2);
+        assert_eq!(0, looper.current_scene);
+
+        looper.update(measure_size_millis / 2);
+        assert_eq!(scene, looper.current_scene);
+        assert!(looper.pending_actions.is_empty());
+    }
+
+    #[test]
+    fn test_scene_switch_after_cycles() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        let scene = looper.add_scene();
+
+        looper.schedule_with_quantization(Action::SwitchScene(scene), LaunchQuantization::AfterLoops(2));
+        looper.update(2);
+        assert_eq!(0, looper.current_scene);
+
+        looper.update(measure_size_millis);
+        assert_eq!(scene, looper.current_scene);
+    }
+
+    #[test]
+    fn test_update_snapshot() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        looper.last_sample_step_pattern(true);
+        looper.duplicate_last_sample();
+        let mut snapshot = looper.snapshot();
+        assert_eq!(2, snapshot.samples.len());
+
+        // The refilled snapshot is the same as the new one
+        let scene = looper.add_scene();
+        looper.switch_scene(scene);
+        looper.toggle_last_sample_in_current_scene();
+        let composition = looper.composition_data();
+        looper.schedule_with_quantization(Action::LoadComposition(composition), LaunchQuantization::NextBar);
+        looper.update_snapshot(&mut snapshot);
+        let new_snapshot = looper.snapshot();
+        assert_eq!(1, snapshot.samples.len());
+        assert_eq!(new_snapshot.current_scene_name, snapshot.current_scene_name);
+        assert_eq!(new_snapshot.clip_states, snapshot.clip_states);
+        assert_eq!(vec![ActionKind::LoadComposition], snapshot.pending_actions);
+    }
+
+    #[test]
+    fn test_toggle_last_sample_in_current_scene() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        assert_eq!(None, looper.toggle_last_sample_in_current_scene());
+
+        looper.last_sample_step_pattern(true);
+        let scene = looper.add_scene();
+        looper.switch_scene(scene);
+        assert_eq!(Some(true), looper.toggle_last_sample_in_current_scene());
+        assert_eq!(vec![0], looper.scenes[scene].samples);
+        assert_eq!(vec![0], looper.scenes[0].samples);
+
+        assert_eq!(Some(false), looper.toggle_last_sample_in_current_scene());
+        assert!(looper.scenes[scene].samples.is_empty());
+    }
+
+    #[test]
+    fn test_song_mode_loops_scenes() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        looper.add_scene();
+        looper.scenes[0].cycles = 1;
+        looper.scenes[1].cycles = 1;
+
+        looper.toggle_song_mode();
+        looper.update(2);
+        assert_eq!(1, looper.current_scene);
+
+        looper.update(measure_size_millis);
+        assert_eq!(0, looper.current_scene);
+    }
+
+    #[test]
+    fn test_stop_recording_at_measure_bar() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        looper.update(2);
+
+        looper.toggle_recording();
+        looper.on_midi_event(&AbsMidiEvent {
+            message: TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: 100 },
+            timestamp: 0,
+        });
+        looper.update(measure_size_millis / 2);
+        looper.toggle_recording();
+        assert!(looper.state == State::Recording);
+
+        looper.update(measure_size_millis / 4);
+        assert!(looper.state == State::Recording);
+
+        looper.update(measure_size_millis / 4);
+        assert!(looper.state == State::Looping);
+        assert_eq!(1, looper.composition.len());
+    }
+
+    #[test]
+    fn test_tempo_change_at_next_beat() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let beat_size_millis = looper.measure.beat_size_millis();
+        looper.update(2);
+
+        looper.schedule_with_quantization(Action::ChangeTempo(100), LaunchQuantization::NextBeat);
+        looper.schedule_with_quantization(Action::ChangeTempo(140), LaunchQuantization::NextBeat);
+        assert_eq!(1, looper.pending_actions.len());
+
+        looper.update(beat_size_millis / 2);
+        assert_eq!(120, looper.measure.tempo_bpm);
+
+        looper.update(beat_size_millis / 2);
+        assert_eq!(140, looper.measure.tempo_bpm);
+    }
+
+    #[test]
+    fn test_tempo_change_keeps_position() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        looper.update(2);
+        looper.update(looper.measure.beat_size_millis() / 2);
+        let tick_cursor = looper.tick_cursor;
+
+        looper.update_tempo_bpm(97);
+        assert_eq!(tick_cursor, looper.tick_cursor);
+    }
+
+    #[test]
+    fn test_tempo_ramp() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        looper.schedule_with_quantization(Action::RampTempo(140, 2), LaunchQuantization::Immediate);
+
+        looper.update(looper.measure.measure_size_millis());
+        assert!(looper.measure.tempo_bpm > 120 && looper.measure.tempo_bpm < 140);
+
+        looper.update(looper.measure.measure_size_millis() * 2);
+        assert_eq!(140, looper.measure.tempo_bpm);
+        assert!(looper.tempo_ramp.is_none());
+    }
+
+    #[test]
+    fn test_tempo_automation() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let beat_size_millis = looper.measure.beat_size_millis();
+        looper.update(2);
+
+        assert!(looper.toggle_tempo_recording());
+        looper.update(beat_size_millis * 2);
+        looper.update_tempo_bpm(100);
+        looper.update(beat_size_millis);
+        assert!(!looper.toggle_tempo_recording());
+        assert_eq!(2, looper.tempo_automation.points.len());
+
+        looper.update(beat_size_millis);
+        assert_eq!(100, looper.measure.tempo_bpm);
+
+        // Changing the tempo by hand suspends the automation
+        looper.update_tempo_bpm(120);
+        looper.update(beat_size_millis);
+        assert_eq!(120, looper.measure.tempo_bpm);
+
+        assert!(looper.toggle_tempo_automation());
+        looper.update(beat_size_millis);
+        assert_eq!(looper.tempo_automation.tempo_bpm(looper.tick_cursor), Some(looper.measure.tempo_bpm));
+
+        looper.clear_tempo_automation();
+        assert!(looper.tempo_automation.is_empty());
+        assert!(!looper.toggle_tempo_automation());
+    }
+
+    #[test]
+    fn test_immediate_action() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        looper.schedule_with_quantization(Action::ChangeTempo(100), LaunchQuantization::Immediate);
+        assert_eq!(100, looper.measure.tempo_bpm);
+        assert!(looper.pending_actions.is_empty());
+    }
+
+    #[test]
+    fn test_tap_tempo() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        assert_eq!(100, looper.tap_tempo(600));
+        assert_eq!(100, looper.measure.tempo_bpm);
+
+        looper.measure.measure_size_bpm = 6;
+        looper.measure.beat_unit = 8;
+        assert_eq!(120, looper.tap_tempo(250));
+
+        assert_eq!(MAX_TEMPO_BPM, looper.tap_tempo(10));
+        assert_eq!(MAX_TEMPO_BPM, looper.tap_tempo(0));
+    }
+
+    #[test]
+    fn test_toggle_clip() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        looper.update(2);
+
+        looper.toggle_recording();
+        looper.on_midi_event(&AbsMidiEvent {
+            message: TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: 100 },
+            timestamp: 0,
+        });
+        looper.toggle_recording();
+        looper.update(measure_size_millis);
+        assert_eq!(vec![ClipState::Playing], looper.clip_states());
+
+        looper.toggle_clip(0);
+        assert_eq!(vec![ClipState::Stopping], looper.clip_states());
+
+        looper.update(measure_size_millis);
+        assert_eq!(vec![ClipState::Stopped], looper.clip_states());
+
+        looper.toggle_clip(0);
+        looper.toggle_clip(0);
+        assert_eq!(vec![ClipState::Stopped], looper.clip_states());
+        assert!(looper.pending_actions.is_empty());
+    }
+
+    #[test]
+    fn test_sample_edits_are_undoable() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        looper.update(2);
+
+        looper.toggle_recording();
+        looper.on_midi_event(&AbsMidiEvent {
+            message: TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: 100 },
+            timestamp: 0,
+        });
+        looper.toggle_recording();
+        looper.update(measure_size_millis);
+        assert_eq!(1, looper.amount_of_measures);
+
+        looper.double_last_sample();
+        assert_eq!(2, looper.amount_of_measures);
+        assert_eq!(Some(3), looper.resize_last_sample(true));
+        assert_eq!(3, looper.amount_of_measures);
+        looper.duplicate_last_sample();
+        assert_eq!(2, looper.composition.len());
+        assert_eq!(vec![0, 1], looper.scenes[0].samples);
+
+        looper.undo_last_edit();
+        assert_eq!(1, looper.composition.len());
+        assert_eq!(vec![0], looper.scenes[0].samples);
+        looper.undo_last_edit();
+        assert_eq!(2, looper.amount_of_measures);
+        looper.undo_last_edit();
+        assert_eq!(1, looper.amount_of_measures);
+        assert_eq!(1, looper.composition[0].amount_of_measures());
+
+        looper.undo_last_edit();
+        assert_eq!(1, looper.composition.len());
+    }
+
+    #[test]
+    fn test_pending_clips_follow_merged_samples() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        for _ in 0..4 {
+            looper.last_sample_step_pattern(true);
+        }
+        looper.set_sample_muted(1, true);
+        looper.set_sample_muted(3, true);
+        looper.schedule_with_quantization(Action::LaunchSample(1), LaunchQuantization::NextBar);
+        looper.schedule_with_quantization(Action::LaunchSample(3), LaunchQuantization::NextBar);
+
+        // The sample 1 is gone, the sample 3 becomes the sample 1
+        looper.merge_samples(&[0, 1]);
+        assert_eq!(1, looper.pending_actions.len());
+        assert!(looper.composition[1].is_muted());
+
+        looper.update(2);
+        looper.update(measure_size_millis);
+        assert!(looper.pending_actions.is_empty());
+        assert!(!looper.composition[1].is_muted());
+    }
+
+    #[test]
+    fn test_step_pattern_is_written_into_its_sample() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let (index, mut pattern) = looper.last_sample_step_pattern(true);
+
+        // A new take is added while the pattern is edited
+        looper.duplicate_last_sample();
+        pattern.toggle_step(0, 0);
+        assert!(looper.write_sample_step_pattern(index, &pattern));
+        assert!(!looper.composition[index].buffer.is_empty());
+        assert!(looper.composition[1].buffer.is_empty());
+
+        looper.undo_last_edit();
+        looper.undo_last_edit();
+        looper.undo_last_edit();
+        assert!(!looper.write_sample_step_pattern(index, &pattern));
+    }
+
+    #[test]
+    fn test_sample_settings_are_undoable() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        looper.last_sample_step_pattern(true);
+        let swing = looper.composition[0].swing();
+        let quantize_strength = looper.composition[0].quantize_strength();
+        let edits = looper.edit_history.len();
+
+        looper.change_last_sample_playback_rate(true);
+        looper.toggle_last_sample_reverse();
+        looper.change_last_sample_swing(true);
+        looper.change_last_sample_quantize_strength(false);
+        looper.requantize_last_sample();
+        looper.toggle_last_sample_groove();
+        assert_eq!(edits + 6, looper.edit_history.len());
+        assert!(looper.composition[0].playback_rate() != PlaybackRate::Normal);
+
+        for _ in 0..6 {
+            looper.undo_last_edit();
+        }
+        assert_eq!(PlaybackRate::Normal, looper.composition[0].playback_rate());
+        assert_eq!(swing, looper.composition[0].swing());
+        assert_eq!(quantize_strength, looper.composition[0].quantize_strength());
+    }
+
+    #[test]
+    fn test_samples_of_any_amount_of_beats() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let beat_size_millis = looper.measure.beat_size_millis();
+        looper.launch_quantization = LaunchQuantization::NextBeat;
+        looper.update(2);
+
+        looper.toggle_recording();
+        for &timestamp in &[0, beat_size_millis, 2 * beat_size_millis] {
+            looper.on_midi_event(&AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: 100 },
+                timestamp: timestamp,
+            });
+        }
+        looper.toggle_recording();
+        looper.update(beat_size_millis);
+        assert_eq!(Quant(3 * QUANTS_PER_BEAT), looper.composition[0].quant_length());
+        // Three beats repeat four times in three measures of 4/4
+        assert_eq!(3, looper.amount_of_measures);
+
+        assert_eq!(Some(4), looper.resize_last_sample_by_beat(true));
+        assert_eq!(1, looper.amount_of_measures);
+        assert_eq!(Some(1), looper.resize_last_sample(false));
+        assert_eq!(Some(2), looper.resize_last_sample(true));
+    }
+
+    #[test]
+    fn test_merge_and_split_samples() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure_size_millis = looper.measure.measure_size_millis();
+        looper.update(2);
+
+        for &(channel, key) in &[(0, 36), (1, 38)] {
+            looper.toggle_recording();
+            looper.on_midi_event(&AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn { channel: channel, key: key, velocity: 100 },
+                timestamp: 0,
+            });
+            looper.toggle_recording();
+            looper.update(measure_size_millis);
+        }
+        assert_eq!(2, looper.composition.len());
+
+        looper.bounce_current_scene();
+        assert_eq!(1, looper.composition.len());
+        assert_eq!(vec![0], looper.scenes[0].samples);
+        assert_eq!(2, looper.composition[0].buffer.len());
+
+        assert_eq!(2, looper.split_last_sample_by_keys());
+        assert_eq!(vec![0, 1], looper.scenes[0].samples);
+        looper.merge_samples(&[0, 1]);
+        assert_eq!(2, looper.split_last_sample_by_channel());
+        assert_eq!(2, looper.composition.len());
+        assert_eq!(1, looper.split_last_sample_by_channel());
+    }
+
+    #[test]
+    fn test_arpeggiated_notes_are_recorded() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let beat_size_millis = looper.measure.beat_size_millis();
+        looper.update(2);
+        looper.toggle_arpeggiator();
+
+        looper.toggle_recording();
+        for key in &[60, 64] {
+            looper.on_midi_event(&AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn { channel: 0, key: *key, velocity: 100 },
+                timestamp: 1000,
+            });
+        }
+        assert!(looper.record_buffer.is_empty());
+
+        looper.update(beat_size_millis);
+        let keys: Vec<u8> = looper.record_buffer.iter().filter_map(|event| {
+            match event.message {
+                TypedMidiMessage::NoteOn { key, .. } => Some(key),
+                _ => None,
+            }
+        }).collect();
+        assert_eq!(vec![60, 64, 60, 64], keys);
+        assert!(looper.record_buffer[0].timestamp > 1000);
+    }
+
+    #[test]
+    fn test_count_in() {
+        let metronome = Metronome { count_in_measures: 2, .. Metronome::default() };
+        let mut looper = Looper::new(DummyMidiNoteTracker, metronome);
+        let measure_size_millis = looper.measure.measure_size_millis();
+        looper.update(2);
+
+        looper.toggle_recording();
+        assert!(looper.state == State::Looping);
+        assert!(looper.is_counting_in());
+
+        looper.update(measure_size_millis);
+        assert!(looper.state == State::Looping);
+
+        looper.update(measure_size_millis);
+        assert!(looper.state == State::Recording);
+        assert!(!looper.is_counting_in());
+    }
+
+    #[test]
+    fn test_metronome_recording_only() {
+        let metronome = Metronome { recording_only: true, .. Metronome::default() };
+        let mut looper = Looper::new(DummyMidiNoteTracker, metronome);
+        assert!(!looper.is_metronome_audible());
+
+        looper.toggle_recording();
+        assert!(looper.is_metronome_audible());
+
+        looper.toggle_metronome();
+        assert!(!looper.is_metronome_audible());
+    }
+
+    #[test]
+    fn test_note_on_without_velocity_releases_chord() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        looper.chord_effect.mode = ChordMode::Harmonizer;
+        for &velocity in &[100, 0] {
+            looper.on_midi_event(&AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: velocity },
+                timestamp: 0,
+            });
+        }
+
+        // None of the keys of the released chord are sounding
+        assert_eq!(3, looper.chord_effect.transform(TypedMidiMessage::NoteOn {
+            channel: 0,
+            key: 64,
+            velocity: 100,
+        }).len());
+    }
+
+    #[test]
+    fn test_legacy_metronome_is_dropped_on_load() {
+        let mut looper = Looper::new(DummyMidiNoteTracker, Metronome::default());
+        let measure = looper.measure.clone();
+        let legacy_metronome = Sample::new(&[AbsMidiEvent {
+            message: TypedMidiMessage::NoteOn { channel: CONTROL_CHANNEL_NUMBER, key: BEAT_KEY_NUMBER, velocity: 100 },
+            timestamp: 0,
+        }], &measure, 0);
+        let sample = Sample::new(&[AbsMidiEvent {
+            message: TypedMidiMessage::NoteOn { channel: 0, key: 60, velocity: 100 },
+            timestamp: 0,
+        }], &measure, 0);
+
+        let composition = CompositionData {
+            samples: vec![legacy_metronome.as_sample_data(), sample.as_sample_data()],
+            measure: measure.clone(),
+            scenes: vec![Scene::new(String::from("Scene 1"), vec![1])],
+            tempo_automation: TempoAutomation::default(),
+            format_version: 0,
+        };
+
+        looper.load_composition(composition.clone());
+        assert_eq!(1, looper.composition.len());
+        assert_eq!(vec![0], looper.scenes[0].samples);
+
+        // The same notes recorded by the user are kept
+        looper.load_composition(CompositionData {
+            format_version: COMPOSITION_FORMAT_VERSION,
+            .. composition
+        });
+        assert_eq!(2, looper.composition.len());
+
+        // So is the empty first sample of an old composition
+        looper.load_composition(CompositionData {
+            samples: vec![Sample::new(&[], &measure, 0).as_sample_data(), sample.as_sample_data()],
+            measure: measure,
+            scenes: Vec::new(),
+            tempo_automation: TempoAutomation::default(),
+            format_version: 0,
+        });
+        assert_eq!(2, looper.composition.len());
     }
 }
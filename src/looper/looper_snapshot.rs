@@ -0,0 +1,39 @@
+use measure::{Measure, Quant};
+use midi::Note;
+use looper::{ActionKind, LaunchQuantization, ClipState, AutomationLane, AutomationPoint};
+
+/// Notes and values of the selected controller of the sample played
+/// in the current measure of the looper. Their quants are relative to
+/// the measure, the samples shorter than it are repeated in it.
+#[derive(Clone, Debug)]
+pub struct SampleSnapshot {
+    pub notes: Vec<Note>,
+    pub automation: Vec<AutomationPoint>,
+    /// Notes played by the note selected in the piano roll
+    pub selected_notes: Vec<Note>,
+    /// Size of the measure of the looper
+    pub window_size: Quant,
+}
+
+/// Copy of the state of the looper needed to render it. The looper
+/// lives on the engine thread, so the front-end only sees the
+/// snapshots it publishes. The engine refills the same snapshots, so
+/// their vectors keep their capacity.
+#[derive(Clone)]
+pub struct LooperSnapshot {
+    pub recording: bool,
+    pub measure: Measure,
+    /// Musical position of the looper in ticks
+    pub tick_cursor: u32,
+    /// Samples of the current scene
+    pub samples: Vec<SampleSnapshot>,
+    /// Controller shown in the automation lane
+    pub automation_lane: AutomationLane,
+    pub amount_of_scenes: usize,
+    pub current_scene: usize,
+    pub current_scene_name: String,
+    /// Only the kinds, so a pending composition is not copied
+    pub pending_actions: Vec<ActionKind>,
+    pub launch_quantization: LaunchQuantization,
+    pub clip_states: Vec<ClipState>,
+}
@@ -0,0 +1,151 @@
+use hardcode::*;
+use measure::Measure;
+use midi::{TypedMidiMessage, MidiSink};
+
+/// Clicks the beats of the looper. It is not a part of the
+/// composition, so it is never saved or undone.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metronome {
+    pub enabled: bool,
+    pub channel: u8,
+    /// Key of the first beat of the measure
+    pub accent_key: u8,
+    pub beat_key: u8,
+    pub accent_velocity: u8,
+    /// Velocity of the first beat of every group of beats
+    pub beat_velocity: u8,
+    /// Velocity of the rest of the beats and of the subdivisions
+    pub subdivision_velocity: u8,
+    /// Amount of clicks per beat
+    pub subdivision: u32,
+    /// Click only while recording or counting in
+    pub recording_only: bool,
+    /// Amount of measures to count in before the recording starts
+    pub count_in_measures: u32,
+}
+
+impl Default for Metronome {
+    fn default() -> Metronome {
+        Metronome {
+            enabled: true,
+            channel: CONTROL_CHANNEL_NUMBER,
+            accent_key: BEAT_KEY_NUMBER,
+            beat_key: BEAT_KEY_NUMBER,
+            accent_velocity: BEAT_ACCENT_VELOCITY,
+            beat_velocity: BEAT_VELOCITY,
+            subdivision_velocity: BEAT_SUBDIVISION_VELOCITY,
+            subdivision: 1,
+            recording_only: false,
+            count_in_measures: 0,
+        }
+    }
+}
+
+impl Metronome {
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /// Feeds the clicks that happen when the looper moves from the tick
+    /// `from` (exclusively) to the tick `to` (inclusively).
+    pub fn replay<Sink: MidiSink>(&self, measure: &Measure, from: u32, to: u32, sink: &mut Sink) {
+        let subdivision = if self.subdivision > 0 { self.subdivision } else { 1 };
+        let clicks_per_measure = subdivision * measure.measure_size_bpm;
+
+        for click in from * subdivision / TICKS_PER_BEAT + 1..to * subdivision / TICKS_PER_BEAT + 1 {
+            let (key, velocity) = if click % clicks_per_measure == 0 {
+                (self.accent_key, self.accent_velocity)
+            } else if click % subdivision == 0 && measure.is_group_start(click / subdivision) {
+                (self.beat_key, self.beat_velocity)
+            } else {
+                (self.beat_key, self.subdivision_velocity)
+            };
+
+            // FIXME(#141): Handle result of the sink message feeding
+            sink.feed(TypedMidiMessage::NoteOn {
+                channel: self.channel,
+                key: key,
+                velocity: velocity,
+            }).unwrap();
+            sink.feed(TypedMidiMessage::NoteOff {
+                channel: self.channel,
+                key: key,
+                velocity: 0,
+            }).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Metronome;
+    use hardcode::*;
+    use measure::Measure;
+    use midi::{TypedMidiMessage, MidiSink};
+    use pm::types::Result;
+
+    fn default_measure() -> Measure {
+        Measure {
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            measure_size_bpm: DEFAULT_MEASURE_SIZE_BPM,
+            beat_unit: DEFAULT_BEAT_UNIT,
+            beat_grouping: Vec::new(),
+            beat_division: DEFAULT_BEAT_DIVISION,
+        }
+    }
+
+    struct RecordingSink {
+        messages: Vec<TypedMidiMessage>,
+    }
+
+    impl MidiSink for RecordingSink {
+        fn feed(&mut self, midi_message: TypedMidiMessage) -> Result<()> {
+            self.messages.push(midi_message);
+            Ok(())
+        }
+    }
+
+    fn clicked_velocities(metronome: &Metronome, measure: &Measure, from: u32, to: u32) -> Vec<u8> {
+        let mut sink = RecordingSink { messages: Vec::new() };
+        metronome.replay(measure, from, to, &mut sink);
+        sink.messages.iter().filter_map(|message| {
+            match *message {
+                TypedMidiMessage::NoteOn { velocity, .. } => Some(velocity),
+                _ => None,
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn test_metronome_accents() {
+        let metronome = Metronome::default();
+        let ticks_per_measure = default_measure().ticks_per_measure();
+
+        assert_eq!(vec![BEAT_VELOCITY, BEAT_VELOCITY, BEAT_VELOCITY, BEAT_ACCENT_VELOCITY],
+                   clicked_velocities(&metronome, &default_measure(), 0, ticks_per_measure));
+        assert!(clicked_velocities(&metronome, &default_measure(), 1, TICKS_PER_BEAT - 1).is_empty());
+    }
+
+    #[test]
+    fn test_metronome_subdivision() {
+        let metronome = Metronome { subdivision: 2, .. Metronome::default() };
+        
+        assert_eq!(vec![BEAT_SUBDIVISION_VELOCITY, BEAT_VELOCITY],
+                   clicked_velocities(&metronome, &default_measure(), 0, TICKS_PER_BEAT));
+    }
+
+    #[test]
+    fn test_metronome_compound_accents() {
+        let metronome = Metronome::default();
+        let measure = Measure {
+            measure_size_bpm: 6,
+            beat_unit: 8,
+            .. default_measure()
+        };
+
+        assert_eq!(vec![BEAT_SUBDIVISION_VELOCITY, BEAT_SUBDIVISION_VELOCITY, BEAT_VELOCITY,
+                        BEAT_SUBDIVISION_VELOCITY, BEAT_SUBDIVISION_VELOCITY, BEAT_ACCENT_VELOCITY],
+                   clicked_velocities(&metronome, &measure, 0, measure.ticks_per_measure()));
+    }
+}
@@ -2,8 +2,36 @@ pub mod sample;
 pub mod sample_data;
 pub mod looper;
 pub mod composition_data;
+pub mod playback_rate;
+pub mod scene;
+pub mod action;
+pub mod clip_state;
+pub mod metronome;
+pub mod groove;
+pub mod tempo_automation;
+pub mod looper_snapshot;
+pub mod sample_edit;
+pub mod arpeggiator;
+pub mod chord_effect;
+pub mod note_repeat;
+pub mod automation_lane;
+pub mod step_pattern;
 
 pub use self::sample::Sample;
 pub use self::sample_data::SampleData;
 pub use self::looper::Looper;
 pub use self::composition_data::CompositionData;
+pub use self::playback_rate::PlaybackRate;
+pub use self::scene::Scene;
+pub use self::action::{Action, ActionKind, PendingAction, LaunchQuantization};
+pub use self::clip_state::ClipState;
+pub use self::metronome::Metronome;
+pub use self::groove::Groove;
+pub use self::tempo_automation::{TempoRamp, TempoAutomation};
+pub use self::looper_snapshot::{LooperSnapshot, SampleSnapshot};
+pub use self::sample_edit::SampleEdit;
+pub use self::arpeggiator::{Arpeggiator, ArpeggiatorMode, ArpeggiatorParameter};
+pub use self::chord_effect::{ChordEffect, ChordMode, Scale};
+pub use self::note_repeat::NoteRepeat;
+pub use self::automation_lane::{AutomationLane, AutomationPoint};
+pub use self::step_pattern::{StepPattern, StepRow, StepChange};
@@ -0,0 +1,254 @@
+use std::cmp;
+
+use hardcode::*;
+use midi::TypedMidiMessage;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Pad {
+    channel: u8,
+    key: u8,
+    velocity: u8,
+    /// The pad has been hit on the current step
+    triggered: bool,
+}
+
+/// Retriggers the held pads on the steps of the measure. The pads
+/// played to the looper while it is enabled are taken by the note
+/// repeat and the looper plays and records the hits generated by it
+/// instead. The aftertouch of the held pads changes the velocity of
+/// the following hits.
+pub struct NoteRepeat {
+    pub enabled: bool,
+    /// Amount of the hits per beat, one of `NOTE_REPEAT_RATES`
+    pub rate: u32,
+
+    pads: Vec<Pad>,
+    /// Keys hit and not released yet
+    sounding_keys: Vec<(u8, u8)>,
+}
+
+impl Default for NoteRepeat {
+    fn default() -> NoteRepeat {
+        NoteRepeat {
+            enabled: false,
+            rate: DEFAULT_NOTE_REPEAT_RATE,
+            pads: Vec::new(),
+            sounding_keys: Vec::new(),
+        }
+    }
+}
+
+impl NoteRepeat {
+    /// Takes the pad played to the looper. Returns false if the
+    /// message is not for the note repeat.
+    pub fn handle_message(&mut self, message: &TypedMidiMessage) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        // Many pads are released with NoteOn without velocity
+        match message.normalized() {
+            TypedMidiMessage::NoteOn { channel, key, velocity } => {
+                self.pads.retain(|pad| (pad.channel, pad.key) != (channel, key));
+                // The pad is hit right away on the next advance
+                self.pads.push(Pad {
+                    channel: channel,
+                    key: key,
+                    velocity: velocity,
+                    triggered: false,
+                });
+                true
+            },
+
+            TypedMidiMessage::NoteOff { channel, key, .. } => {
+                self.pads.retain(|pad| (pad.channel, pad.key) != (channel, key));
+                true
+            },
+
+            TypedMidiMessage::PolyAftertouch { channel, key, pressure } => {
+                let mut handled = false;
+                for pad in self.pads.iter_mut().filter(|pad| (pad.channel, pad.key) == (channel, key)) {
+                    pad.velocity = cmp::max(1, pressure);
+                    handled = true;
+                }
+                handled
+            },
+
+            TypedMidiMessage::ChannelAftertouch { channel, pressure } => {
+                let mut handled = false;
+                for pad in self.pads.iter_mut().filter(|pad| pad.channel == channel) {
+                    pad.velocity = cmp::max(1, pressure);
+                    handled = true;
+                }
+                handled
+            },
+
+            _ => false,
+        }
+    }
+
+    /// Turns the note repeat on or off. The hits sounding are
+    /// released on the next advance.
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.pads.clear();
+    }
+
+    pub fn switch_rate(&mut self) -> u32 {
+        let next = NOTE_REPEAT_RATES.iter()
+            .position(|rate| *rate == self.rate)
+            .map_or(0, |index| (index + 1) % NOTE_REPEAT_RATES.len());
+        self.rate = NOTE_REPEAT_RATES[next];
+        self.rate
+    }
+
+    fn step_ticks(&self) -> u32 {
+        QUANTS_PER_BEAT / self.rate * TICKS_PER_QUANT
+    }
+
+    /// Generates the messages for the looper moving from the tick
+    /// `from` (exclusively) to the tick `to` (inclusively). The hits
+    /// are aligned to the beats and released in the middle of the
+    /// steps.
+    pub fn advance(&mut self, from: u32, to: u32) -> Vec<TypedMidiMessage> {
+        if !self.enabled {
+            return self.release();
+        }
+
+        let mut messages = Vec::new();
+
+        let step_ticks = self.step_ticks();
+        let half_step_ticks = step_ticks / 2;
+        if (from + half_step_ticks) / step_ticks != (to + half_step_ticks) / step_ticks {
+            messages.extend(self.release());
+        }
+
+        if from / step_ticks != to / step_ticks {
+            messages.extend(self.release());
+            for pad in &mut self.pads {
+                pad.triggered = false;
+            }
+        }
+
+        for pad in self.pads.iter_mut().filter(|pad| !pad.triggered) {
+            pad.triggered = true;
+            if !self.sounding_keys.contains(&(pad.channel, pad.key)) {
+                self.sounding_keys.push((pad.channel, pad.key));
+                messages.push(TypedMidiMessage::NoteOn {
+                    channel: pad.channel,
+                    key: pad.key,
+                    velocity: pad.velocity,
+                });
+            }
+        }
+
+        messages
+    }
+
+    fn release(&mut self) -> Vec<TypedMidiMessage> {
+        self.sounding_keys.drain(..).map(|(channel, key)| {
+            TypedMidiMessage::NoteOff {
+                channel: channel,
+                key: key,
+                velocity: 0,
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoteRepeat;
+    use midi::TypedMidiMessage;
+
+    fn note_on(key: u8, velocity: u8) -> TypedMidiMessage {
+        TypedMidiMessage::NoteOn { channel: 0, key: key, velocity: velocity }
+    }
+
+    fn note_off(key: u8) -> TypedMidiMessage {
+        TypedMidiMessage::NoteOff { channel: 0, key: key, velocity: 0 }
+    }
+
+    /// Messages generated within the given amount of ticks with the
+    /// ticks they are generated at
+    fn play_ticks(note_repeat: &mut NoteRepeat, from: u32, to: u32) -> Vec<(u32, TypedMidiMessage)> {
+        let mut messages = Vec::new();
+        for tick in from..to {
+            for message in note_repeat.advance(tick, tick + 1) {
+                messages.push((tick + 1, message));
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn test_retrigger() {
+        let mut note_repeat = NoteRepeat::default();
+        note_repeat.toggle();
+        note_repeat.rate = 4;
+        let step_ticks = note_repeat.step_ticks();
+
+        assert!(note_repeat.handle_message(&note_on(36, 100)));
+        assert_eq!(vec![
+            (1, note_on(36, 100)),
+            (step_ticks / 2, note_off(36)),
+            (step_ticks, note_on(36, 100)),
+            (step_ticks * 3 / 2, note_off(36)),
+            (step_ticks * 2, note_on(36, 100)),
+        ], play_ticks(&mut note_repeat, 0, step_ticks * 2));
+
+        assert!(note_repeat.handle_message(&note_off(36)));
+        assert_eq!(vec![(step_ticks * 5 / 2, note_off(36))],
+                   play_ticks(&mut note_repeat, step_ticks * 2, step_ticks * 4));
+    }
+
+    #[test]
+    fn test_note_on_without_velocity_releases_pad() {
+        let mut note_repeat = NoteRepeat::default();
+        note_repeat.toggle();
+        let step_ticks = note_repeat.step_ticks();
+
+        note_repeat.handle_message(&note_on(36, 100));
+        assert_eq!(vec![note_on(36, 100)], note_repeat.advance(0, 1));
+        assert!(note_repeat.handle_message(&note_on(36, 0)));
+        assert_eq!(vec![(step_ticks / 2, note_off(36))],
+                   play_ticks(&mut note_repeat, 1, step_ticks * 2));
+    }
+
+    #[test]
+    fn test_aftertouch_changes_velocity() {
+        let mut note_repeat = NoteRepeat::default();
+        note_repeat.toggle();
+        let step_ticks = note_repeat.step_ticks();
+
+        note_repeat.handle_message(&note_on(36, 100));
+        note_repeat.handle_message(&note_on(38, 100));
+        assert!(note_repeat.handle_message(&TypedMidiMessage::PolyAftertouch {
+            channel: 0,
+            key: 38,
+            pressure: 50,
+        }));
+        assert!(!note_repeat.handle_message(&TypedMidiMessage::PolyAftertouch {
+            channel: 0,
+            key: 40,
+            pressure: 50,
+        }));
+        assert_eq!(vec![note_on(36, 100), note_on(38, 50)], note_repeat.advance(0, 1));
+
+        assert!(note_repeat.handle_message(&TypedMidiMessage::ChannelAftertouch {
+            channel: 0,
+            pressure: 0,
+        }));
+        assert_eq!(vec![note_off(36), note_off(38), note_on(36, 1), note_on(38, 1)],
+                   note_repeat.advance(step_ticks - 1, step_ticks));
+    }
+
+    #[test]
+    fn test_rates() {
+        let mut note_repeat = NoteRepeat::default();
+        note_repeat.rate = 0;
+        assert_eq!(2, note_repeat.switch_rate());
+        assert_eq!(3, note_repeat.switch_rate());
+        assert_eq!(4, note_repeat.switch_rate());
+    }
+}
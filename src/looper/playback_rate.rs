@@ -0,0 +1,80 @@
+/// Speed at which a sample is replayed relatively to the tempo of the
+/// looper.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum PlaybackRate {
+    Half,
+    TwoThirds,
+    Normal,
+    ThreeHalves,
+    Double,
+}
+
+const PLAYBACK_RATES: [PlaybackRate; 5] = [
+    PlaybackRate::Half,
+    PlaybackRate::TwoThirds,
+    PlaybackRate::Normal,
+    PlaybackRate::ThreeHalves,
+    PlaybackRate::Double,
+];
+
+impl Default for PlaybackRate {
+    fn default() -> PlaybackRate {
+        PlaybackRate::Normal
+    }
+}
+
+impl PlaybackRate {
+    /// The rate as a `(numerator, denominator)` pair. A sample
+    /// recorded with the length of `L` quants is replayed in `L *
+    /// denominator / numerator` quants of the looper.
+    pub fn as_ratio(&self) -> (u32, u32) {
+        match *self {
+            PlaybackRate::Half => (1, 2),
+            PlaybackRate::TwoThirds => (2, 3),
+            PlaybackRate::Normal => (1, 1),
+            PlaybackRate::ThreeHalves => (3, 2),
+            PlaybackRate::Double => (2, 1),
+        }
+    }
+
+    pub fn as_percent(&self) -> u32 {
+        let (numerator, denominator) = self.as_ratio();
+        (numerator * 100 + denominator / 2) / denominator
+    }
+
+    pub fn faster(&self) -> PlaybackRate {
+        let index = self.index();
+        PLAYBACK_RATES[if index + 1 < PLAYBACK_RATES.len() { index + 1 } else { index }]
+    }
+
+    pub fn slower(&self) -> PlaybackRate {
+        let index = self.index();
+        PLAYBACK_RATES[if index > 0 { index - 1 } else { index }]
+    }
+
+    fn index(&self) -> usize {
+        PLAYBACK_RATES.iter().position(|rate| rate == self).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlaybackRate;
+
+    #[test]
+    fn test_playback_rate_bounds() {
+        assert_eq!(PlaybackRate::Double, PlaybackRate::Double.faster());
+        assert_eq!(PlaybackRate::Half, PlaybackRate::Half.slower());
+        assert_eq!(PlaybackRate::ThreeHalves, PlaybackRate::Normal.faster());
+        assert_eq!(PlaybackRate::TwoThirds, PlaybackRate::Normal.slower());
+    }
+
+    #[test]
+    fn test_playback_rate_percent() {
+        assert_eq!(50, PlaybackRate::Half.as_percent());
+        assert_eq!(67, PlaybackRate::TwoThirds.as_percent());
+        assert_eq!(100, PlaybackRate::Normal.as_percent());
+        assert_eq!(150, PlaybackRate::ThreeHalves.as_percent());
+        assert_eq!(200, PlaybackRate::Double.as_percent());
+    }
+}
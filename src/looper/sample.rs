@@ -1,116 +1,990 @@
-use sdl2::render::Renderer;
+use std::cmp::{self, Ordering};
+use std::mem;
+use std::collections::{BTreeMap, BTreeSet};
+
+use num::integer::{gcd, lcm};
+use rand::{XorShiftRng, SeedableRng};
 
 use midi;
-use midi::{AbsMidiEvent, Note, MidiSink};
+use midi::{AbsMidiEvent, TypedMidiMessage, Note, MidiSink};
 use measure::*;
-use looper::SampleData;
+use hardcode::*;
+use looper::{SampleData, PlaybackRate, Groove, AutomationLane, AutomationPoint, StepPattern};
+
+/// Moves the events according to the groove keeping them inside of the
+/// sample of the given length
+fn apply_groove(events: &mut Vec<QuantMidiEvent>, groove: &Groove, Quant(length): Quant) {
+    let length = length as i64;
+
+    for event in events.iter_mut() {
+        let quant = groove.apply(event.quant);
+        event.quant = Quant((((quant % length) + length) % length) as u32);
+    }
+
+    events.sort_by_key(|event| (event.quant, event.offset));
+}
+
+/// The condition following the given one in `NOTE_CONDITIONS`, or
+/// preceding it if not `forward`
+fn switch_condition(condition: NoteCondition, forward: bool) -> NoteCondition {
+    let amount = NOTE_CONDITIONS.len();
+    let next = match NOTE_CONDITIONS.iter().position(|known| *known == condition) {
+        Some(index) if forward => (index + 1) % amount,
+        Some(index) => (index + amount - 1) % amount,
+        None => 0,
+    };
+    NOTE_CONDITIONS[next]
+}
+
+/// The key the row of the step sequencer has moved the key to
+fn changed_key(key_changes: &[(u8, u8)], key: u8) -> u8 {
+    key_changes.iter()
+        .find(|&&(old_key, _)| old_key == key)
+        .map_or(key, |&(_, new_key)| new_key)
+}
+
+fn is_note_on(message: &TypedMidiMessage, channel: u8, key: u8) -> bool {
+    match *message {
+        TypedMidiMessage::NoteOn { channel: note_channel, key: note_key, .. } =>
+            (note_channel, note_key) == (channel, key),
+        _ => false,
+    }
+}
 
 #[derive(Clone)]
 pub struct Sample {
-    // FIXME(#153): Improve performance of the event look up in sample
     pub buffer: Vec<QuantMidiEvent>,
-    pub amount_of_measures: u32,
-    measure_shift: u32,
+    /// The sample plays its quant `looper quant + shift`
+    quant_shift: Quant,
     notes: Vec<Note>,
+    /// Length of the sample, not necessarily a whole number of
+    /// measures
     sample_quant_length: Quant,
     quants_per_measure: Quant,
+    playback_rate: PlaybackRate,
+    reverse: bool,
+    /// Percent of the swing step the off-beat steps are delayed by
+    swing: u32,
+    groove: Option<Groove>,
+    /// How close to the quants the events are moved from the moments
+    /// they were recorded at, in percent
+    quantize_strength: u32,
+    /// Channel the events are sent on instead of the recorded ones
+    output_channel: Option<u8>,
+    muted: bool,
+    /// The buffer with the playback rate and the reverse applied. The
+    /// quants of its events are the quants of the looper. The events
+    /// are sorted by their ticks, so the events due are looked up by
+    /// binary search.
+    replay_buffer: Vec<QuantMidiEvent>,
+    /// Length after which the replay of the sample repeats itself
+    replay_quant_length: Quant,
+    /// Amount of times the replay has wrapped around, counts the loop
+    /// cycles for the note conditions
+    cycle: u32,
+    /// Decides the probabilities of the note conditions, seeded with
+    /// `NOTE_CONDITION_SEED` mixed with the index of the sample, so the
+    /// replay is reproducible
+    rng: XorShiftRng,
+    /// Notes skipped by their conditions, so their NoteOffs are
+    /// skipped too
+    skipped_notes: Vec<(u8, u8)>,
+    /// Channel, key and quant of the NoteOn of the note selected in
+    /// the piano roll
+    selected_note: Option<(u8, u8, Quant)>,
 }
 
 impl Sample {
     pub fn as_sample_data(&self) -> SampleData {
+        let Quant(quants_per_measure) = self.quants_per_measure;
+        let Quant(length) = self.sample_quant_length;
+        let Quant(shift) = self.quant_shift;
+
         SampleData {
-            amount_of_measures: self.amount_of_measures,
+            amount_of_measures: self.amount_of_measures(),
+            quant_length: if length % quants_per_measure == 0 { None } else { Some(length) },
             buffer: self.buffer.clone(),
-            measure_shift: self.measure_shift,
-            quants_per_measure: self.quants_per_measure.as_u32()
+            measure_shift: shift / quants_per_measure,
+            quant_shift: if shift % quants_per_measure == 0 { None } else { Some(shift) },
+            quants_per_measure: self.quants_per_measure.as_u32(),
+            playback_rate: self.playback_rate,
+            reverse: self.reverse,
+            swing: self.swing,
+            groove: self.groove.clone(),
+            quantize_strength: self.quantize_strength,
+            output_channel: self.output_channel,
         }
     }
 
-    pub fn from_sample_data(sample_data: &SampleData) -> Sample {
-        let notes = midi::events_to_notes(&sample_data.buffer);
-        let buffer = sample_data.buffer.clone();
-        let amount_of_measures = sample_data.amount_of_measures;
-        let quants_per_measure = Quant(sample_data.quants_per_measure);
-        let measure_shift = sample_data.measure_shift;
+    /// Samples saved with a different resolution of quants (e.g.
+    /// before the quants got independent from the recording grid) are
+    /// rescaled to the quants of the measure.
+    pub fn from_sample_data(sample_data: &SampleData, measure: &Measure) -> Sample {
+        let quants_per_measure = measure.quants_per_measure();
+        let saved_quants_per_measure = sample_data.quants_per_measure;
+        let rescale = |quant: u32| Quant(quant * quants_per_measure.as_u32() / saved_quants_per_measure);
 
-        Sample {
+        let buffer = if saved_quants_per_measure == quants_per_measure.as_u32() {
+            sample_data.buffer.clone()
+        } else {
+            sample_data.buffer.iter().map(|event| {
+                QuantMidiEvent {
+                    quant: rescale(event.quant.as_u32()),
+                    .. event.clone()
+                }
+            }).collect()
+        };
+        let length = sample_data.quant_length
+            .unwrap_or(sample_data.amount_of_measures * saved_quants_per_measure);
+        let shift = sample_data.quant_shift
+            .unwrap_or(sample_data.measure_shift * saved_quants_per_measure);
+
+        let mut sample = Sample {
             buffer: buffer,
-            amount_of_measures: amount_of_measures,
-            notes: notes,
-            sample_quant_length: Quant(amount_of_measures) * quants_per_measure,
+            notes: Vec::new(),
+            sample_quant_length: rescale(length),
             quants_per_measure: quants_per_measure,
-            measure_shift: measure_shift
-        }
+            quant_shift: rescale(shift),
+            playback_rate: sample_data.playback_rate,
+            reverse: sample_data.reverse,
+            swing: sample_data.swing,
+            groove: sample_data.groove.clone(),
+            quantize_strength: sample_data.quantize_strength,
+            output_channel: sample_data.output_channel,
+            muted: false,
+            replay_buffer: Vec::new(),
+            replay_quant_length: Quant(0),
+            cycle: 0,
+            rng: XorShiftRng::from_seed(NOTE_CONDITION_SEED),
+            skipped_notes: Vec::new(),
+            selected_note: None,
+        };
+        sample.update_replay_buffer();
+        sample
     }
 
+    /// The sample lasting the whole amount of measures the buffer
+    /// spans
     pub fn new(buffer: &[AbsMidiEvent], measure: &Measure, measure_shift: u32) -> Sample {
+        let quants_per_measure = measure.quants_per_measure();
         let amount_of_measures = measure.amount_of_measures_in_buffer(buffer);
-        let quant_buffer = measure.quantize_buffer(buffer);
+        Sample::with_length(buffer,
+                            measure,
+                            Quant(amount_of_measures) * quants_per_measure,
+                            Quant(measure_shift) * quants_per_measure)
+    }
+
+    /// The sample lasting the given amount of quants, e.g. three beats
+    /// played over the 4/4 measures
+    pub fn with_length(buffer: &[AbsMidiEvent], measure: &Measure, length: Quant, shift: Quant) -> Sample {
+        let quant_buffer = measure.quantize_buffer_to_length(buffer, length);
+
+        let mut sample = Sample::from_quant_buffer(quant_buffer,
+                                                   length,
+                                                   measure.quants_per_measure(),
+                                                   shift);
+        sample.update_replay_buffer();
+        sample
+    }
+
+    /// Bounces the samples into one. The result lasts the least
+    /// common multiple of their lengths and has the playback rates,
+    /// the grooves and the shifts of the samples baked in. The note
+    /// conditions are kept, but count the cycles of the result.
+    pub fn merge(samples: &[&Sample], quants_per_measure: Quant) -> Sample {
+        let length = samples.iter().fold(1, |length, sample| {
+            lcm(length, sample.replay_quant_length.as_u32())
+        });
+        let tick_length = length * TICKS_PER_QUANT;
 
-        let notes = midi::events_to_notes(&quant_buffer);
+        let mut buffer = Vec::new();
+        for sample in samples {
+            let replay_tick_length = sample.replay_tick_length();
+            // The sample plays its tick `looper tick + shift`
+            let tick_shift = sample.quant_shift.as_u32() * TICKS_PER_QUANT % tick_length;
 
+            for repetition in 0..tick_length / replay_tick_length {
+                for event in &sample.replay_buffer {
+                    let tick = event.tick() as u32 + repetition * replay_tick_length + tick_length - tick_shift;
+                    buffer.push(event.moved_to_tick(tick % tick_length));
+                }
+            }
+        }
+        buffer.sort_by_key(|event| event.tick());
+
+        let mut sample = Sample::from_quant_buffer(buffer, Quant(length), quants_per_measure, Quant(0));
+        // The events are already where they are heard
+        sample.quantize_strength = 0;
+        sample.update_replay_buffer();
+        sample
+    }
+
+    /// Splits the events of the sample into the parts given by
+    /// `part_of`, e.g. by channel. The parts keep the settings of the
+    /// sample and go in the order of their numbers.
+    pub fn split<F: Fn(&TypedMidiMessage) -> usize>(&self, part_of: F) -> Vec<Sample> {
+        let mut parts: BTreeMap<usize, Vec<QuantMidiEvent>> = BTreeMap::new();
+        for event in &self.buffer {
+            parts.entry(part_of(&event.message)).or_insert_with(Vec::new).push(event.clone());
+        }
+
+        parts.into_iter().map(|(_, buffer)| {
+            let mut part = self.clone();
+            part.buffer = buffer;
+            part.update_replay_buffer();
+            part
+        }).collect()
+    }
+
+    /// The sample with the default settings. Its replay buffer is left
+    /// empty.
+    fn from_quant_buffer(buffer: Vec<QuantMidiEvent>,
+                         length: Quant,
+                         quants_per_measure: Quant,
+                         shift: Quant) -> Sample {
         Sample {
-            buffer: quant_buffer,
-            amount_of_measures: amount_of_measures,
-            notes: notes,
-            sample_quant_length: Quant(amount_of_measures) * measure.quants_per_measure(),
-            quants_per_measure: measure.quants_per_measure(),
-            measure_shift: measure_shift,
+            buffer: buffer,
+            notes: Vec::new(),
+            sample_quant_length: length,
+            quants_per_measure: quants_per_measure,
+            quant_shift: shift,
+            playback_rate: PlaybackRate::Normal,
+            reverse: false,
+            swing: 0,
+            groove: None,
+            quantize_strength: 100,
+            output_channel: None,
+            muted: false,
+            replay_buffer: Vec::new(),
+            replay_quant_length: Quant(0),
+            cycle: 0,
+            rng: XorShiftRng::from_seed(NOTE_CONDITION_SEED),
+            skipped_notes: Vec::new(),
+            selected_note: None,
         }
     }
 
-    pub fn replay_quant<Sink: MidiSink>(&self, current_quant: Quant, sink: &mut Sink) {
-        let quant_shift = Quant(self.measure_shift) * self.quants_per_measure;
-        let sample_quant = (current_quant + quant_shift) % self.sample_quant_length;
+    pub fn quant_length(&self) -> Quant {
+        self.sample_quant_length
+    }
+
+    /// Amount of measures the sample lasts rounded up
+    pub fn amount_of_measures(&self) -> u32 {
+        let Quant(quants_per_measure) = self.quants_per_measure;
+        (self.sample_quant_length.as_u32() + quants_per_measure - 1) / quants_per_measure
+    }
 
-        // FIXME(#153): Improve performance of the event look up in sample
-        for event in &self.buffer {
-            if event.quant == sample_quant {
-                // FIXME(#141): Handle result of the sink message feeding
-                sink.feed(event.message).unwrap();
+    /// Length after which the replay of the sample repeats itself.
+    /// Takes the playback rate into account.
+    pub fn replay_quant_length(&self) -> Quant {
+        self.replay_quant_length
+    }
+
+    /// Amount of measures after which the replay of the sample
+    /// repeats itself rounded up. Takes the playback rate into
+    /// account.
+    pub fn effective_amount_of_measures(&self) -> u32 {
+        let Quant(quants_per_measure) = self.quants_per_measure;
+        (self.replay_quant_length.as_u32() + quants_per_measure - 1) / quants_per_measure
+    }
+
+    pub fn playback_rate(&self) -> PlaybackRate {
+        self.playback_rate
+    }
+
+    pub fn set_playback_rate(&mut self, playback_rate: PlaybackRate) {
+        self.playback_rate = playback_rate;
+        self.update_replay_buffer();
+    }
+
+    pub fn toggle_reverse(&mut self) {
+        self.reverse = !self.reverse;
+        self.update_replay_buffer();
+    }
+
+    pub fn swing(&self) -> u32 {
+        self.swing
+    }
+
+    pub fn set_swing(&mut self, swing: u32) {
+        self.swing = if swing > MAX_SWING_PERCENT { MAX_SWING_PERCENT } else { swing };
+        self.update_replay_buffer();
+    }
+
+    pub fn set_groove(&mut self, groove: Option<Groove>) {
+        self.groove = groove;
+        self.update_replay_buffer();
+    }
+
+    pub fn has_groove(&self) -> bool {
+        self.groove.is_some()
+    }
+
+    pub fn extract_groove(&self) -> Groove {
+        Groove::extract(&self.buffer, self.quants_per_measure)
+    }
+
+    pub fn quantize_strength(&self) -> u32 {
+        self.quantize_strength
+    }
+
+    pub fn set_quantize_strength(&mut self, quantize_strength: u32) {
+        self.quantize_strength = if quantize_strength > 100 { 100 } else { quantize_strength };
+        self.update_replay_buffer();
+    }
+
+    pub fn output_channel(&self) -> Option<u8> {
+        self.output_channel
+    }
+
+    pub fn set_output_channel(&mut self, output_channel: Option<u8>) {
+        self.output_channel = output_channel;
+        self.update_replay_buffer();
+    }
+
+    /// Snaps the recorded moments of the events to the grid of the
+    /// measure. The moments themselves are kept, so the sample can be
+    /// re-quantized to another grid later.
+    pub fn requantize(&mut self, measure: &Measure) {
+        let step_ticks = (measure.grid_size_quants() * TICKS_PER_QUANT) as i64;
+        let Quant(sample_length) = self.sample_quant_length;
+        let sample_tick_length = (sample_length * TICKS_PER_QUANT) as i64;
+
+        for event in &mut self.buffer {
+            // Offsets are way shorter than the sample, so the shifted
+            // tick is never negative
+            let tick = event.tick() + sample_tick_length;
+            let snapped_tick = (tick + step_ticks / 2) / step_ticks * step_ticks;
+
+            event.quant = Quant((snapped_tick / TICKS_PER_QUANT as i64) as u32 % sample_length);
+            event.offset = (tick - snapped_tick) as i32;
+        }
+
+        self.update_replay_buffer();
+    }
+
+    /// Moves the events by the given amount of quants. The events
+    /// moved past an end of the sample wrap around to the other one.
+    pub fn nudge(&mut self, quants: i32) {
+        let Quant(sample_length) = self.sample_quant_length;
+        let sample_length = sample_length as i64;
+
+        for event in &mut self.buffer {
+            let Quant(quant) = event.quant;
+            let quant = quant as i64 + quants as i64;
+            event.quant = Quant(((quant % sample_length + sample_length) % sample_length) as u32);
+        }
+        self.buffer.sort_by_key(|event| (event.quant, event.offset));
+
+        self.update_replay_buffer();
+    }
+
+    /// Moves the whole sample relative to the measures of the looper.
+    /// The samples shorter than the measure move by the remainder of
+    /// the measure.
+    pub fn nudge_measures(&mut self, measures: i32) {
+        let replay_length = self.replay_quant_length.as_u32() as i64;
+        // The sample plays its quant `looper quant + shift`, so the
+        // later it should sound the smaller the shift is
+        let quant_shift = self.quant_shift.as_u32() as i64 - measures as i64 * self.quants_per_measure.as_u32() as i64;
+        self.quant_shift = Quant(((quant_shift % replay_length + replay_length) % replay_length) as u32);
+    }
+
+    pub fn set_amount_of_measures(&mut self, amount_of_measures: u32) {
+        let length = Quant(cmp::max(amount_of_measures, 1)) * self.quants_per_measure;
+        self.set_quant_length(length);
+    }
+
+    /// Cuts the sample to the given length or extends it with
+    /// silence. The notes held over the new end are released right
+    /// before it.
+    pub fn set_quant_length(&mut self, length: Quant) {
+        let length = cmp::max(length, Quant(1));
+        let tick_length = (length.as_u32() * TICKS_PER_QUANT) as i64;
+
+        if length < self.sample_quant_length {
+            let mut opened_notes = [[false; 128]; 16];
+            let mut events = self.buffer.clone();
+            events.sort_by_key(|event| event.tick());
+
+            self.buffer = events.into_iter().filter_map(|event| {
+                match event.message {
+                    TypedMidiMessage::NoteOn { channel, key, .. } => {
+                        let opened = event.tick() < tick_length;
+                        opened_notes[channel as usize][key as usize] = opened;
+                        if opened { Some(event) } else { None }
+                    },
+
+                    TypedMidiMessage::NoteOff { channel, key, .. } => {
+                        let opened = mem::replace(&mut opened_notes[channel as usize][key as usize], false);
+                        if event.tick() < tick_length {
+                            Some(event)
+                        } else if opened {
+                            Some(QuantMidiEvent::at_tick(event.message, (tick_length - 1) as u32))
+                        } else {
+                            None
+                        }
+                    },
+
+                    _ => if event.tick() < tick_length { Some(event) } else { None },
+                }
+            }).collect();
+        }
+
+        self.sample_quant_length = length;
+        self.update_replay_buffer();
+    }
+
+    /// Repeats the content of the sample, so it becomes twice as long
+    /// and the repetition can be varied later
+    pub fn double(&mut self) {
+        let length = self.sample_quant_length;
+        let repetition: Vec<QuantMidiEvent> = self.buffer.iter().map(|event| {
+            QuantMidiEvent {
+                quant: event.quant + length,
+                .. event.clone()
+            }
+        }).collect();
+        self.buffer.extend(repetition);
+
+        self.sample_quant_length = length * Quant(2);
+        self.update_replay_buffer();
+    }
+
+    /// Seeds the probabilities of the note conditions with the index
+    /// of the sample in the composition, so the samples do not decide
+    /// in lockstep
+    pub fn seed_note_conditions(&mut self, index: usize) {
+        let mut seed = NOTE_CONDITION_SEED;
+        seed[0] ^= (index as u32).wrapping_mul(0x9E3779B9);
+        self.rng = XorShiftRng::from_seed(seed);
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Feeds the events that happen when the looper moves from the
+    /// tick `from` (exclusively) to the tick `to` (inclusively) and
+    /// meet their conditions on the current loop cycle.
+    pub fn replay<Sink: MidiSink>(&mut self, from: u32, to: u32, sink: &mut Sink) {
+        let replay_length = self.replay_tick_length() as i64;
+        let tick_shift = self.quant_shift.as_u32() * TICKS_PER_QUANT;
+        let start = (from + tick_shift) as i64 % replay_length;
+        let end = start + (to - from) as i64;
+        let cycle = self.cycle;
+
+        // The muted samples keep counting the cycles, so the
+        // conditions stay aligned to the loop when they are unmuted
+        if end >= replay_length {
+            self.cycle = self.cycle.wrapping_add(1);
+        }
+
+        if self.muted {
+            return;
+        }
+
+        // The events after the end of the sample are replayed from its
+        // beginning on the next cycle
+        for &(window_start, window_end, cycle) in &[(start, end, cycle),
+                                                    (start - replay_length, end - replay_length, cycle.wrapping_add(1))] {
+            let first = self.first_event_after(window_start);
+            for event in self.replay_buffer[first..].iter().take_while(|event| event.tick() <= window_end) {
+                let played = match event.message {
+                    TypedMidiMessage::NoteOn { channel, key, .. } => {
+                        self.skipped_notes.retain(|&note| note != (channel, key));
+                        let played = event.condition.is_met(cycle, &mut self.rng);
+                        if !played {
+                            self.skipped_notes.push((channel, key));
+                        }
+                        played
+                    },
+
+                    TypedMidiMessage::NoteOff { channel, key, .. } => {
+                        match self.skipped_notes.iter().position(|&note| note == (channel, key)) {
+                            Some(index) => {
+                                self.skipped_notes.remove(index);
+                                false
+                            },
+                            None => true,
+                        }
+                    },
+
+                    _ => event.condition.is_met(cycle, &mut self.rng),
+                };
+
+                if played {
+                    // FIXME(#141): Handle result of the sink message feeding
+                    sink.feed(event.message).unwrap();
+                }
             }
         }
     }
 
-    fn measure_notes(&self, measure_number: u32) -> Vec<Note> {
-        let start: Quant = Quant(measure_number) * self.quants_per_measure;
-        let end: Quant = Quant(measure_number + 1) * self.quants_per_measure;
-        let mut result = Vec::new();
+    /// Whether the sample has recorded notes with the key
+    pub fn has_note(&self, channel: u8, key: u8) -> bool {
+        self.buffer.iter().any(|event| is_note_on(&event.message, channel, key))
+    }
+
+    /// Switches the condition of the notes with the key to the next
+    /// one of `NOTE_CONDITIONS` and returns it. Returns `None` if the
+    /// sample has no such notes.
+    pub fn switch_note_condition(&mut self, channel: u8, key: u8) -> Option<NoteCondition> {
+        let condition = match self.buffer.iter().find(|event| is_note_on(&event.message, channel, key)) {
+            Some(event) => switch_condition(event.condition, true),
+            None => return None,
+        };
+
+        for event in self.buffer.iter_mut().filter(|event| is_note_on(&event.message, channel, key)) {
+            event.condition = condition;
+        }
+        self.update_replay_buffer();
+        Some(condition)
+    }
+
+    /// Selects the note with the key sounding at the quant of the
+    /// replay, where it is seen in the piano roll, and returns its
+    /// condition. Clears the selection if there is no such note.
+    pub fn select_note(&mut self, key: u8, replay_quant: Quant) -> Option<NoteCondition> {
+        let quant = self.sample_quant(replay_quant);
+        let note_on = self.buffer.iter().enumerate()
+            .position(|(index, event)| match event.message {
+                TypedMidiMessage::NoteOn { key: note_key, .. } if note_key == key => self.note_contains(index, quant),
+                _ => false,
+            });
+
+        self.selected_note = note_on.and_then(|index| {
+            let event = &self.buffer[index];
+            event.message.channel().map(|channel| (channel, key, event.quant))
+        });
+        note_on.map(|index| self.buffer[index].condition)
+    }
+
+    /// Switches the condition of the selected note to the next one of
+    /// `NOTE_CONDITIONS`, or the previous one if not `forward`, and
+    /// returns it. Returns `None` if no note is selected.
+    pub fn switch_selected_note_condition(&mut self, forward: bool) -> Option<NoteCondition> {
+        let index = match self.selected_note_on() {
+            Some(index) => index,
+            None => return None,
+        };
+
+        let condition = switch_condition(self.buffer[index].condition, forward);
+        self.buffer[index].condition = condition;
+        self.update_replay_buffer();
+        Some(condition)
+    }
+
+    /// Whether a note of the sample is selected
+    pub fn has_selected_note(&self) -> bool {
+        self.selected_note_on().is_some()
+    }
+
+    /// Index of the NoteOn of the selected note in the buffer. The
+    /// selection is lost once the note is moved.
+    fn selected_note_on(&self) -> Option<usize> {
+        self.selected_note.and_then(|(channel, key, quant)| {
+            self.buffer.iter().position(|event| event.quant == quant && is_note_on(&event.message, channel, key))
+        })
+    }
+
+    /// Whether the note started by the NoteOn at the index of the
+    /// buffer sounds at the quant of the sample. The note held over
+    /// the end of the sample continues at its beginning.
+    fn note_contains(&self, note_on: usize, Quant(quant): Quant) -> bool {
+        let (channel, key) = match self.buffer[note_on].message {
+            TypedMidiMessage::NoteOn { channel, key, .. } => (channel, key),
+            _ => return false,
+        };
+        let is_note_end = |event: &QuantMidiEvent| match event.message {
+            TypedMidiMessage::NoteOn { channel: note_channel, key: note_key, .. } |
+            TypedMidiMessage::NoteOff { channel: note_channel, key: note_key, .. } =>
+                (note_channel, note_key) == (channel, key),
+            _ => false,
+        };
+
+        let Quant(start) = self.buffer[note_on].quant;
+        let end = self.buffer[note_on + 1..].iter()
+            .chain(self.buffer[..note_on].iter())
+            .find(|event| is_note_end(event))
+            .map_or(self.sample_quant_length.as_u32(), |event| event.quant.as_u32());
+
+        if start <= end {
+            quant == start || (start < quant && quant < end)
+        } else {
+            start <= quant || quant < end
+        }
+    }
+
+    /// Index of the first event of the replay buffer after the tick
+    fn first_event_after(&self, tick: i64) -> usize {
+        self.replay_buffer.binary_search_by(|event| {
+            if event.tick() <= tick { Ordering::Less } else { Ordering::Greater }
+        }).unwrap_err()
+    }
+
+    fn replay_tick_length(&self) -> u32 {
+        self.replay_quant_length.as_u32() * TICKS_PER_QUANT
+    }
+
+    /// Stretches the (possibly reversed) buffer according to the
+    /// playback rate. The result covers the whole
+    /// `replay_quant_length`, so a sample played faster is repeated
+    /// several times in it.
+    fn update_replay_buffer(&mut self) {
+        let (numerator, denominator) = self.playback_rate.as_ratio();
+        let Quant(sample_length) = self.sample_quant_length;
+
+        // The smallest length that fits the whole number of stretched
+        // samples, counted in the largest unit of the sample that
+        // divides the measure, e.g. measures or beats
+        let unit = gcd(sample_length, self.quants_per_measure.as_u32());
+        let scaled_amount_of_units = sample_length / unit * denominator;
+        self.replay_quant_length =
+            Quant(unit * (scaled_amount_of_units / gcd(scaled_amount_of_units, numerator)));
+
+        let sample_tick_length = (sample_length * TICKS_PER_QUANT) as i64;
+        let mut source: Vec<QuantMidiEvent> = self.buffer.iter().map(|event| {
+            let Quant(quant) = event.quant;
+            let offset = event.offset as i64 * (100 - self.quantize_strength) as i64 / 100;
+            let tick = (quant * TICKS_PER_QUANT) as i64 + offset;
+            event.moved_to_tick(((tick % sample_tick_length + sample_tick_length) % sample_tick_length) as u32)
+        }).collect();
+        source.sort_by_key(|event| (event.quant, event.offset));
 
-        for note in &self.notes {
-            if (start <= note.start_quant && note.start_quant <= end) || (start <= note.end_quant && note.end_quant <= end) {
-                result.push(*note);
+        if self.reverse {
+            source = midi::reverse_events(&source, self.sample_quant_length);
+        }
+
+        if self.swing > 0 {
+            apply_groove(&mut source, &Groove::swing(self.swing), self.sample_quant_length);
+        }
+        if let Some(ref groove) = self.groove {
+            apply_groove(&mut source, groove, self.sample_quant_length);
+        }
+
+        let Quant(replay_length) = self.replay_quant_length;
+        let repetitions = replay_length * numerator / denominator / sample_length;
+
+        if let Some(output_channel) = self.output_channel {
+            for event in &mut source {
+                event.message = event.message.with_channel(output_channel);
             }
         }
 
-        result
+        self.replay_buffer.clear();
+        for repetition in 0..repetitions {
+            for event in &source {
+                let tick = event.tick() as u32 + repetition * sample_length * TICKS_PER_QUANT;
+                self.replay_buffer.push(event.moved_to_tick(tick * denominator / numerator));
+            }
+        }
+
+        self.notes = midi::events_to_notes(&self.replay_buffer);
+    }
+
+    pub fn quants_per_measure(&self) -> Quant {
+        self.quants_per_measure
+    }
+
+    /// The quant of the replay played at the given quant of the
+    /// looper
+    pub fn replay_quant(&self, Quant(looper_quant): Quant) -> Quant {
+        Quant((looper_quant + self.quant_shift.as_u32()) % self.replay_quant_length.as_u32())
+    }
+
+    /// Start of the first repetition of the replay within the window of
+    /// the looper relative to the window, usually before the window,
+    /// and the amount of the repetitions.
+    fn window_repetitions(&self, window_start: Quant, Quant(window_size): Quant) -> (i64, u32) {
+        let Quant(replay_length) = self.replay_quant_length;
+        let Quant(start) = self.replay_quant(window_start);
+        (-(start as i64), (start + window_size) / replay_length + 1)
+    }
+
+    /// Fills `notes` with the notes heard in the window of the looper
+    /// relative to the window. The sample is repeated as many times as
+    /// it sounds in the window and the notes crossing its edges are cut
+    /// by them. The vector is reused, so the snapshots don't allocate.
+    pub fn window_notes(&self, window_start: Quant, window_size: Quant, notes: &mut Vec<Note>) {
+        self.notes_in_window(|_| true, window_start, window_size, notes);
+    }
+
+    /// Fills `notes` with the notes of the window played by the
+    /// selected note
+    pub fn window_selected_notes(&self, window_start: Quant, window_size: Quant, notes: &mut Vec<Note>) {
+        let (note_on, key) = match (self.selected_note_on(), self.selected_note) {
+            (Some(note_on), Some((_, key, _))) => (note_on, key),
+            _ => {
+                notes.clear();
+                return;
+            },
+        };
+
+        self.notes_in_window(|note| {
+            let middle = Quant((note.start_quant.as_u32() + note.end_quant.as_u32()) / 2);
+            note.key == key && self.note_contains(note_on, self.sample_quant(middle))
+        }, window_start, window_size, notes);
+    }
+
+    fn notes_in_window<F>(&self, is_shown: F, window_start: Quant, window_size: Quant, result: &mut Vec<Note>)
+        where F: Fn(&Note) -> bool {
+        let size = window_size.as_u32() as i64;
+        let clamp = |quant: i64| Quant(cmp::min(cmp::max(quant, 0), size) as u32);
+        let (first_base, amount_of_repetitions) = self.window_repetitions(window_start, window_size);
+        result.clear();
+
+        for repetition in 0..amount_of_repetitions {
+            let base = first_base + (repetition * self.replay_quant_length.as_u32()) as i64;
+            for note in self.notes.iter().filter(|note| is_shown(note)) {
+                let start = note.start_quant.as_u32() as i64 + base;
+                let end = note.end_quant.as_u32() as i64 + base;
+                if start <= size && end >= 0 {
+                    result.push(Note {
+                        start_quant: clamp(start),
+                        end_quant: clamp(end),
+                        .. *note
+                    });
+                }
+            }
+        }
+    }
+
+    /// Fills `points` with the values of the controller in the window
+    /// of the looper relative to the window. The value the controller
+    /// has when the window starts is put at its beginning, so the graph
+    /// covers the whole window.
+    pub fn window_automation(&self,
+                             lane: AutomationLane,
+                             window_start: Quant,
+                             window_size: Quant,
+                             result: &mut Vec<AutomationPoint>) {
+        let size = window_size.as_u32() as i64;
+        let points = || self.replay_buffer.iter().filter_map(move |event| {
+            match AutomationLane::of_message(&event.message) {
+                Some((event_lane, value)) if event_lane == lane => Some(AutomationPoint {
+                    quant: event.quant,
+                    value: value,
+                }),
+                _ => None,
+            }
+        });
+        result.clear();
+
+        // The value set before the window. Without one the value set
+        // last in the sample holds since its previous repetition.
+        let start = self.replay_quant(window_start);
+        let initial = points().filter(|point| point.quant < start).last().or_else(|| points().last());
+        if let Some(point) = initial {
+            result.push(AutomationPoint { quant: Quant(0), value: point.value });
+        }
+
+        let (first_base, amount_of_repetitions) = self.window_repetitions(window_start, window_size);
+        for repetition in 0..amount_of_repetitions {
+            let base = first_base + (repetition * self.replay_quant_length.as_u32()) as i64;
+            for point in points() {
+                let quant = point.quant.as_u32() as i64 + base;
+                if 0 <= quant && quant < size {
+                    result.push(AutomationPoint { quant: Quant(quant as u32), .. point });
+                }
+            }
+        }
+    }
+
+    /// Controllers that have recorded values in the sample
+    pub fn automation_lanes(&self) -> Vec<AutomationLane> {
+        let lanes: BTreeSet<AutomationLane> = self.buffer.iter()
+            .filter_map(|event| AutomationLane::of_message(&event.message).map(|(lane, _)| lane))
+            .collect();
+        lanes.into_iter().collect()
+    }
+
+    pub fn clear_automation(&mut self, lane: AutomationLane) {
+        self.buffer.retain(|event| !lane.contains(&event.message));
+        self.update_replay_buffer();
+    }
+
+    /// The notes of the sample on the grid of the step sequencer. The
+    /// channel of the first note is taken, `default_channel` if the
+    /// sample has no notes. The grid covers the recorded sample, the
+    /// playback rate and the reverse are not applied.
+    pub fn step_pattern(&self, step_quants: u32, default_channel: u8) -> StepPattern {
+        let notes = midi::events_to_notes(&self.buffer);
+        let channel = notes.first().map_or(default_channel, |note| note.channel);
+        let amount_of_steps = (self.sample_quant_length.as_u32() / step_quants) as usize;
+        StepPattern::from_notes(&notes, channel, step_quants, amount_of_steps)
+    }
+
+    /// Writes the steps changed in the pattern. The notes of the other
+    /// steps keep their lengths, timing and conditions, the rows that
+    /// changed keys take their notes along.
+    pub fn write_step_pattern(&mut self, pattern: &StepPattern) {
+        let channel = pattern.channel;
+        let key_changes = pattern.key_changes();
+        for event in &mut self.buffer {
+            event.message = match event.message {
+                TypedMidiMessage::NoteOn { channel: note_channel, key, velocity } if note_channel == channel =>
+                    TypedMidiMessage::NoteOn {
+                        channel: channel,
+                        key: changed_key(&key_changes, key),
+                        velocity: velocity,
+                    },
+                TypedMidiMessage::NoteOff { channel: note_channel, key, velocity } if note_channel == channel =>
+                    TypedMidiMessage::NoteOff {
+                        channel: channel,
+                        key: changed_key(&key_changes, key),
+                        velocity: velocity,
+                    },
+                message => message,
+            };
+        }
+
+        let sample_length = self.sample_quant_length;
+        for change in pattern.step_changes() {
+            let notes = self.step_notes(pattern, change.key, change.step);
+            match change.velocity {
+                None => {
+                    let mut removed = vec![false; self.buffer.len()];
+                    for &(note_on, note_off) in &notes {
+                        removed[note_on] = true;
+                        if let Some(note_off) = note_off {
+                            removed[note_off] = true;
+                        }
+                    }
+                    let mut index = 0;
+                    self.buffer.retain(|_| {
+                        index += 1;
+                        !removed[index - 1]
+                    });
+                },
+
+                Some(velocity) if notes.is_empty() => {
+                    self.buffer.extend(pattern.step_events(change.key, change.step, velocity).into_iter()
+                                       .filter(|event| event.quant < sample_length));
+                    self.buffer.sort_by_key(|event| (event.quant, event.offset));
+                },
+
+                Some(velocity) => for &(note_on, _) in &notes {
+                    if let TypedMidiMessage::NoteOn { channel, key, .. } = self.buffer[note_on].message {
+                        self.buffer[note_on].message = TypedMidiMessage::NoteOn {
+                            channel: channel,
+                            key: key,
+                            velocity: velocity,
+                        };
+                    }
+                },
+            }
+        }
+
+        self.update_replay_buffer();
     }
 
-    pub fn render(&self, raw_measure_number: u32, renderer: &mut Renderer) {
-        let current_measure_number = (raw_measure_number + self.measure_shift) % self.amount_of_measures;
-        let current_measure_notes = self.measure_notes(current_measure_number);
-        let note_shift = Quant(current_measure_number) * self.quants_per_measure;
+    /// Indices of the NoteOns of the notes shown on the step of the
+    /// pattern and of their NoteOffs. The NoteOff of the note held over
+    /// the end of the sample is at its beginning.
+    fn step_notes(&self, pattern: &StepPattern, key: u8, step: usize) -> Vec<(usize, Option<usize>)> {
+        let is_note_off = |message: &TypedMidiMessage| match *message {
+            TypedMidiMessage::NoteOff { channel: note_channel, key: note_key, .. } =>
+                (note_channel, note_key) == (pattern.channel, key),
+            _ => false,
+        };
 
-        for note in &current_measure_notes {
-            note.render(renderer, self.quants_per_measure, note_shift);
+        self.buffer.iter().enumerate()
+            .filter(|&(_, event)| is_note_on(&event.message, pattern.channel, key) && pattern.is_on_step(event.quant, step))
+            .map(|(note_on, _)| {
+                let note_off = self.buffer[note_on..].iter().position(|event| is_note_off(&event.message))
+                    .map(|index| note_on + index)
+                    .or_else(|| self.buffer[..note_on].iter().position(|event| is_note_off(&event.message)));
+                (note_on, note_off)
+            })
+            .collect()
+    }
+
+    /// The quant of the sample played at the quant of the replay. The
+    /// quant is mapped back through the playback rate and the reverse,
+    /// but not through the swing and the groove.
+    fn sample_quant(&self, replay_quant: Quant) -> Quant {
+        let (numerator, denominator) = self.playback_rate.as_ratio();
+        let Quant(sample_length) = self.sample_quant_length;
+        let quant = replay_quant.as_u32() * numerator / denominator % sample_length;
+        if self.reverse {
+            Quant((sample_length - quant) % sample_length)
+        } else {
+            Quant(quant)
         }
     }
+
+    /// Sets the value of the controller at the quant of the replay,
+    /// where it is seen in the piano roll
+    pub fn draw_automation(&mut self, lane: AutomationLane, replay_quant: Quant, value: u16) {
+        let Quant(quant) = self.sample_quant(replay_quant);
+
+        // The values are drawn on the channel the controller was
+        // recorded on, or the channel of the notes for the new ones
+        let channel = self.buffer.iter()
+            .find(|event| lane.contains(&event.message))
+            .or_else(|| self.buffer.iter().find(|event| event.message.channel().is_some()))
+            .and_then(|event| event.message.channel())
+            .unwrap_or(0);
+
+        self.buffer.retain(|event| event.quant != Quant(quant) || !lane.contains(&event.message));
+        self.buffer.push(QuantMidiEvent::at_tick(lane.message(channel, cmp::min(value, lane.max_value())),
+                                                 quant * TICKS_PER_QUANT));
+        self.buffer.sort_by_key(|event| (event.quant, event.offset));
+        self.update_replay_buffer();
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::{Duration, Instant};
+
     use super::Sample;
     use hardcode::*;
-    use measure::Measure;
-    use midi::{AbsMidiEvent, TypedMidiMessage};
+    use measure::{Measure, Quant, QuantMidiEvent, NoteCondition};
+    use midi::{AbsMidiEvent, TypedMidiMessage, MidiSink, Note};
+    use looper::{PlaybackRate, SampleData, Groove, AutomationLane, AutomationPoint};
+    use pm::types::Result;
 
     use serde_json;
 
-    const DEFAULT_MEASURE: Measure = Measure {
-        tempo_bpm: DEFAULT_TEMPO_BPM,
-        measure_size_bpm: DEFAULT_MEASURE_SIZE_BPM,
-        quantation_level: DEFAULT_QUANTATION_LEVEL,
-    };
+    const GRID_SIZE_QUANTS: u32 = QUANTS_PER_BEAT / DEFAULT_BEAT_DIVISION;
+
+    fn window_notes(sample: &Sample, window_start: Quant, window_size: Quant) -> Vec<Note> {
+        let mut notes = Vec::new();
+        sample.window_notes(window_start, window_size, &mut notes);
+        notes
+    }
+
+    fn window_selected_notes(sample: &Sample, window_start: Quant, window_size: Quant) -> Vec<Note> {
+        let mut notes = Vec::new();
+        sample.window_selected_notes(window_start, window_size, &mut notes);
+        notes
+    }
+
+    fn window_automation(sample: &Sample, lane: AutomationLane, window_start: Quant, window_size: Quant) -> Vec<AutomationPoint> {
+        let mut points = Vec::new();
+        sample.window_automation(lane, window_start, window_size, &mut points);
+        points
+    }
+
+    fn default_measure() -> Measure {
+        Measure {
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            measure_size_bpm: DEFAULT_MEASURE_SIZE_BPM,
+            beat_unit: DEFAULT_BEAT_UNIT,
+            beat_grouping: Vec::new(),
+            beat_division: DEFAULT_BEAT_DIVISION,
+        }
+    }
 
     macro_rules! test_sample_data {
         (
@@ -154,39 +1028,698 @@ mod tests {
         };
     }
 
+    struct RecordingSink {
+        messages: Vec<TypedMidiMessage>,
+    }
+
+    impl MidiSink for RecordingSink {
+        fn feed(&mut self, midi_message: TypedMidiMessage) -> Result<()> {
+            self.messages.push(midi_message);
+            Ok(())
+        }
+    }
+
+    fn replay_quants(sample: &mut Sample, quants: u32) -> Vec<(u32, TypedMidiMessage)> {
+        let mut result = Vec::new();
+
+        for quant in 0..quants {
+            let mut sink = RecordingSink { messages: Vec::new() };
+            // Replays the ticks of the quant
+            let from = quant * TICKS_PER_QUANT + sample.replay_tick_length() - 1;
+            sample.replay(from, from + TICKS_PER_QUANT, &mut sink);
+            result.extend(sink.messages.into_iter().map(|message| (quant, message)));
+        }
+
+        result
+    }
+
     #[test]
     fn test_amount_of_measure_calculation() {
         let expected_amount_of_measures = 2;
 
         let buffer = test_sample_data! [
-            [0, 0, DEFAULT_MEASURE.measure_size_millis() * expected_amount_of_measures]
+            [0, 0, default_measure().measure_size_millis() * expected_amount_of_measures]
         ];
 
         // FIXME(#156): Add Unit Tests for shifted samples
-        let sample = Sample::new(buffer, &DEFAULT_MEASURE, 0);
+        let sample = Sample::new(buffer, &default_measure(), 0);
 
-        println!("{}", sample.amount_of_measures);
+        println!("{}", sample.amount_of_measures());
 
-        assert_eq!(expected_amount_of_measures, sample.amount_of_measures);
+        assert_eq!(expected_amount_of_measures, sample.amount_of_measures());
     }
 
     #[test]
     fn test_sample_serialization() {
         let expected_amount_of_measures = 2;
         let buffer = test_sample_data! [
-            [0, 0, DEFAULT_MEASURE.measure_size_millis() * expected_amount_of_measures]
+            [0, 0, default_measure().measure_size_millis() * expected_amount_of_measures]
         ];
 
-        let sample = Sample::new(buffer, &DEFAULT_MEASURE, 0);
+        let sample = Sample::new(buffer, &default_measure(), 0);
 
-        let massaged_sample: Sample = Sample::from_sample_data(&serde_json::from_str(&serde_json::to_string(&sample.as_sample_data()).unwrap()).unwrap());
+        let massaged_sample: Sample = Sample::from_sample_data(&serde_json::from_str(&serde_json::to_string(&sample.as_sample_data()).unwrap()).unwrap(), &default_measure());
 
         assert_eq!(sample.buffer, massaged_sample.buffer);
-        assert_eq!(sample.measure_shift, massaged_sample.measure_shift);
+        assert_eq!(sample.quant_shift, massaged_sample.quant_shift);
         assert_eq!(sample.notes, massaged_sample.notes);
         assert_eq!(sample.quants_per_measure, massaged_sample.quants_per_measure);
 
-        assert_eq!(sample.amount_of_measures, massaged_sample.amount_of_measures);
+        assert_eq!(sample.amount_of_measures(), massaged_sample.amount_of_measures());
         assert_eq!(sample.sample_quant_length, massaged_sample.sample_quant_length);
     }
+
+    #[test]
+    fn test_playback_rate_effective_amount_of_measures() {
+        let buffer = test_sample_data! [
+            [0, 0, default_measure().measure_size_millis()]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+
+        sample.set_playback_rate(PlaybackRate::Half);
+        assert_eq!(2, sample.effective_amount_of_measures());
+
+        sample.set_playback_rate(PlaybackRate::Double);
+        assert_eq!(1, sample.effective_amount_of_measures());
+
+        sample.set_playback_rate(PlaybackRate::TwoThirds);
+        assert_eq!(3, sample.effective_amount_of_measures());
+
+        sample.set_playback_rate(PlaybackRate::ThreeHalves);
+        assert_eq!(2, sample.effective_amount_of_measures());
+
+        assert_eq!(1, sample.amount_of_measures());
+    }
+
+    #[test]
+    fn test_playback_rate_replay() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 2],
+            [2, quant * 8, quant * 2]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+
+        sample.set_playback_rate(PlaybackRate::Half);
+        assert_eq!(vec![(0, test_msg!(on => 1)),
+                        (4 * GRID_SIZE_QUANTS, test_msg!(off => 1)),
+                        (16 * GRID_SIZE_QUANTS, test_msg!(on => 2)),
+                        (20 * GRID_SIZE_QUANTS, test_msg!(off => 2))],
+                   replay_quants(&mut sample, 32 * GRID_SIZE_QUANTS));
+
+        sample.set_playback_rate(PlaybackRate::Double);
+        assert_eq!(vec![(0, test_msg!(on => 1)),
+                        (1 * GRID_SIZE_QUANTS, test_msg!(off => 1)),
+                        (4 * GRID_SIZE_QUANTS, test_msg!(on => 2)),
+                        (5 * GRID_SIZE_QUANTS, test_msg!(off => 2)),
+                        (8 * GRID_SIZE_QUANTS, test_msg!(on => 1)),
+                        (9 * GRID_SIZE_QUANTS, test_msg!(off => 1)),
+                        (12 * GRID_SIZE_QUANTS, test_msg!(on => 2)),
+                        (13 * GRID_SIZE_QUANTS, test_msg!(off => 2))],
+                   replay_quants(&mut sample, 16 * GRID_SIZE_QUANTS));
+    }
+
+    #[test]
+    fn test_reverse_swaps_note_on_and_off() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = &[
+            AbsMidiEvent {
+                timestamp: 0,
+                message: TypedMidiMessage::NoteOn { channel: 0, key: 1, velocity: 100 },
+            },
+            AbsMidiEvent {
+                timestamp: quant * 4,
+                message: TypedMidiMessage::NoteOff { channel: 0, key: 1, velocity: 0 },
+            },
+            AbsMidiEvent {
+                timestamp: quant * 4,
+                message: TypedMidiMessage::NoteOn { channel: 0, key: 2, velocity: 50 },
+            },
+            AbsMidiEvent {
+                timestamp: quant * 6,
+                message: TypedMidiMessage::NoteOff { channel: 0, key: 2, velocity: 0 },
+            },
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        let original_buffer = sample.buffer.clone();
+
+        sample.toggle_reverse();
+        assert!(sample.reverse);
+        assert_eq!(original_buffer, sample.buffer);
+        assert_eq!(vec![
+            (0, TypedMidiMessage::NoteOff { channel: 0, key: 1, velocity: 0 }),
+            (10 * GRID_SIZE_QUANTS, TypedMidiMessage::NoteOn { channel: 0, key: 2, velocity: 50 }),
+            (12 * GRID_SIZE_QUANTS, TypedMidiMessage::NoteOff { channel: 0, key: 2, velocity: 0 }),
+            (12 * GRID_SIZE_QUANTS, TypedMidiMessage::NoteOn { channel: 0, key: 1, velocity: 100 }),
+        ], replay_quants(&mut sample, 16 * GRID_SIZE_QUANTS));
+
+        sample.toggle_reverse();
+        assert_eq!(sample.buffer, sample.replay_buffer);
+    }
+
+    #[test]
+    fn test_playback_rate_and_reverse_serialization() {
+        let buffer = test_sample_data! [
+            [0, 0, default_measure().measure_size_millis()]
+        ];
+
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        sample.set_playback_rate(PlaybackRate::Half);
+        sample.toggle_reverse();
+
+        let massaged_sample: Sample = Sample::from_sample_data(&serde_json::from_str(&serde_json::to_string(&sample.as_sample_data()).unwrap()).unwrap(), &default_measure());
+
+        assert_eq!(PlaybackRate::Half, massaged_sample.playback_rate());
+        assert!(massaged_sample.reverse);
+        assert_eq!(sample.replay_buffer, massaged_sample.replay_buffer);
+        assert_eq!(sample.effective_amount_of_measures(), massaged_sample.effective_amount_of_measures());
+    }
+
+    #[test]
+    fn test_output_channel() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4]
+        ];
+
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        sample.set_output_channel(Some(9));
+        assert_eq!(TypedMidiMessage::NoteOn { channel: 9, key: 1, velocity: 0 }, sample.replay_buffer[0].message);
+        assert_eq!(9, sample.notes[0].channel);
+        // The recorded channel is kept, so the override can be removed
+        assert_eq!(test_msg!(on => 1), sample.buffer[0].message);
+
+        let massaged_sample: Sample = Sample::from_sample_data(&serde_json::from_str(&serde_json::to_string(&sample.as_sample_data()).unwrap()).unwrap(), &default_measure());
+        assert_eq!(Some(9), massaged_sample.output_channel());
+        assert_eq!(sample.replay_buffer, massaged_sample.replay_buffer);
+
+        sample.set_output_channel(None);
+        assert_eq!(test_msg!(on => 1), sample.replay_buffer[0].message);
+    }
+
+    #[test]
+    fn test_select_note() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4],
+            [2, quant * 4, quant * 4],
+            [1, quant * 8, quant * 4]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        let Quant(length) = sample.quant_length();
+
+        assert_eq!(None, sample.select_note(3, Quant(GRID_SIZE_QUANTS * 9)));
+        assert!(!sample.has_selected_note());
+        assert_eq!(Some(NoteCondition::Always), sample.select_note(1, Quant(GRID_SIZE_QUANTS * 9)));
+
+        // Only the selected note is edited
+        assert_eq!(Some(NoteCondition::Probability(75)), sample.switch_selected_note_condition(true));
+        assert_eq!(vec![NoteCondition::Always, NoteCondition::Probability(75)],
+                   sample.buffer.iter()
+                   .filter(|event| match event.message {
+                       TypedMidiMessage::NoteOn { key: 1, .. } => true,
+                       _ => false,
+                   })
+                   .map(|event| event.condition)
+                   .collect::<Vec<_>>());
+        assert_eq!(vec![GRID_SIZE_QUANTS * 8],
+                   window_selected_notes(&sample, Quant(0), Quant(length)).iter()
+                   .map(|note| note.start_quant.as_u32())
+                   .collect::<Vec<_>>());
+
+        assert_eq!(Some(NoteCondition::Always), sample.switch_selected_note_condition(false));
+        assert_eq!(Some(NoteCondition::Cycle { cycle: 4, period: 4 }), sample.switch_selected_note_condition(false));
+
+        // The reversed note is selected where it is played
+        sample.toggle_reverse();
+        assert_eq!(Some(NoteCondition::Always), sample.select_note(1, Quant(length - GRID_SIZE_QUANTS)));
+        assert_eq!(Some(NoteCondition::Cycle { cycle: 4, period: 4 }),
+                   sample.select_note(1, Quant(length - GRID_SIZE_QUANTS * 10)));
+        assert_eq!(vec![length - GRID_SIZE_QUANTS * 12],
+                   window_selected_notes(&sample, Quant(0), Quant(length)).iter()
+                   .map(|note| note.start_quant.as_u32())
+                   .collect::<Vec<_>>());
+
+        // The selection is lost once the note is moved
+        sample.nudge(1);
+        assert!(!sample.has_selected_note());
+        assert_eq!(None, sample.switch_selected_note_condition(true));
+    }
+
+    #[test]
+    fn test_note_conditions() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4],
+            [2, quant * 4, quant * 4]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        assert_eq!(Some(NoteCondition::Probability(75)), sample.switch_note_condition(0, 1));
+        assert_eq!(None, sample.switch_note_condition(0, 3));
+        sample.buffer[0].condition = NoteCondition::Cycle { cycle: 2, period: 2 };
+        sample.buffer[2].condition = NoteCondition::Probability(50);
+        sample.update_replay_buffer();
+
+        // The conditions survive saving
+        let massaged_sample: Sample = Sample::from_sample_data(&serde_json::from_str(&serde_json::to_string(&sample.as_sample_data()).unwrap()).unwrap(), &default_measure());
+        assert_eq!(sample.replay_buffer, massaged_sample.replay_buffer);
+
+        let replay_cycles = |sample: &mut Sample, cycles: u32| -> Vec<Vec<TypedMidiMessage>> {
+            let loop_ticks = sample.replay_tick_length();
+            (0..cycles).map(|cycle| {
+                let mut sink = RecordingSink { messages: Vec::new() };
+                // Starts at the end of the first cycle, so the replay
+                // covers the whole cycles from the second one on
+                for tick in (cycle + 1) * loop_ticks - 1..(cycle + 2) * loop_ticks - 1 {
+                    sample.replay(tick, tick + 1, &mut sink);
+                }
+                sink.messages
+            }).collect()
+        };
+
+        let mut replayed_sample = sample.clone();
+        let cycles = replay_cycles(&mut replayed_sample, 16);
+        for (index, messages) in cycles.iter().enumerate() {
+            let contains = |message| messages.contains(&message);
+            let every_second_cycle = (index + 1) % 2 == 1;
+            assert_eq!(every_second_cycle, contains(test_msg!(on => 1)));
+            assert_eq!(every_second_cycle, contains(test_msg!(off => 1)));
+            // The skipped notes are not released
+            assert_eq!(contains(test_msg!(on => 2)), contains(test_msg!(off => 2)));
+        }
+        assert!(cycles.iter().any(|messages| messages.contains(&test_msg!(on => 2))));
+        assert!(cycles.iter().any(|messages| !messages.contains(&test_msg!(on => 2))));
+
+        // The probabilities are reproducible
+        assert_eq!(cycles, replay_cycles(&mut sample.clone(), 16));
+
+        // but differ between the samples
+        let mut other_sample = sample.clone();
+        other_sample.seed_note_conditions(1);
+        sample.seed_note_conditions(0);
+        assert_eq!(cycles, replay_cycles(&mut sample, 16));
+        assert!(cycles != replay_cycles(&mut other_sample, 16));
+    }
+
+    #[test]
+    fn test_automation() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        let modulation = AutomationLane::ControlChange(1);
+        assert!(sample.automation_lanes().is_empty());
+        assert!(window_automation(&sample, modulation, Quant(0), sample.quants_per_measure()).is_empty());
+
+        sample.draw_automation(modulation, Quant(24), 64);
+        sample.draw_automation(modulation, Quant(48), 200);
+        sample.draw_automation(AutomationLane::PitchBend, Quant(0), 8192);
+        assert_eq!(vec![modulation, AutomationLane::PitchBend], sample.automation_lanes());
+
+        // The last value holds from the previous repetition of the sample
+        assert_eq!(vec![
+            AutomationPoint { quant: Quant(0), value: 127 },
+            AutomationPoint { quant: Quant(24), value: 64 },
+            AutomationPoint { quant: Quant(48), value: 127 },
+        ], window_automation(&sample, modulation, Quant(0), sample.quants_per_measure()));
+
+        // Drawing at the same quant replaces the value
+        sample.draw_automation(modulation, Quant(48), 32);
+        assert_eq!(3, window_automation(&sample, modulation, Quant(0), sample.quants_per_measure()).len());
+
+        sample.clear_automation(modulation);
+        assert_eq!(vec![AutomationLane::PitchBend], sample.automation_lanes());
+        assert_eq!(3, sample.buffer.len());
+    }
+
+    #[test]
+    fn test_step_pattern() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant],
+            [2, quant * 4, quant]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        let step_quants = default_measure().grid_size_quants();
+
+        let mut pattern = sample.step_pattern(step_quants, 9);
+        assert_eq!(0, pattern.channel);
+        assert_eq!(16, pattern.amount_of_steps());
+        let row = pattern.rows.iter().position(|row| row.key == 1).unwrap();
+        assert!(pattern.rows[row].steps[0].is_some());
+        pattern.toggle_step(row, 0);
+        pattern.toggle_step(row, 2);
+
+        sample.write_step_pattern(&pattern);
+        assert_eq!(vec![
+            (12, TypedMidiMessage::NoteOn { channel: 0, key: 1, velocity: DEFAULT_STEP_VELOCITY }),
+            (17, test_msg!(off => 1)),
+            // The recorded note is left alone
+            (24, test_msg!(on => 2)),
+            (30, test_msg!(off => 2)),
+        ], replay_quants(&mut sample, 96));
+        pattern.mark_written();
+        assert_eq!(pattern, sample.step_pattern(step_quants, 9));
+
+        // The notes of the untouched steps are kept as they are
+        sample.switch_note_condition(0, 2);
+        let untouched: Vec<QuantMidiEvent> = sample.buffer.iter()
+            .filter(|event| event.message.channel() == Some(0) && event.quant >= Quant(24))
+            .cloned()
+            .collect();
+        let mut pattern = sample.step_pattern(step_quants, 9);
+        let row = pattern.rows.iter().position(|row| row.key == 1).unwrap();
+        pattern.change_velocity(row, 2, false);
+        sample.write_step_pattern(&pattern);
+        assert_eq!(untouched, sample.buffer[2..].to_vec());
+        assert_eq!(TypedMidiMessage::NoteOn {
+            channel: 0,
+            key: 1,
+            velocity: DEFAULT_STEP_VELOCITY - STEP_VELOCITY_STEP,
+        }, sample.buffer[0].message);
+    }
+
+    #[test]
+    fn test_migration_of_quants_per_measure() {
+        // Saved when 4/4 measure had 16 quants
+        let sample_data = SampleData {
+            amount_of_measures: 1,
+            quant_length: None,
+            buffer: vec![
+                QuantMidiEvent { message: test_msg!(on => 1), quant: Quant(4), offset: 0, condition: NoteCondition::Always },
+                QuantMidiEvent { message: test_msg!(off => 1), quant: Quant(8), offset: 0, condition: NoteCondition::Always },
+            ],
+            measure_shift: 0,
+            quant_shift: None,
+            quants_per_measure: 16,
+            playback_rate: PlaybackRate::Normal,
+            reverse: false,
+            swing: 0,
+            groove: None,
+            quantize_strength: 100,
+            output_channel: None,
+        };
+
+        let mut sample = Sample::from_sample_data(&sample_data, &default_measure());
+
+        assert_eq!(default_measure().quants_per_measure(), sample.quants_per_measure);
+        assert_eq!(vec![(24, test_msg!(on => 1)), (48, test_msg!(off => 1))],
+                   replay_quants(&mut sample, 96));
+        assert_eq!(96, sample.as_sample_data().quants_per_measure);
+    }
+
+    #[test]
+    fn test_swing_delays_off_beat() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, quant * 2, quant]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+
+        sample.set_swing(50);
+        assert_eq!(vec![(18, test_msg!(on => 1)), (21, test_msg!(off => 1))],
+                   replay_quants(&mut sample, 96));
+
+        sample.set_swing(100);
+        assert_eq!(MAX_SWING_PERCENT, sample.swing());
+    }
+
+    #[test]
+    fn test_extract_groove_from_recording() {
+        let measure = default_measure();
+        let step_millis = measure.beat_size_millis() / GROOVE_BEAT_DIVISION;
+        let quant_millis = measure.beat_size_millis() / QUANTS_PER_BEAT;
+        // Every second step is played two quants late and snapped back
+        // to the recording grid
+        let buffer = test_sample_data! [
+            [1, 0, quant_millis],
+            [1, step_millis + 2 * quant_millis, quant_millis],
+            [1, 2 * step_millis, quant_millis],
+            [1, 3 * step_millis + 2 * quant_millis, quant_millis]
+        ];
+        let sample = Sample::new(buffer, &measure, 0);
+        assert_eq!(Quant(GRID_SIZE_QUANTS), sample.buffer[2].quant);
+
+        let groove = sample.extract_groove();
+        assert_eq!(vec![0, 2, 0, 2], groove.offsets[..4].to_vec());
+    }
+
+    #[test]
+    fn test_swing_and_groove_serialization() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant],
+            [2, quant * 2, quant]
+        ];
+
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        sample.set_swing(30);
+        sample.set_groove(Some(Groove { step_quants: 6, offsets: vec![0, 1] }));
+
+        let massaged_sample: Sample = Sample::from_sample_data(&serde_json::from_str(&serde_json::to_string(&sample.as_sample_data()).unwrap()).unwrap(), &default_measure());
+
+        assert_eq!(30, massaged_sample.swing());
+        assert!(massaged_sample.has_groove());
+        assert_eq!(sample.replay_buffer, massaged_sample.replay_buffer);
+    }
+
+    #[test]
+    fn test_quantize_strength() {
+        let quant = default_measure().grid_size_millis();
+        // Recorded a bit later than the first step of the grid
+        let buffer = test_sample_data! [
+            [1, quant + 10, quant]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        assert_eq!(vec![(6, test_msg!(on => 1)), (12, test_msg!(off => 1))],
+                   replay_quants(&mut sample, 96));
+        assert_eq!(0, sample.replay_buffer[0].offset);
+
+        sample.set_quantize_strength(0);
+        assert_eq!(vec![(6, test_msg!(on => 1)), (12, test_msg!(off => 1))],
+                   replay_quants(&mut sample, 96));
+        assert_eq!(Quant(6), sample.replay_buffer[0].quant);
+        assert_eq!(9, sample.replay_buffer[0].offset);
+
+        let mut sink = RecordingSink { messages: Vec::new() };
+        sample.replay(120, 128, &mut sink);
+        assert!(sink.messages.is_empty());
+        sample.replay(128, 129, &mut sink);
+        assert_eq!(vec![test_msg!(on => 1)], sink.messages);
+
+        sample.set_quantize_strength(50);
+        assert_eq!(4, sample.replay_buffer[0].offset);
+    }
+
+    #[test]
+    fn test_requantize() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, quant * 3, quant]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        assert_eq!(Quant(18), sample.buffer[0].quant);
+
+        let eighths = Measure { beat_division: 2, .. default_measure() };
+        sample.requantize(&eighths);
+        assert_eq!(Quant(24), sample.buffer[0].quant);
+        assert_eq!(-6 * TICKS_PER_QUANT as i32, sample.buffer[0].offset);
+
+        sample.requantize(&default_measure());
+        assert_eq!(Quant(18), sample.buffer[0].quant);
+        assert_eq!(0, sample.buffer[0].offset);
+    }
+
+    #[test]
+    fn test_nudge_wraps_around() {
+        let quant = default_measure().grid_size_millis();
+        let quants_per_measure = default_measure().quants_per_measure();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4 + 1]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+
+        sample.nudge(-(GRID_SIZE_QUANTS as i32));
+        assert_eq!(test_msg!(off => 1), sample.buffer[0].message);
+        assert_eq!(Quant(3 * GRID_SIZE_QUANTS), sample.buffer[0].quant);
+        assert_eq!(test_msg!(on => 1), sample.buffer[1].message);
+        assert_eq!(quants_per_measure - Quant(GRID_SIZE_QUANTS), sample.buffer[1].quant);
+
+        sample.nudge(GRID_SIZE_QUANTS as i32);
+        assert_eq!(Quant(0), sample.buffer[0].quant);
+    }
+
+    #[test]
+    fn test_nudge_measures() {
+        let measure_size_millis = default_measure().measure_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, measure_size_millis * 2]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+
+        let quants_per_measure = sample.quants_per_measure();
+        sample.nudge_measures(1);
+        assert_eq!(quants_per_measure, sample.replay_quant(Quant(0)));
+        sample.nudge_measures(-1);
+        assert_eq!(Quant(0), sample.replay_quant(Quant(0)));
+    }
+
+    #[test]
+    fn test_sample_shorter_than_measure() {
+        let measure = default_measure();
+        let buffer = test_sample_data! [
+            [1, 0, measure.beat_size_millis() / 2]
+        ];
+        let length = Quant(3 * QUANTS_PER_BEAT);
+        let mut sample = Sample::with_length(buffer, &measure, length, Quant(0));
+
+        assert_eq!(length, sample.replay_quant_length());
+        assert_eq!(1, sample.amount_of_measures());
+        assert_eq!(vec![0, 72], replay_quants(&mut sample, 96).into_iter()
+                   .filter(|&(_, message)| message == test_msg!(on => 1))
+                   .map(|(quant, _)| quant)
+                   .collect::<Vec<_>>());
+
+        // The second measure of the looper starts on the second beat
+        // of the sample
+        let starts = |notes: Vec<Note>| notes.iter().map(|note| note.start_quant).collect::<Vec<_>>();
+        let quants_per_measure = measure.quants_per_measure();
+        assert_eq!(vec![Quant(0), Quant(72)], starts(window_notes(&sample, Quant(0), quants_per_measure)));
+        assert_eq!(vec![Quant(48)], starts(window_notes(&sample, quants_per_measure, quants_per_measure)));
+
+        let data = sample.as_sample_data();
+        assert_eq!(Some(length.as_u32()), data.quant_length);
+        assert_eq!(length, Sample::from_sample_data(&data, &measure).quant_length());
+    }
+
+    #[test]
+    fn test_trim_releases_held_notes() {
+        let measure_size_millis = default_measure().measure_size_millis();
+        let tick_length = default_measure().ticks_per_measure() as i64;
+        let buffer = &[
+            AbsMidiEvent { timestamp: 0, message: test_msg!(on => 1) },
+            AbsMidiEvent { timestamp: measure_size_millis, message: test_msg!(on => 2) },
+            AbsMidiEvent { timestamp: measure_size_millis + 100, message: test_msg!(off => 2) },
+            AbsMidiEvent { timestamp: measure_size_millis * 2 - 100, message: test_msg!(off => 1) },
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        assert_eq!(2, sample.amount_of_measures());
+
+        sample.set_amount_of_measures(1);
+        assert_eq!(1, sample.amount_of_measures());
+        assert_eq!(2, sample.buffer.len());
+        assert_eq!(test_msg!(on => 1), sample.buffer[0].message);
+        assert_eq!(test_msg!(off => 1), sample.buffer[1].message);
+        assert_eq!(tick_length - 1, sample.buffer[1].tick());
+
+        sample.set_amount_of_measures(3);
+        assert_eq!(3, sample.effective_amount_of_measures());
+        assert_eq!(2, sample.buffer.len());
+    }
+
+    #[test]
+    fn test_double() {
+        let quant = default_measure().grid_size_millis();
+        let quants_per_measure = default_measure().quants_per_measure();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+
+        sample.double();
+        assert_eq!(2, sample.amount_of_measures());
+        assert_eq!(4, sample.buffer.len());
+        assert_eq!(sample.buffer[0].quant + quants_per_measure, sample.buffer[2].quant);
+        assert_eq!(2, sample.notes.len());
+    }
+
+    #[test]
+    fn test_merge_takes_shift_into_account() {
+        let quant = default_measure().grid_size_millis();
+        let measure_size_millis = default_measure().measure_size_millis();
+        let quants_per_measure = default_measure().quants_per_measure();
+        let one_measure = Sample::new(test_sample_data! [[1, 0, quant * 4]], &default_measure(), 0);
+        let two_measures = Sample::new(test_sample_data! [[2, 0, measure_size_millis * 2]], &default_measure(), 1);
+
+        let merged = Sample::merge(&[&one_measure, &two_measures], quants_per_measure);
+        assert_eq!(2, merged.amount_of_measures());
+        assert_eq!(Quant(0), merged.quant_shift);
+
+        let note_ons: Vec<(Quant, TypedMidiMessage)> = merged.buffer.iter()
+            .filter(|event| match event.message {
+                TypedMidiMessage::NoteOn { .. } => true,
+                _ => false,
+            })
+            .map(|event| (event.quant, event.message))
+            .collect();
+        assert_eq!(vec![
+            (Quant(0), test_msg!(on => 1)),
+            (quants_per_measure, test_msg!(on => 1)),
+            (quants_per_measure, test_msg!(on => 2)),
+        ], note_ons);
+    }
+
+    #[test]
+    fn test_split() {
+        let quant = default_measure().grid_size_millis();
+        let buffer = test_sample_data! [
+            [1, 0, quant * 4],
+            [2, quant * 4, quant * 4],
+            [1, quant * 8, quant * 4]
+        ];
+        let mut sample = Sample::new(buffer, &default_measure(), 0);
+        sample.set_swing(25);
+
+        let parts = sample.split(|message| match *message {
+            TypedMidiMessage::NoteOn { key, .. } | TypedMidiMessage::NoteOff { key, .. } => key as usize,
+            _ => 0,
+        });
+        assert_eq!(2, parts.len());
+        assert_eq!(4, parts[0].buffer.len());
+        assert_eq!(2, parts[1].buffer.len());
+        assert_eq!(test_msg!(on => 2), parts[1].buffer[0].message);
+        assert_eq!(25, parts[1].swing());
+    }
+
+    /// Replays many samples dense with CC data tick by tick, the way
+    /// the looper does. Returns the amount of the recorded events, the
+    /// replayed ones and how long the replay took.
+    fn replay_dense_samples() -> (usize, usize, Duration) {
+        let measure = default_measure();
+        let amount_of_measures = 4;
+        let buffer: Vec<AbsMidiEvent> = (0..measure.measure_size_millis() * amount_of_measures / 2).map(|index| {
+            AbsMidiEvent {
+                message: TypedMidiMessage::ControlChange { channel: 0, number: 1, value: (index % 128) as u8 },
+                timestamp: index * 2,
+            }
+        }).collect();
+        let mut samples: Vec<Sample> = (0..32).map(|_| Sample::new(&buffer, &measure, 0)).collect();
+        let loop_ticks = amount_of_measures * measure.ticks_per_measure();
+
+        let mut sink = RecordingSink { messages: Vec::new() };
+        let start = Instant::now();
+        for tick in 0..loop_ticks {
+            for sample in &mut samples {
+                sample.replay(tick + loop_ticks - 1, tick + loop_ticks, &mut sink);
+            }
+        }
+
+        (samples.len() * buffer.len(), sink.messages.len(), start.elapsed())
+    }
+
+    #[test]
+    fn test_replay_dense_samples() {
+        let (recorded, replayed, _) = replay_dense_samples();
+        assert_eq!(recorded, replayed);
+    }
+
+    /// Run it with `cargo test -- --ignored --nocapture` to see the
+    /// timing
+    #[test]
+    #[ignore]
+    fn bench_replay_dense_samples() {
+        let (_, replayed, elapsed) = replay_dense_samples();
+        println!("Replayed {} events in {:?}", replayed, elapsed);
+    }
 }
@@ -1,12 +1,40 @@
 use measure::QuantMidiEvent;
+use looper::{PlaybackRate, Groove};
+
+fn default_quantize_strength() -> u32 {
+    100
+}
 
 /// The purpose of this struct is to be serialized or deserialized by
 /// serde without implementing custom Deserialize trait, because doing
 /// that comparing to rustc_serialize is more difficult.
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SampleData {
+    /// Amount of measures the sample lasts rounded up
     pub amount_of_measures: u32,
+    /// Length of the sample in quants if it is not a whole number of
+    /// measures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quant_length: Option<u32>,
     pub buffer: Vec<QuantMidiEvent>,
+    /// Amount of whole measures the sample is shifted by
     pub measure_shift: u32,
-    pub quants_per_measure: u32
+    /// Shift of the sample in quants if it is not a whole number of
+    /// measures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quant_shift: Option<u32>,
+    /// Resolution of the quants of the buffer
+    pub quants_per_measure: u32,
+    #[serde(default)]
+    pub playback_rate: PlaybackRate,
+    #[serde(default)]
+    pub reverse: bool,
+    #[serde(default)]
+    pub swing: u32,
+    #[serde(default)]
+    pub groove: Option<Groove>,
+    #[serde(default = "default_quantize_strength")]
+    pub quantize_strength: u32,
+    #[serde(default)]
+    pub output_channel: Option<u8>,
 }
@@ -0,0 +1,11 @@
+use looper::Sample;
+
+/// The way to revert an edit of the composition
+#[derive(Clone)]
+pub enum SampleEdit {
+    /// The sample at the index was replaced by its edited version
+    Changed(usize, Sample),
+    /// The sample at the index was added to the end of the
+    /// composition
+    Added(usize),
+}
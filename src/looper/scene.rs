@@ -0,0 +1,43 @@
+const DEFAULT_SCENE_CYCLES: u32 = 4;
+
+fn default_scene_cycles() -> u32 {
+    DEFAULT_SCENE_CYCLES
+}
+
+/// Named group of samples of the composition that are played
+/// together, e.g. intro, verse or chorus.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub name: String,
+    /// Indices of the samples of the composition
+    pub samples: Vec<usize>,
+    /// How many cycles the scene is played in the song mode before
+    /// switching to the next one
+    #[serde(default = "default_scene_cycles")]
+    pub cycles: u32,
+}
+
+impl Scene {
+    pub fn new(name: String, samples: Vec<usize>) -> Scene {
+        Scene {
+            name: name,
+            samples: samples,
+            cycles: DEFAULT_SCENE_CYCLES,
+        }
+    }
+
+    pub fn contains(&self, sample_index: usize) -> bool {
+        self.samples.contains(&sample_index)
+    }
+
+    /// Forgets about the removed sample and shifts the indices of the
+    /// samples that were after it.
+    pub fn remove_sample(&mut self, sample_index: usize) {
+        self.samples.retain(|&index| index != sample_index);
+        for index in &mut self.samples {
+            if *index > sample_index {
+                *index -= 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use std::cmp;
+
+use hardcode::*;
+use measure::{Quant, QuantMidiEvent};
+use midi::{TypedMidiMessage, Note};
+
+/// Row of the step sequencer playing one key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepRow {
+    pub key: u8,
+    /// Velocities of the steps, `None` for the steps that are off
+    pub steps: Vec<Option<u8>>,
+}
+
+/// Cell of the step sequencer edited since the pattern was last
+/// written into the sample
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepChange {
+    pub key: u8,
+    pub step: usize,
+    pub velocity: Option<u8>,
+}
+
+/// Notes of one channel of a sample laid out on the grid of the step
+/// sequencer. The notes between the steps are shown on the closest
+/// ones, but keep their timing in the sample.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepPattern {
+    pub channel: u8,
+    pub step_quants: u32,
+    pub rows: Vec<StepRow>,
+    /// The rows as they are in the sample
+    written_rows: Vec<StepRow>,
+    /// Keys of the notes of the channel that did not fit into the rows
+    hidden_keys: Vec<u8>,
+}
+
+impl StepPattern {
+    /// The rows take the keys of the notes first, the rest of them
+    /// take `STEP_SEQUENCER_DEFAULT_KEYS`. The notes of the keys that
+    /// did not fit are left alone.
+    pub fn from_notes(notes: &[Note], channel: u8, step_quants: u32, amount_of_steps: usize) -> StepPattern {
+        let mut keys: Vec<u8> = notes.iter()
+            .filter(|note| note.channel == channel)
+            .map(|note| note.key)
+            .collect();
+        keys.sort();
+        keys.dedup();
+        let hidden_keys = if keys.len() > STEP_SEQUENCER_ROWS {
+            keys.split_off(STEP_SEQUENCER_ROWS)
+        } else {
+            Vec::new()
+        };
+        for key in STEP_SEQUENCER_DEFAULT_KEYS.iter() {
+            if keys.len() < STEP_SEQUENCER_ROWS && !keys.contains(key) {
+                keys.push(*key);
+            }
+        }
+        // The highest keys go on top like in the piano roll
+        keys.sort_by(|a, b| b.cmp(a));
+
+        let mut rows: Vec<StepRow> = keys.iter().map(|&key| {
+            StepRow {
+                key: key,
+                steps: vec![None; amount_of_steps],
+            }
+        }).collect();
+
+        let mut pattern = StepPattern {
+            channel: channel,
+            step_quants: step_quants,
+            rows: Vec::new(),
+            written_rows: Vec::new(),
+            hidden_keys: hidden_keys,
+        };
+        for note in notes.iter().filter(|note| note.channel == channel) {
+            let step = pattern.step_of(note.start_quant, amount_of_steps);
+            if let Some(row) = rows.iter_mut().find(|row| row.key == note.key) {
+                row.steps[step] = Some(note.velocity);
+            }
+        }
+
+        pattern.written_rows = rows.clone();
+        pattern.rows = rows;
+        pattern
+    }
+
+    /// The step the note starting at the quant is shown on
+    fn step_of(&self, Quant(quant): Quant, amount_of_steps: usize) -> usize {
+        ((quant + self.step_quants / 2) / self.step_quants) as usize % amount_of_steps
+    }
+
+    /// Whether the note starting at the quant is shown on the step
+    pub fn is_on_step(&self, quant: Quant, step: usize) -> bool {
+        self.step_of(quant, self.amount_of_steps()) == step
+    }
+
+    pub fn amount_of_steps(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.steps.len())
+    }
+
+    /// Turns the step on with `DEFAULT_STEP_VELOCITY` or off. Returns
+    /// the new velocity of the step.
+    pub fn toggle_step(&mut self, row: usize, step: usize) -> Option<u8> {
+        let velocity = &mut self.rows[row].steps[step];
+        *velocity = match *velocity {
+            Some(_) => None,
+            None => Some(DEFAULT_STEP_VELOCITY),
+        };
+        *velocity
+    }
+
+    /// Changes the velocity of the step by `STEP_VELOCITY_STEP` if the
+    /// step is on
+    pub fn change_velocity(&mut self, row: usize, step: usize, increase: bool) -> Option<u8> {
+        let velocity = &mut self.rows[row].steps[step];
+        *velocity = velocity.map(|velocity| {
+            if increase {
+                cmp::min(velocity.saturating_add(STEP_VELOCITY_STEP), 127)
+            } else {
+                cmp::max(velocity.saturating_sub(STEP_VELOCITY_STEP), 1)
+            }
+        });
+        *velocity
+    }
+
+    /// Moves the key of the row by a semitone skipping the keys of the
+    /// other rows and of the notes that did not fit into the rows.
+    /// Returns the new key.
+    pub fn change_key(&mut self, row: usize, increase: bool) -> u8 {
+        let mut key = self.rows[row].key;
+        loop {
+            key = match (increase, key) {
+                (true, 127) | (false, 0) => return self.rows[row].key,
+                (true, key) => key + 1,
+                (false, key) => key - 1,
+            };
+            if !self.rows.iter().any(|row| row.key == key) && !self.hidden_keys.contains(&key) {
+                break;
+            }
+        }
+
+        self.rows[row].key = key;
+        key
+    }
+
+    /// The rows whose keys have changed since the pattern was last
+    /// written as pairs of the old and the new key
+    pub fn key_changes(&self) -> Vec<(u8, u8)> {
+        self.written_rows.iter().zip(self.rows.iter())
+            .filter(|&(written_row, row)| written_row.key != row.key)
+            .map(|(written_row, row)| (written_row.key, row.key))
+            .collect()
+    }
+
+    /// The steps that have changed since the pattern was last written
+    /// with the new keys of the rows
+    pub fn step_changes(&self) -> Vec<StepChange> {
+        let mut changes = Vec::new();
+        for (written_row, row) in self.written_rows.iter().zip(self.rows.iter()) {
+            for (step, (written_velocity, velocity)) in written_row.steps.iter().zip(row.steps.iter()).enumerate() {
+                if written_velocity != velocity {
+                    changes.push(StepChange {
+                        key: row.key,
+                        step: step,
+                        velocity: *velocity,
+                    });
+                }
+            }
+        }
+        changes
+    }
+
+    /// Remembers that the changes are written into the sample
+    pub fn mark_written(&mut self) {
+        self.written_rows = self.rows.clone();
+    }
+
+    /// The note of the step turned on. It lasts until the end of the
+    /// step.
+    pub fn step_events(&self, key: u8, step: usize, velocity: u8) -> Vec<QuantMidiEvent> {
+        let step_ticks = self.step_quants * TICKS_PER_QUANT;
+        let tick = step as u32 * step_ticks;
+        vec![
+            QuantMidiEvent::at_tick(TypedMidiMessage::NoteOn {
+                channel: self.channel,
+                key: key,
+                velocity: velocity,
+            }, tick),
+            QuantMidiEvent::at_tick(TypedMidiMessage::NoteOff {
+                channel: self.channel,
+                key: key,
+                velocity: 0,
+            }, tick + step_ticks - 1),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StepPattern, StepChange};
+    use hardcode::*;
+    use measure::{Quant, NoteCondition};
+    use midi::{Note, TypedMidiMessage};
+
+    fn note(key: u8, start_quant: u32, velocity: u8) -> Note {
+        Note {
+            start_quant: Quant(start_quant),
+            end_quant: Quant(start_quant + 3),
+            key: key,
+            channel: 9,
+            velocity: velocity,
+            condition: NoteCondition::Always,
+        }
+    }
+
+    #[test]
+    fn test_from_notes() {
+        let notes = [note(36, 0, 100), note(40, 13, 80), note(40, 47, 90)];
+        let pattern = StepPattern::from_notes(&notes, 9, 6, 8);
+
+        assert_eq!(STEP_SEQUENCER_ROWS, pattern.rows.len());
+        assert_eq!(8, pattern.amount_of_steps());
+        let row = |key| pattern.rows.iter().find(|row| row.key == key).unwrap();
+        assert_eq!(Some(100), row(36).steps[0]);
+        assert_eq!(Some(80), row(40).steps[2]);
+        // Rounded to the step after the last one, i.e. the first one
+        assert_eq!(Some(90), row(40).steps[0]);
+        assert!(row(38).steps.iter().all(|step| step.is_none()));
+        assert!(pattern.rows.windows(2).all(|rows| rows[0].key > rows[1].key));
+    }
+
+    #[test]
+    fn test_edit_steps() {
+        let mut pattern = StepPattern::from_notes(&[], 9, 6, 16);
+        assert_eq!(Some(DEFAULT_STEP_VELOCITY), pattern.toggle_step(0, 4));
+        assert_eq!(Some(DEFAULT_STEP_VELOCITY + STEP_VELOCITY_STEP), pattern.change_velocity(0, 4, true));
+        assert_eq!(None, pattern.change_velocity(0, 5, true));
+        let key = pattern.rows[0].key;
+        assert_eq!(vec![StepChange {
+            key: key,
+            step: 4,
+            velocity: Some(DEFAULT_STEP_VELOCITY + STEP_VELOCITY_STEP),
+        }], pattern.step_changes());
+        assert_eq!(None, pattern.toggle_step(0, 4));
+        assert!(pattern.step_changes().is_empty());
+
+        // The keys of the other rows, 38 and 39, are skipped
+        let lowest = pattern.rows.len() - 1;
+        assert_eq!(37, pattern.change_key(lowest, true));
+        assert_eq!(40, pattern.change_key(lowest, true));
+        assert_eq!(37, pattern.change_key(lowest, false));
+        assert_eq!(vec![(36, 37)], pattern.key_changes());
+        pattern.mark_written();
+        assert!(pattern.key_changes().is_empty());
+        assert_eq!(TypedMidiMessage::NoteOn { channel: 9, key: 40, velocity: 1 },
+                   pattern.step_events(40, 1, 1)[0].message);
+    }
+
+    #[test]
+    fn test_hidden_keys_are_skipped() {
+        let notes: Vec<Note> = (0..STEP_SEQUENCER_ROWS as u8 + 1).map(|index| note(60 + 2 * index, 0, 100)).collect();
+        let mut pattern = StepPattern::from_notes(&notes, 9, 6, 8);
+        let highest_key = 60 + 2 * STEP_SEQUENCER_ROWS as u8;
+        assert!(pattern.rows.iter().all(|row| row.key != highest_key));
+
+        // The key of the note that did not fit is not taken
+        assert_eq!(highest_key - 1, pattern.change_key(0, true));
+        assert_eq!(highest_key + 1, pattern.change_key(0, true));
+    }
+}
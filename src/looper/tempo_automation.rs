@@ -0,0 +1,131 @@
+use std::cmp;
+
+/// Gradual change of the tempo over the given amount of ticks
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TempoRamp {
+    from_bpm: u32,
+    to_bpm: u32,
+    length_ticks: u32,
+    elapsed_ticks: u32,
+}
+
+impl TempoRamp {
+    pub fn new(from_bpm: u32, to_bpm: u32, length_ticks: u32) -> TempoRamp {
+        TempoRamp {
+            from_bpm: from_bpm,
+            to_bpm: to_bpm,
+            length_ticks: length_ticks,
+            elapsed_ticks: 0,
+        }
+    }
+
+    /// Moves along the ramp and returns the tempo reached
+    pub fn advance(&mut self, delta_ticks: u32) -> u32 {
+        self.elapsed_ticks = cmp::min(self.elapsed_ticks + delta_ticks, self.length_ticks);
+        self.tempo_bpm()
+    }
+
+    pub fn tempo_bpm(&self) -> u32 {
+        if self.length_ticks == 0 {
+            return self.to_bpm;
+        }
+
+        interpolate(self.from_bpm, self.to_bpm, self.elapsed_ticks, self.length_ticks)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed_ticks >= self.length_ticks
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TempoPoint {
+    /// Position in the loop in ticks
+    pub tick: u32,
+    pub tempo_bpm: u32,
+}
+
+/// Recorded changes of the tempo within the loop. The tempo between
+/// the points changes linearly, before the first and after the last
+/// point it stays the same.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TempoAutomation {
+    pub points: Vec<TempoPoint>,
+}
+
+impl TempoAutomation {
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Adds the point keeping the points sorted. The point recorded
+    /// at the same tick before is replaced.
+    pub fn record(&mut self, tick: u32, tempo_bpm: u32) {
+        let point = TempoPoint { tick: tick, tempo_bpm: tempo_bpm };
+        match self.points.binary_search_by_key(&tick, |point| point.tick) {
+            Ok(index) => self.points[index] = point,
+            Err(index) => self.points.insert(index, point),
+        }
+    }
+
+    pub fn tempo_bpm(&self, tick: u32) -> Option<u32> {
+        let next = self.points.iter()
+            .position(|point| point.tick > tick)
+            .unwrap_or(self.points.len());
+
+        if next == 0 {
+            return self.points.first().map(|point| point.tempo_bpm);
+        }
+
+        let from = self.points[next - 1];
+        match self.points.get(next) {
+            Some(to) => Some(interpolate(from.tempo_bpm, to.tempo_bpm, tick - from.tick, to.tick - from.tick)),
+            None => Some(from.tempo_bpm),
+        }
+    }
+}
+
+fn interpolate(from_bpm: u32, to_bpm: u32, elapsed: u32, length: u32) -> u32 {
+    let from_bpm = from_bpm as i64;
+    let to_bpm = to_bpm as i64;
+    (from_bpm + (to_bpm - from_bpm) * elapsed as i64 / length as i64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TempoRamp, TempoAutomation};
+
+    #[test]
+    fn test_ramp() {
+        let mut ramp = TempoRamp::new(120, 140, 100);
+
+        assert_eq!(120, ramp.tempo_bpm());
+        assert_eq!(130, ramp.advance(50));
+        assert!(!ramp.is_finished());
+        assert_eq!(140, ramp.advance(100));
+        assert!(ramp.is_finished());
+
+        let mut slow_down = TempoRamp::new(140, 120, 100);
+        assert_eq!(135, slow_down.advance(25));
+    }
+
+    #[test]
+    fn test_automation() {
+        let mut automation = TempoAutomation::default();
+        assert_eq!(None, automation.tempo_bpm(0));
+
+        automation.record(100, 100);
+        automation.record(300, 140);
+        automation.record(200, 90);
+        automation.record(200, 120);
+
+        assert_eq!(100, automation.tempo_bpm(0).unwrap());
+        assert_eq!(110, automation.tempo_bpm(150).unwrap());
+        assert_eq!(130, automation.tempo_bpm(250).unwrap());
+        assert_eq!(140, automation.tempo_bpm(400).unwrap());
+    }
+}
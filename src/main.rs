@@ -15,19 +15,18 @@ extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 
+extern crate dimooper;
+
 use std::path::{Path, PathBuf};
 use std::env;
 
-mod looper;
-mod traits;
-mod midi;
+use dimooper::{looper, engine, traits, midi, hardcode, measure, error};
+
 mod graphics_primitives;
-mod hardcode;
-mod measure;
 mod ui;
 mod screen;
+mod view;
 mod config;
-mod error;
 mod path;
 mod fundamental;
 
@@ -35,6 +34,7 @@ use midi::PortMidiNoteTracker;
 use pm::PortMidiDeviceId as DeviceId;
 use ui::Popup;
 use screen::*;
+use engine::Engine;
 use config::Config;
 use hardcode::*;
 use error::{Result, OrExit};
@@ -47,23 +47,27 @@ fn config_path() -> Result<PathBuf> {
 
 type Looper = looper::Looper<PortMidiNoteTracker>;
 
-fn create_looper(context: &pm::PortMidi, output_id: DeviceId) -> Result<Looper> {
+fn create_looper(context: &pm::PortMidi, output_id: DeviceId, config: &Config) -> Result<Looper> {
     let out_info = try!(context.device(output_id));
     println!("Sending recorded events: {} {}",
              out_info.id(),
              out_info.name());
     let out_port = try!(context.output_port(out_info, 1024));
-    let looper = looper::Looper::new(PortMidiNoteTracker::new(out_port));
+    let looper = looper::Looper::new(PortMidiNoteTracker::new(out_port),
+                                     config.metronome.clone());
     Ok(looper)
 }
 
-fn create_event_loop(context: &pm::PortMidi, input_id: DeviceId) -> Result<EventLoop<'static>> {
-    let window_width = RATIO_WIDTH  * RATIO_FACTOR;
-    let window_height = RATIO_HEIGHT * RATIO_FACTOR;
-
+fn create_input_port(context: &pm::PortMidi, input_id: DeviceId) -> Result<pm::InputPort> {
     let in_info = try!(context.device(input_id));
     println!("Listening on: {} {}", in_info.id(), in_info.name());
     let in_port = try!(context.input_port(in_info, 1024));
+    Ok(in_port)
+}
+
+fn create_event_loop(in_port: Option<pm::InputPort>) -> Result<EventLoop<'static>> {
+    let window_width = RATIO_WIDTH  * RATIO_FACTOR;
+    let window_height = RATIO_HEIGHT * RATIO_FACTOR;
 
     let sdl_context = try!(sdl2::init());
     let video_subsystem = try!(sdl_context.video());
@@ -142,16 +146,24 @@ fn main() {
         .map_err(|err| { println!("[WARNING] Cannot load config: {}. Using default config.", err); err })
         .unwrap_or_default();
 
-    let mut event_loop = create_event_loop(&context, input_id)
+    let in_port = create_input_port(&context, input_id)
         .or_exit("Initialization error");
     match mode {
         "looper" => {
             let bpm_popup = create_popup(&ttf_context).or_exit("Unable to create popup");
-            let looper = create_looper(&context, output_id)
+            let looper = create_looper(&context, output_id, &config)
                 .or_exit("Looper initialization error");
-            event_loop.run(LooperScreen::<PortMidiNoteTracker>::new(looper, bpm_popup, &config))
+            // The engine thread reads the MIDI input, so the event
+            // loop does not get the port
+            let engine = Engine::spawn(looper, LooperController::new(&config), Some(in_port))
+                .or_exit("Engine initialization error");
+            let mut event_loop = create_event_loop(None)
+                .or_exit("Initialization error");
+            config = event_loop.run(LooperScreen::<PortMidiNoteTracker>::new(engine, bpm_popup, &config));
         },
         "keyboard" => {
+            let mut event_loop = create_event_loop(Some(in_port))
+                .or_exit("Initialization error");
             config = context
                 .device(output_id)
                 .and_then(|out_info| context.output_port(out_info, 1024))
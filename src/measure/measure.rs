@@ -1,27 +1,103 @@
 use midi::AbsMidiEvent;
-use measure::{Quant, QuantMidiEvent};
+use measure::{Quant, QuantMidiEvent, MusicalPosition, NoteCondition};
+use hardcode::*;
+
+const FOUR_MINUTES_MILLIS: u64 = 240000;
+
+fn default_beat_unit() -> u32 {
+    DEFAULT_BEAT_UNIT
+}
+
+fn default_beat_division() -> u32 {
+    DEFAULT_BEAT_DIVISION
+}
+
+/// Groups the beats the way they are usually felt: every beat of a
+/// simple meter on its own, compound meters by three and odd ones by
+/// two with the last group of three (7/8 is 2 + 2 + 3).
+fn default_beat_grouping(beats: u32, beat_unit: u32) -> Vec<u32> {
+    if beat_unit < 8 {
+        vec![1; beats as usize]
+    } else if beats % 3 == 0 {
+        vec![3; (beats / 3) as usize]
+    } else if beats < 4 {
+        vec![beats]
+    } else {
+        let mut grouping = vec![2; (beats / 2) as usize];
+        if beats % 2 == 1 {
+            *grouping.last_mut().unwrap() = 3;
+        }
+        grouping
+    }
+}
 
 // FIXME(#142): measure should have only converters
 // make all of the fields private
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Measure {
+    /// Quarter notes per minute regardless of the time signature
     pub tempo_bpm: u32,
+    /// Numerator of the time signature
     pub measure_size_bpm: u32,
-    pub quantation_level: u32,
+    /// Denominator of the time signature
+    #[serde(default = "default_beat_unit")]
+    pub beat_unit: u32,
+    /// Sizes of the groups the beats of the measure are split into,
+    /// e.g. `[3, 2, 2]` for 7/8. Empty grouping is derived from the
+    /// time signature.
+    #[serde(default)]
+    pub beat_grouping: Vec<u32>,
+    /// Amount of steps of the recording grid per beat, e.g. 4 for
+    /// sixteenths or 3 for eighth triplets in 4/4. The beat is always
+    /// split into `QUANTS_PER_BEAT` quants, so the grid can be changed
+    /// without touching the recorded samples.
+    #[serde(default = "default_beat_division")]
+    pub beat_division: u32,
 }
 
 impl Measure {
-    /// Snaps the timestamp to the closest quant
+    /// Snaps the timestamp to the closest step of the recording grid
     pub fn snap_timestamp_to_quant(&self, timestamp: u32) -> Quant {
-        Quant((timestamp + self.quant_size_millis() / 2) / self.quant_size_millis())
+        let steps = timestamp as u64 * self.beat_division() as u64 * self.beats_per_four_minutes();
+        let step = (2 * steps + FOUR_MINUTES_MILLIS) / (2 * FOUR_MINUTES_MILLIS);
+        Quant(step as u32 * self.grid_size_quants())
     }
 
-    pub fn timestamp_to_quant(&self, timestamp: u32) -> Quant {
-        Quant(timestamp / self.quant_size_millis())
+    pub fn timestamp_to_tick(&self, timestamp: u32) -> u32 {
+        (timestamp as u64 * TICKS_PER_BEAT as u64 * self.beats_per_four_minutes() / FOUR_MINUTES_MILLIS) as u32
     }
 
-    pub fn timestamp_to_measure(&self, timestamp: u32) -> u32 {
-        timestamp / self.measure_size_millis()
+    /// Converts the passed time into ticks. The fractions of the ticks
+    /// are accumulated in `remainder`, so the position advanced by
+    /// small steps does not drift from the wall time.
+    pub fn millis_to_ticks(&self, millis: u32, remainder: &mut u64) -> u32 {
+        let scaled_ticks = *remainder + millis as u64 * TICKS_PER_BEAT as u64 * self.beats_per_four_minutes();
+        *remainder = scaled_ticks % FOUR_MINUTES_MILLIS;
+        (scaled_ticks / FOUR_MINUTES_MILLIS) as u32
+    }
+
+    /// The tempo is measured in quarter notes, so the amount of beats
+    /// per minute is an integer only for four minutes
+    fn beats_per_four_minutes(&self) -> u64 {
+        (self.tempo_bpm * self.beat_unit) as u64
+    }
+
+    pub fn ticks_per_measure(&self) -> u32 {
+        self.measure_size_bpm * TICKS_PER_BEAT
+    }
+
+    /// MIDI clock is sent at the fixed rate per quarter note, so its
+    /// period in ticks depends on the beat unit
+    pub fn ticks_per_midi_clock(&self) -> u32 {
+        TICKS_PER_BEAT * self.beat_unit / (4 * MIDI_CLOCKS_PER_QUARTER_NOTE)
+    }
+
+    pub fn musical_position(&self, tick: u32) -> MusicalPosition {
+        MusicalPosition {
+            bar: tick / self.ticks_per_measure(),
+            beat: tick % self.ticks_per_measure() / TICKS_PER_BEAT,
+            tick: tick % TICKS_PER_BEAT,
+        }
     }
 
     pub fn amount_of_measures_in_buffer(&self, buffer: &[AbsMidiEvent]) -> u32 {
@@ -36,12 +112,19 @@ impl Measure {
 
     pub fn quantize_buffer(&self, buffer: &[AbsMidiEvent]) -> Vec<QuantMidiEvent> {
         let amount_of_measures = self.amount_of_measures_in_buffer(buffer);
-        let quants_per_sample = Quant(amount_of_measures) * self.quants_per_measure();
+        self.quantize_buffer_to_length(buffer, Quant(amount_of_measures) * self.quants_per_measure())
+    }
 
+    /// Quantizes the buffer of a sample lasting the given amount of
+    /// quants. The events past the end wrap around to the beginning.
+    pub fn quantize_buffer_to_length(&self, buffer: &[AbsMidiEvent], quants_per_sample: Quant) -> Vec<QuantMidiEvent> {
         buffer.iter().map(|event| {
+            let quant = self.snap_timestamp_to_quant(event.timestamp);
             QuantMidiEvent {
                 message: event.message,
-                quant: self.snap_timestamp_to_quant(event.timestamp) % quants_per_sample,
+                quant: quant % quants_per_sample,
+                offset: self.timestamp_to_tick(event.timestamp) as i32 - (quant.as_u32() * TICKS_PER_QUANT) as i32,
+                condition: NoteCondition::Always,
             }
         }).collect()
     }
@@ -55,122 +138,229 @@ impl Measure {
     // FIXME(#142): measure should have only converters
     // Get rid of this or make private
     pub fn beat_size_millis(&self) -> u32 {
-        (60000.0 / self.tempo_bpm as f32) as u32
+        (FOUR_MINUTES_MILLIS / self.beats_per_four_minutes()) as u32
+    }
+
+    pub fn beat_grouping(&self) -> Vec<u32> {
+        let grouped_beats: u32 = self.beat_grouping.iter().sum();
+
+        if !self.beat_grouping.is_empty() && grouped_beats == self.measure_size_bpm {
+            self.beat_grouping.clone()
+        } else {
+            default_beat_grouping(self.measure_size_bpm, self.beat_unit)
+        }
+    }
+
+    /// Checks whether the beat of the measure starts a group of beats.
+    /// The first beat of the measure always does.
+    pub fn is_group_start(&self, beat: u32) -> bool {
+        let beat = beat % self.measure_size_bpm;
+        let mut group_start = 0;
+
+        for group_size in self.beat_grouping() {
+            if group_start == beat {
+                return true;
+            }
+            group_start += group_size;
+        }
+
+        false
     }
 
     // FIXME(#142): measure should have only converters
     // Get rid of this or make private
     pub fn quants_per_measure(&self) -> Quant {
-        Quant(self.measure_size_bpm.pow(self.quantation_level))
+        Quant(self.measure_size_bpm * QUANTS_PER_BEAT)
+    }
+
+    /// The beat division if the quants of the beat can be split into
+    /// it evenly, otherwise the default one
+    pub fn beat_division(&self) -> u32 {
+        if self.beat_division > 0 && QUANTS_PER_BEAT % self.beat_division == 0 {
+            self.beat_division
+        } else {
+            DEFAULT_BEAT_DIVISION
+        }
     }
 
-    pub fn quant_size_millis(&self) -> u32 {
-        let mut result = self.measure_size_millis() as f32;
+    /// Length of the sample recorded into the buffer in the whole
+    /// amount of `unit`, e.g. beats. Like with the measures the unit
+    /// the last event falls into is included.
+    pub fn length_of_buffer(&self, buffer: &[AbsMidiEvent], Quant(unit): Quant) -> Quant {
+        let n = buffer.len();
 
-        for _ in 0..self.quantation_level {
-            result /= self.measure_size_bpm as f32
+        if n > 0 {
+            let ticks = self.timestamp_to_tick(buffer[n - 1].timestamp - buffer[0].timestamp);
+            Quant((ticks / (unit * TICKS_PER_QUANT) + 1) * unit)
+        } else {
+            Quant(unit)
         }
+    }
 
-        result as u32
+    pub fn grid_size_quants(&self) -> u32 {
+        QUANTS_PER_BEAT / self.beat_division()
     }
 
-    pub fn scale_time_cursor(&self, new_measure: &Measure, amount_of_measures: u32, time_cursor: u32) -> u32 {
-        let s0 = (amount_of_measures * self.measure_size_millis()) as f32;
-        let s1 = (amount_of_measures * new_measure.measure_size_millis()) as f32;
-        (time_cursor as f32 / s0 * s1) as u32
+    pub fn grid_size_millis(&self) -> u32 {
+        self.beat_size_millis() / self.beat_division()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Measure};
-    use measure::{Quant, QuantMidiEvent};
+    use measure::{Quant, QuantMidiEvent, MusicalPosition, NoteCondition};
     use midi::{TypedMidiMessage, AbsMidiEvent};
+    use hardcode::{QUANTS_PER_BEAT, TICKS_PER_BEAT};
 
     const TEMPO_BPM: u32 = 120;
     const MEASURE_SIZE_BPM: u32 = 4;
-    const QUANTATION_LEVEL: u32 = 2;
+    const BEAT_UNIT: u32 = 4;
+    const BEAT_DIVISION: u32 = 4;
 
     const MEASURE_SIZE_MILLIS: u32 =  2000;
     const BEAT_SIZE_MILLIS: u32 =  500;
-    const QUANT_SIZE_MILLIS: u32 =  125;
+    const GRID_SIZE_MILLIS: u32 =  125;
+    const GRID_SIZE_QUANTS: u32 = 6;
+
+    fn measure() -> Measure {
+        Measure {
+            tempo_bpm: TEMPO_BPM,
+            measure_size_bpm: MEASURE_SIZE_BPM,
+            beat_unit: BEAT_UNIT,
+            beat_grouping: Vec::new(),
+            beat_division: BEAT_DIVISION,
+        }
+    }
 
-    const MEASURE: Measure = Measure {
-        tempo_bpm: TEMPO_BPM,
-        measure_size_bpm: MEASURE_SIZE_BPM,
-        quantation_level: QUANTATION_LEVEL,
-    };
+    fn time_signature(measure_size_bpm: u32, beat_unit: u32) -> Measure {
+        Measure {
+            measure_size_bpm: measure_size_bpm,
+            beat_unit: beat_unit,
+            .. measure()
+        }
+    }
 
     #[test]
     fn test_measure_new() {
-        assert_eq!(TEMPO_BPM, MEASURE.tempo_bpm);
-        assert_eq!(MEASURE_SIZE_BPM, MEASURE.measure_size_bpm);
-        assert_eq!(QUANTATION_LEVEL, MEASURE.quantation_level);
+        assert_eq!(TEMPO_BPM, measure().tempo_bpm);
+        assert_eq!(MEASURE_SIZE_BPM, measure().measure_size_bpm);
+        assert_eq!(BEAT_DIVISION, measure().beat_division);
 
-        assert_eq!(MEASURE_SIZE_MILLIS, MEASURE.measure_size_millis());
-        assert_eq!(BEAT_SIZE_MILLIS, MEASURE.beat_size_millis());
-        assert_eq!(QUANT_SIZE_MILLIS, MEASURE.quant_size_millis());
+        assert_eq!(MEASURE_SIZE_MILLIS, measure().measure_size_millis());
+        assert_eq!(BEAT_SIZE_MILLIS, measure().beat_size_millis());
+        assert_eq!(GRID_SIZE_MILLIS, measure().grid_size_millis());
     }
 
     #[test]
     fn test_measure_update() {
-        assert_eq!(MEASURE_SIZE_MILLIS, MEASURE.measure_size_millis());
-        assert_eq!(BEAT_SIZE_MILLIS, MEASURE.beat_size_millis());
-        assert_eq!(QUANT_SIZE_MILLIS, MEASURE.quant_size_millis());
+        assert_eq!(MEASURE_SIZE_MILLIS, measure().measure_size_millis());
+        assert_eq!(BEAT_SIZE_MILLIS, measure().beat_size_millis());
+        assert_eq!(GRID_SIZE_MILLIS, measure().grid_size_millis());
 
-        let updated_measure = Measure { tempo_bpm: TEMPO_BPM + 40, .. MEASURE };
+        let updated_measure = Measure { tempo_bpm: TEMPO_BPM + 40, .. measure() };
 
         assert_eq!(1500, updated_measure.measure_size_millis());
         assert_eq!(375, updated_measure.beat_size_millis());
-        assert_eq!(93, updated_measure.quant_size_millis());
+        assert_eq!(93, updated_measure.grid_size_millis());
     }
 
     #[test]
     fn test_snap_timestamp_to_quant() {
-        assert_eq!(Quant(0), MEASURE.snap_timestamp_to_quant(0));
-        assert_eq!(Quant(1), MEASURE.snap_timestamp_to_quant(MEASURE.quant_size_millis()));
-        assert_eq!(Quant(0), MEASURE.snap_timestamp_to_quant(MEASURE.quant_size_millis() / 2 - 1));
-        assert_eq!(Quant(1), MEASURE.snap_timestamp_to_quant(MEASURE.quant_size_millis() / 2 + 1));
+        assert_eq!(Quant(0), measure().snap_timestamp_to_quant(0));
+        assert_eq!(Quant(GRID_SIZE_QUANTS), measure().snap_timestamp_to_quant(measure().grid_size_millis()));
+        assert_eq!(Quant(0), measure().snap_timestamp_to_quant(measure().grid_size_millis() / 2 - 1));
+        assert_eq!(Quant(GRID_SIZE_QUANTS), measure().snap_timestamp_to_quant(measure().grid_size_millis() / 2 + 1));
     }
 
     #[test]
-    fn test_timestamp_to_quant() {
-        assert_eq!(Quant(0), MEASURE.timestamp_to_quant(0));
-        assert_eq!(Quant(1), MEASURE.timestamp_to_quant(MEASURE.quant_size_millis()));
-        assert_eq!(Quant(0), MEASURE.timestamp_to_quant(MEASURE.quant_size_millis() / 2 - 1));
-        assert_eq!(Quant(0), MEASURE.timestamp_to_quant(MEASURE.quant_size_millis() / 2 + 1));
+    fn test_snap_timestamp_to_triplet_grid() {
+        let triplets = Measure { beat_division: 3, .. measure() };
+
+        assert_eq!(Quant(8), triplets.snap_timestamp_to_quant(triplets.grid_size_millis()));
+        assert_eq!(Quant(16), triplets.snap_timestamp_to_quant(2 * BEAT_SIZE_MILLIS / 3));
+        assert_eq!(Quant(QUANTS_PER_BEAT), triplets.snap_timestamp_to_quant(BEAT_SIZE_MILLIS - 10));
     }
 
     #[test]
-    fn test_timestamp_to_measure() {
-        assert_eq!(0, MEASURE.timestamp_to_measure(0));
-        assert_eq!(1, MEASURE.timestamp_to_measure(MEASURE.measure_size_millis()));
-        assert_eq!(0, MEASURE.timestamp_to_measure(MEASURE.measure_size_millis() - 1));
+    fn test_unsupported_beat_division() {
+        let quintuplets = Measure { beat_division: 5, .. measure() };
+        assert_eq!(BEAT_DIVISION, quintuplets.beat_division());
+    }
+
+    #[test]
+    fn test_timestamp_to_tick() {
+        assert_eq!(0, measure().timestamp_to_tick(0));
+        assert_eq!(TICKS_PER_BEAT, measure().timestamp_to_tick(BEAT_SIZE_MILLIS));
+        assert_eq!(TICKS_PER_BEAT / 2, measure().timestamp_to_tick(BEAT_SIZE_MILLIS / 2));
+        assert_eq!(measure().ticks_per_measure(), measure().timestamp_to_tick(MEASURE_SIZE_MILLIS));
     }
 
     #[test]
     fn test_quants_per_measure() {
-        assert_eq!(Quant(16), MEASURE.quants_per_measure());
+        assert_eq!(Quant(96), measure().quants_per_measure());
+    }
+
+    #[test]
+    fn test_odd_and_compound_quants_per_measure() {
+        assert_eq!(Quant(72), time_signature(3, 4).quants_per_measure());
+        assert_eq!(Quant(144), time_signature(6, 8).quants_per_measure());
+        assert_eq!(Quant(168), time_signature(7, 8).quants_per_measure());
+        assert_eq!(250, time_signature(7, 8).beat_size_millis());
+        assert_eq!(1750, time_signature(7, 8).measure_size_millis());
+    }
+
+    #[test]
+    fn test_ticks_per_midi_clock() {
+        assert_eq!(TICKS_PER_BEAT / 24, measure().ticks_per_midi_clock());
+        assert_eq!(TICKS_PER_BEAT / 12, time_signature(6, 8).ticks_per_midi_clock());
     }
 
     #[test]
-    fn test_scale_time_cursor() {
-        let amount_of_measures = 2;
-        let time_cursor = MEASURE.measure_size_millis();
+    fn test_beat_grouping() {
+        assert_eq!(vec![1, 1, 1, 1], measure().beat_grouping());
+        assert_eq!(vec![3, 3], time_signature(6, 8).beat_grouping());
+        assert_eq!(vec![2, 2, 3], time_signature(7, 8).beat_grouping());
+
+        let custom = Measure { beat_grouping: vec![3, 2, 2], .. time_signature(7, 8) };
+        assert_eq!(vec![3, 2, 2], custom.beat_grouping());
+        assert!(custom.is_group_start(0));
+        assert!(custom.is_group_start(3));
+        assert!(custom.is_group_start(5));
+        assert!(!custom.is_group_start(4));
+
+        let invalid = Measure { beat_grouping: vec![3, 3], .. time_signature(7, 8) };
+        assert_eq!(vec![2, 2, 3], invalid.beat_grouping());
+    }
 
-        let new_measure = Measure { tempo_bpm: TEMPO_BPM + 45, .. MEASURE };
+    #[test]
+    fn test_millis_to_ticks_does_not_drift() {
+        // A beat at 130 BPM lasts 461.5 ms
+        let measure = Measure { tempo_bpm: 130, .. measure() };
+        let mut remainder = 0;
+        let mut ticks = 0;
+
+        for _ in 0..60000 / 3 {
+            ticks += measure.millis_to_ticks(3, &mut remainder);
+        }
 
-        assert_eq!(new_measure.measure_size_millis(),
-                   MEASURE.scale_time_cursor(&new_measure,
-                                             amount_of_measures,
-                                             time_cursor))
+        assert_eq!(130 * TICKS_PER_BEAT, ticks);
+    }
+
+    #[test]
+    fn test_musical_position() {
+        let position = time_signature(7, 8).musical_position(2 * 7 * TICKS_PER_BEAT + 3 * TICKS_PER_BEAT + 5);
+
+        assert_eq!(MusicalPosition { bar: 2, beat: 3, tick: 5 }, position);
+        assert!(!position.is_beat_start());
+        assert!(time_signature(7, 8).musical_position(7 * TICKS_PER_BEAT).is_bar_start());
     }
 
     #[test]
     fn test_quantize_buffer() {
         let buffer = &[
             AbsMidiEvent {
-                timestamp: MEASURE.measure_size_millis() - 1,
+                timestamp: measure().measure_size_millis() - 1,
                 message: TypedMidiMessage::NoteOn {
                     channel: 0,
                     key: 0,
@@ -179,11 +369,38 @@ mod tests {
             }
         ];
 
-        let quantized_buffer = MEASURE.quantize_buffer(buffer);
+        let quantized_buffer = measure().quantize_buffer(buffer);
 
         assert_eq!(vec![QuantMidiEvent {
             message: TypedMidiMessage::NoteOn { channel: 0, key: 0, velocity: 0 },
             quant: Quant(0),
+            offset: -1,
+            condition: NoteCondition::Always,
         }], quantized_buffer);
     }
+
+    #[test]
+    fn test_quantize_buffer_to_beats() {
+        let buffer = &[
+            AbsMidiEvent {
+                timestamp: 0,
+                message: TypedMidiMessage::NoteOn { channel: 0, key: 0, velocity: 0 },
+            },
+            AbsMidiEvent {
+                timestamp: 2 * BEAT_SIZE_MILLIS + GRID_SIZE_MILLIS,
+                message: TypedMidiMessage::NoteOff { channel: 0, key: 0, velocity: 0 },
+            },
+            AbsMidiEvent {
+                timestamp: 3 * BEAT_SIZE_MILLIS + GRID_SIZE_MILLIS,
+                message: TypedMidiMessage::NoteOn { channel: 0, key: 0, velocity: 0 },
+            }
+        ];
+        let three_beats = measure().length_of_buffer(&buffer[..2], Quant(QUANTS_PER_BEAT));
+        assert_eq!(Quant(3 * QUANTS_PER_BEAT), three_beats);
+        assert_eq!(Quant(QUANTS_PER_BEAT), measure().length_of_buffer(&[], Quant(QUANTS_PER_BEAT)));
+
+        // The event after the third beat wraps around
+        let quantized_buffer = measure().quantize_buffer_to_length(buffer, three_beats);
+        assert_eq!(Quant(GRID_SIZE_QUANTS), quantized_buffer[2].quant);
+    }
 }
@@ -1,7 +1,13 @@
 mod measure;
 mod quant;
 mod quant_midi_event;
+mod tap_tempo;
+mod musical_position;
+mod note_condition;
 
 pub use self::quant::Quant;
 pub use self::quant_midi_event::QuantMidiEvent;
 pub use self::measure::Measure;
+pub use self::tap_tempo::TapTempo;
+pub use self::musical_position::MusicalPosition;
+pub use self::note_condition::NoteCondition;
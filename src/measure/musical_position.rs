@@ -0,0 +1,19 @@
+/// Position of the looper in bars, beats of the bar and ticks of the
+/// beat. Unlike the time in milliseconds it does not depend on the
+/// tempo.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MusicalPosition {
+    pub bar: u32,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+impl MusicalPosition {
+    pub fn is_beat_start(&self) -> bool {
+        self.tick == 0
+    }
+
+    pub fn is_bar_start(&self) -> bool {
+        self.beat == 0 && self.is_beat_start()
+    }
+}
@@ -0,0 +1,77 @@
+use rand::Rng;
+
+/// Decides whether the event of a sample is played on the current
+/// loop cycle of the sample
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoteCondition {
+    Always,
+    /// Played with the probability in percent
+    Probability(u32),
+    /// Played on the `cycle`th loop of every `period` loops, counting
+    /// from one, e.g. the 4th of 4
+    Cycle { cycle: u32, period: u32 },
+}
+
+impl Default for NoteCondition {
+    fn default() -> NoteCondition {
+        NoteCondition::Always
+    }
+}
+
+impl NoteCondition {
+    pub fn is_always(&self) -> bool {
+        *self == NoteCondition::Always
+    }
+
+    /// Whether the event is played on the loop cycle `cycle` counted
+    /// from zero
+    pub fn is_met<R: Rng>(&self, cycle: u32, rng: &mut R) -> bool {
+        match *self {
+            NoteCondition::Always => true,
+            NoteCondition::Probability(percent) => rng.gen_range(0, 100) < percent,
+            NoteCondition::Cycle { cycle: condition_cycle, period } =>
+                period == 0 || cycle % period + 1 == condition_cycle,
+        }
+    }
+
+    /// Digits identifying the condition: the percent of the
+    /// probability, or the cycle followed by the period
+    pub fn number(&self) -> u32 {
+        match *self {
+            NoteCondition::Always => 100,
+            NoteCondition::Probability(percent) => percent,
+            NoteCondition::Cycle { cycle, period } => cycle * 10 + period,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{XorShiftRng, SeedableRng};
+
+    use super::NoteCondition;
+
+    #[test]
+    fn test_cycle() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let every_second = NoteCondition::Cycle { cycle: 2, period: 2 };
+        let fourth_of_four = NoteCondition::Cycle { cycle: 4, period: 4 };
+
+        let played = |condition: NoteCondition, rng: &mut XorShiftRng| -> Vec<u32> {
+            (0..8).filter(|cycle| condition.is_met(*cycle, rng)).collect()
+        };
+        assert_eq!(vec![1, 3, 5, 7], played(every_second, &mut rng));
+        assert_eq!(vec![3, 7], played(fourth_of_four, &mut rng));
+    }
+
+    #[test]
+    fn test_probability() {
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        assert!((0..100).all(|cycle| NoteCondition::Probability(100).is_met(cycle, &mut rng)));
+        assert!((0..100).all(|cycle| !NoteCondition::Probability(0).is_met(cycle, &mut rng)));
+
+        let played = (0..1000).filter(|cycle| NoteCondition::Probability(50).is_met(*cycle, &mut rng)).count();
+        assert!(400 < played && played < 600);
+    }
+}
@@ -1,8 +1,40 @@
 use midi::TypedMidiMessage;
-use measure::Quant;
+use measure::{Quant, NoteCondition};
+use hardcode::TICKS_PER_QUANT;
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
 pub struct QuantMidiEvent {
     pub message: TypedMidiMessage,
     pub quant: Quant,
+    /// Distance in ticks from the quant to the moment the event
+    /// actually happened
+    #[serde(default)]
+    pub offset: i32,
+    #[serde(default, skip_serializing_if = "NoteCondition::is_always")]
+    pub condition: NoteCondition,
+}
+
+impl QuantMidiEvent {
+    /// The event placed exactly at the given tick
+    pub fn at_tick(message: TypedMidiMessage, tick: u32) -> QuantMidiEvent {
+        QuantMidiEvent {
+            message: message,
+            quant: Quant(tick / TICKS_PER_QUANT),
+            offset: (tick % TICKS_PER_QUANT) as i32,
+            condition: NoteCondition::Always,
+        }
+    }
+
+    /// The same event placed exactly at the given tick
+    pub fn moved_to_tick(&self, tick: u32) -> QuantMidiEvent {
+        QuantMidiEvent {
+            condition: self.condition,
+            .. QuantMidiEvent::at_tick(self.message, tick)
+        }
+    }
+
+    /// Position of the event in ticks including its offset
+    pub fn tick(&self) -> i64 {
+        self.quant.as_u32() as i64 * TICKS_PER_QUANT as i64 + self.offset as i64
+    }
 }
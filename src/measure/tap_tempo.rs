@@ -0,0 +1,100 @@
+use hardcode::*;
+
+/// Guesses the beat size from the moments the user taps the beats
+pub struct TapTempo {
+    taps: Vec<u32>,
+}
+
+impl TapTempo {
+    pub fn new() -> TapTempo {
+        TapTempo {
+            taps: Vec::new(),
+        }
+    }
+
+    /// Registers the tap and returns the average interval between the
+    /// recent taps. The intervals too far from the median one are not
+    /// taken into account, so a single missed or doubled tap does not
+    /// spoil the result. The taps read at the same moment as the last
+    /// one are ignored.
+    pub fn tap(&mut self, timestamp: u32) -> Option<u32> {
+        if let Some(&last_tap) = self.taps.last() {
+            if timestamp == last_tap {
+                return None;
+            }
+            if timestamp < last_tap || timestamp - last_tap > TAP_TEMPO_TIMEOUT {
+                self.taps.clear();
+            }
+        }
+
+        self.taps.push(timestamp);
+        if self.taps.len() > TAP_TEMPO_MAX_TAPS {
+            self.taps.remove(0);
+        }
+
+        let mut intervals: Vec<u32> = self.taps.windows(2).map(|taps| taps[1] - taps[0]).collect();
+        if intervals.is_empty() {
+            return None;
+        }
+
+        let median = {
+            let mut sorted_intervals = intervals.clone();
+            sorted_intervals.sort();
+            sorted_intervals[sorted_intervals.len() / 2]
+        };
+        intervals.retain(|&interval| {
+            let deviation = if interval > median { interval - median } else { median - interval };
+            deviation * 100 <= median * TAP_TEMPO_TOLERANCE_PERCENT
+        });
+
+        Some(intervals.iter().sum::<u32>() / intervals.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TapTempo;
+    use hardcode::TAP_TEMPO_TIMEOUT;
+
+    #[test]
+    fn test_tap_tempo_average() {
+        let mut tap_tempo = TapTempo::new();
+
+        assert_eq!(None, tap_tempo.tap(1000));
+        assert_eq!(Some(500), tap_tempo.tap(1500));
+        assert_eq!(Some(505), tap_tempo.tap(2010));
+    }
+
+    #[test]
+    fn test_tap_tempo_ignores_simultaneous_taps() {
+        let mut tap_tempo = TapTempo::new();
+
+        assert_eq!(None, tap_tempo.tap(1000));
+        assert_eq!(None, tap_tempo.tap(1000));
+        assert_eq!(Some(500), tap_tempo.tap(1500));
+        assert_eq!(None, tap_tempo.tap(1500));
+        assert_eq!(Some(500), tap_tempo.tap(2000));
+    }
+
+    #[test]
+    fn test_tap_tempo_rejects_outliers() {
+        let mut tap_tempo = TapTempo::new();
+
+        for &timestamp in &[0, 500, 1000, 1500, 1700, 2200] {
+            tap_tempo.tap(timestamp);
+        }
+
+        assert_eq!(Some(500), tap_tempo.tap(2700));
+    }
+
+    #[test]
+    fn test_tap_tempo_timeout() {
+        let mut tap_tempo = TapTempo::new();
+
+        tap_tempo.tap(0);
+        tap_tempo.tap(500);
+
+        assert_eq!(None, tap_tempo.tap(500 + TAP_TEMPO_TIMEOUT + 1));
+        assert_eq!(Some(400), tap_tempo.tap(900 + TAP_TEMPO_TIMEOUT + 1));
+    }
+}
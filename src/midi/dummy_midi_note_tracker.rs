@@ -6,6 +6,10 @@ pub struct DummyMidiNoteTracker;
 
 impl MidiNoteTracker for DummyMidiNoteTracker {
     fn close_opened_notes(&mut self) {}
+
+    fn feed_untracked(&mut self, _: TypedMidiMessage) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl MidiSink for DummyMidiNoteTracker {
@@ -1,5 +1,13 @@
 use midi::MidiSink;
 
+use pm::types::Result;
+use midi::TypedMidiMessage;
+
 pub trait MidiNoteTracker : MidiSink {
     fn close_opened_notes(&mut self);
+
+    /// Sends the message without remembering the notes it opens, so
+    /// they are never closed by `close_opened_notes`. Used for the
+    /// feedback to the controller, like the LEDs of the pads.
+    fn feed_untracked(&mut self, midi_message: TypedMidiMessage) -> Result<()>;
 }
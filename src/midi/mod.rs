@@ -1,13 +1,8 @@
-use std::cmp;
-
 use pm::types::MidiMessage;
 use pm::types::MidiEvent;
 
-use sdl2::render::Renderer;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-
-use measure::{Quant, QuantMidiEvent};
+use measure::{Quant, QuantMidiEvent, NoteCondition};
+use hardcode::TICKS_PER_QUANT;
 
 mod port_midi_note_tracker;
 mod dummy_midi_note_tracker;
@@ -21,13 +16,22 @@ pub use self::dummy_midi_note_tracker::DummyMidiNoteTracker;
 
 const NOTE_ON_STATUS: u8 = 0b10010000;
 const NOTE_OFF_STATUS: u8 = 0b10000000;
+const POLY_AFTERTOUCH_STATUS: u8 = 0b10100000;
 const CONTROL_CHANGE_STATUS: u8 = 0b10110000;
+const CHANNEL_AFTERTOUCH_STATUS: u8 = 0b11010000;
+const PITCH_BEND_STATUS: u8 = 0b11100000;
+const TIMING_CLOCK_STATUS: u8 = 0b11111000;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize, Serialize)]
 pub enum TypedMidiMessage {
     NoteOn {channel: u8, key: u8, velocity: u8},
     NoteOff {channel: u8, key: u8, velocity: u8},
     ControlChange {channel: u8, number: u8, value: u8},
+    PolyAftertouch {channel: u8, key: u8, pressure: u8},
+    ChannelAftertouch {channel: u8, pressure: u8},
+    /// The 14-bit value is centered at `PITCH_BEND_CENTER`
+    PitchBend {channel: u8, value: u16},
+    TimingClock,
 }
 
 impl Into<MidiMessage> for TypedMidiMessage {
@@ -52,11 +56,84 @@ impl Into<MidiMessage> for TypedMidiMessage {
                     status: CONTROL_CHANGE_STATUS | channel,
                     data1: number,
                     data2: value,
+                },
+
+            TypedMidiMessage::PolyAftertouch {channel, key, pressure} =>
+                MidiMessage {
+                    status: POLY_AFTERTOUCH_STATUS | channel,
+                    data1: key,
+                    data2: pressure,
+                },
+
+            TypedMidiMessage::ChannelAftertouch {channel, pressure} =>
+                MidiMessage {
+                    status: CHANNEL_AFTERTOUCH_STATUS | channel,
+                    data1: pressure,
+                    data2: 0,
+                },
+
+            TypedMidiMessage::PitchBend {channel, value} =>
+                MidiMessage {
+                    status: PITCH_BEND_STATUS | channel,
+                    data1: (value & 0x7F) as u8,
+                    data2: (value >> 7) as u8,
+                },
+
+            TypedMidiMessage::TimingClock =>
+                MidiMessage {
+                    status: TIMING_CLOCK_STATUS,
+                    data1: 0,
+                    data2: 0,
                 }
         }
     }
 }
 
+impl TypedMidiMessage {
+    pub fn channel(&self) -> Option<u8> {
+        match *self {
+            TypedMidiMessage::NoteOn { channel, .. } |
+            TypedMidiMessage::NoteOff { channel, .. } |
+            TypedMidiMessage::ControlChange { channel, .. } |
+            TypedMidiMessage::PolyAftertouch { channel, .. } |
+            TypedMidiMessage::ChannelAftertouch { channel, .. } |
+            TypedMidiMessage::PitchBend { channel, .. } => Some(channel),
+            TypedMidiMessage::TimingClock => None,
+        }
+    }
+
+    /// NoteOn without velocity, which many devices send when the key
+    /// is released, turned into NoteOff. The other messages are left
+    /// as they are.
+    pub fn normalized(self) -> TypedMidiMessage {
+        match self {
+            TypedMidiMessage::NoteOn { channel, key, velocity: 0 } =>
+                TypedMidiMessage::NoteOff { channel: channel, key: key, velocity: 0 },
+            message => message,
+        }
+    }
+
+    /// The same message sent on another channel. Channel-less
+    /// messages are left as they are.
+    pub fn with_channel(self, channel: u8) -> TypedMidiMessage {
+        match self {
+            TypedMidiMessage::NoteOn { key, velocity, .. } =>
+                TypedMidiMessage::NoteOn { channel: channel, key: key, velocity: velocity },
+            TypedMidiMessage::NoteOff { key, velocity, .. } =>
+                TypedMidiMessage::NoteOff { channel: channel, key: key, velocity: velocity },
+            TypedMidiMessage::ControlChange { number, value, .. } =>
+                TypedMidiMessage::ControlChange { channel: channel, number: number, value: value },
+            TypedMidiMessage::PolyAftertouch { key, pressure, .. } =>
+                TypedMidiMessage::PolyAftertouch { channel: channel, key: key, pressure: pressure },
+            TypedMidiMessage::ChannelAftertouch { pressure, .. } =>
+                TypedMidiMessage::ChannelAftertouch { channel: channel, pressure: pressure },
+            TypedMidiMessage::PitchBend { value, .. } =>
+                TypedMidiMessage::PitchBend { channel: channel, value: value },
+            TypedMidiMessage::TimingClock => TypedMidiMessage::TimingClock,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct AbsMidiEvent {
     pub message: TypedMidiMessage,
@@ -70,52 +147,7 @@ pub struct Note {
     pub key: u8,
     pub channel: u8,
     pub velocity: u8,
-}
-
-macro_rules! colors {
-    ($($hex:expr),*) => {
-        &[$(
-            Color::RGB((($hex & 0xFF0000) >> 16) as u8,
-                       (($hex & 0xFF00) >> 8) as u8,
-                       ($hex & 0xFF) as u8)
-        ),*]
-    }
-}
-
-const CHANNEL_PALETTE: &'static [Color; 5] = colors![0xF15A5A, 0xF0C419, 0x4EBA6F, 0x2D95BF,
-                                                     0x955BA5];
-
-fn multiply_color_vector(color: Color, factor: f32) -> Color {
-    match color {
-        Color::RGB(r, g, b) | Color::RGBA(r, g, b, _) => {
-            Color::RGB((r as f32 * factor) as u8,
-                       (g as f32 * factor) as u8,
-                       (b as f32 * factor) as u8)
-        }
-    }
-}
-
-impl Note {
-    pub fn render(&self, renderer: &mut Renderer, Quant(window_size): Quant, window_position: Quant) {
-        let window_width = renderer.viewport().width();
-        let window_height = renderer.viewport().height();
-        let row_height = window_height as f32 / 128.0;
-
-        let brightness_factor =  self.velocity as f32 / 127.0;
-        let base_color = CHANNEL_PALETTE[self.channel as usize % CHANNEL_PALETTE.len()];
-        let color = multiply_color_vector(base_color, brightness_factor);
-
-        let Quant(start) = self.start_quant - cmp::min(window_position, self.start_quant);
-        let Quant(end) = self.end_quant - cmp::min(window_position, self.end_quant);
-        let x1 = (start as f32 / window_size as f32 * (window_width as f32 - 10.0) + 5.0) as i32;
-        let x2 = (end as f32 / window_size as f32 * (window_width as f32 - 10.0) + 5.0) as i32;
-        let y = (row_height * (127 - self.key) as f32) as i32;
-
-        let note_rect = Rect::new(x1, y, (x2 - x1 + 1) as u32, row_height as u32);
-
-        renderer.set_draw_color(color);
-        renderer.fill_rect(note_rect).unwrap();
-    }
+    pub condition: NoteCondition,
 }
 
 pub fn parse_midi_event(raw_event: &MidiEvent) -> Option<AbsMidiEvent> {
@@ -146,6 +178,22 @@ pub fn parse_midi_message(raw_message: &MidiMessage) -> Option<TypedMidiMessage>
             value: raw_message.data2,
         }),
 
+        POLY_AFTERTOUCH_STATUS => Some(TypedMidiMessage::PolyAftertouch {
+            channel: get_note_channel(raw_message),
+            key: get_note_key(raw_message),
+            pressure: raw_message.data2,
+        }),
+
+        CHANNEL_AFTERTOUCH_STATUS => Some(TypedMidiMessage::ChannelAftertouch {
+            channel: get_note_channel(raw_message),
+            pressure: raw_message.data1,
+        }),
+
+        PITCH_BEND_STATUS => Some(TypedMidiMessage::PitchBend {
+            channel: get_note_channel(raw_message),
+            value: raw_message.data1 as u16 | (raw_message.data2 as u16) << 7,
+        }),
+
         _ => None,
     }
 }
@@ -164,6 +212,8 @@ pub fn events_to_notes(replay_buffer: &[QuantMidiEvent]) -> Vec<Note> {
                         result.push(note);
 
                         note.start_quant = event.quant;
+                        note.velocity = velocity;
+                        note.condition = event.condition;
                         note_tracker[channel as usize][key as usize] = Some(note);
                     }
                     None => note_tracker[channel as usize][key as usize] = Some(Note {
@@ -172,6 +222,7 @@ pub fn events_to_notes(replay_buffer: &[QuantMidiEvent]) -> Vec<Note> {
                         key: key,
                         channel: channel,
                         velocity: velocity,
+                        condition: event.condition,
                     }),
                 }
             },
@@ -191,6 +242,79 @@ pub fn events_to_notes(replay_buffer: &[QuantMidiEvent]) -> Vec<Note> {
     result
 }
 
+/// Reverses the buffer of a sample with the length of `length`
+/// quants. Notes are paired before reversing so the NoteOn of the
+/// reversed note is placed where the NoteOff used to be and keeps the
+/// original velocity and condition.
+pub fn reverse_events(replay_buffer: &[QuantMidiEvent], length: Quant) -> Vec<QuantMidiEvent> {
+    let length = length.as_u32() as i64 * TICKS_PER_QUANT as i64;
+    let mirror = |tick: i64| ((length - tick % length) % length + length) % length;
+
+    let mut note_tracker: [[Option<(i64, u8, NoteCondition)>; 128]; 16] = [[None; 128]; 16];
+    // The second element of the tuple orders events within the same
+    // tick: NoteOffs go first so they don't cut the notes starting
+    // there, except the NoteOffs of zero length notes.
+    let mut result: Vec<(i64, u8, QuantMidiEvent)> = Vec::new();
+
+    {
+        let mut push_note = |channel: u8, key: u8, (start, velocity, condition): (i64, u8, NoteCondition), end: i64| {
+            let note_on = QuantMidiEvent {
+                condition: condition,
+                .. QuantMidiEvent::at_tick(TypedMidiMessage::NoteOn { channel: channel, key: key, velocity: velocity },
+                                           mirror(end) as u32)
+            };
+            result.push((mirror(end), 1, note_on));
+            result.push((mirror(start),
+                         if start == end { 2 } else { 0 },
+                         QuantMidiEvent::at_tick(TypedMidiMessage::NoteOff { channel: channel, key: key, velocity: 0 },
+                                                 mirror(start) as u32)));
+        };
+
+        for event in replay_buffer {
+            match event.message {
+                TypedMidiMessage::NoteOn { channel, key, velocity } => {
+                    if let Some(note) = note_tracker[channel as usize][key as usize] {
+                        push_note(channel, key, note, event.tick());
+                    }
+                    note_tracker[channel as usize][key as usize] = Some((event.tick(), velocity, event.condition));
+                },
+
+                TypedMidiMessage::NoteOff { channel, key, .. } => {
+                    if let Some(note) = note_tracker[channel as usize][key as usize].take() {
+                        push_note(channel, key, note, event.tick());
+                    }
+                },
+
+                _ => (),
+            }
+        }
+
+        // Notes that were never released in the sample are held
+        // until its end
+        for channel in 0..16 {
+            for key in 0..128 {
+                if let Some(note) = note_tracker[channel][key] {
+                    push_note(channel as u8, key as u8, note, length);
+                }
+            }
+        }
+    }
+
+    for event in replay_buffer {
+        match event.message {
+            TypedMidiMessage::ControlChange { .. } |
+            TypedMidiMessage::PolyAftertouch { .. } |
+            TypedMidiMessage::ChannelAftertouch { .. } |
+            TypedMidiMessage::PitchBend { .. } =>
+                result.push((mirror(event.tick()), 1, event.moved_to_tick(mirror(event.tick()) as u32))),
+            _ => (),
+        }
+    }
+
+    result.sort_by_key(|&(tick, order, _)| (tick, order));
+    result.into_iter().map(|(_, _, event)| event).collect()
+}
+
 pub fn get_message_type_code(message: &MidiMessage) -> u8 {
     message.status & 0b11110000
 }
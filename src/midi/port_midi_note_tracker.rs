@@ -31,6 +31,10 @@ impl MidiNoteTracker for PortMidiNoteTracker {
             }
         }
     }
+
+    fn feed_untracked(&mut self, midi_message: TypedMidiMessage) -> Result<()> {
+        self.out_port.write_message(midi_message)
+    }
 }
 
 impl MidiSink for PortMidiNoteTracker {
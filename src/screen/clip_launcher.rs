@@ -0,0 +1,102 @@
+use midi::*;
+use hardcode::*;
+use looper::{Looper, ClipState};
+
+/// Session mode of the looper screen. The samples of the current
+/// scene occupy the slots of a grid, each slot is launched or stopped
+/// by its own pad note and its state is reported back to the
+/// controller through the velocities of NoteOns.
+pub struct ClipLauncher {
+    active: bool,
+    /// Velocities that were last sent to the LEDs of the pads
+    led_velocities: [Option<u8>; CLIP_LAUNCHER_COLUMNS * CLIP_LAUNCHER_ROWS],
+}
+
+fn slot_of_key(channel: u8, key: u8) -> Option<usize> {
+    let slot = key.wrapping_sub(CLIP_LAUNCHER_FIRST_KEY) as usize;
+    if channel == CLIP_LAUNCHER_CHANNEL && key >= CLIP_LAUNCHER_FIRST_KEY && slot < CLIP_LAUNCHER_COLUMNS * CLIP_LAUNCHER_ROWS {
+        Some(slot)
+    } else {
+        None
+    }
+}
+
+fn led_velocity(clip_state: Option<&ClipState>) -> u8 {
+    match clip_state {
+        None => CLIP_LED_EMPTY_VELOCITY,
+        Some(&ClipState::Stopped) => CLIP_LED_STOPPED_VELOCITY,
+        Some(&ClipState::Playing) => CLIP_LED_PLAYING_VELOCITY,
+        Some(&ClipState::Launching) | Some(&ClipState::Stopping) => CLIP_LED_QUEUED_VELOCITY,
+    }
+}
+
+impl ClipLauncher {
+    pub fn new() -> ClipLauncher {
+        ClipLauncher {
+            active: false,
+            led_velocities: [None; CLIP_LAUNCHER_COLUMNS * CLIP_LAUNCHER_ROWS],
+        }
+    }
+
+    pub fn toggle<NoteTracker: MidiNoteTracker>(&mut self, looper: &mut Looper<NoteTracker>) {
+        self.active = !self.active;
+        self.led_velocities = [None; CLIP_LAUNCHER_COLUMNS * CLIP_LAUNCHER_ROWS];
+
+        if !self.active {
+            for slot in 0..self.led_velocities.len() {
+                looper.send_feedback(TypedMidiMessage::NoteOn {
+                    channel: CLIP_LAUNCHER_CHANNEL,
+                    key: CLIP_LAUNCHER_FIRST_KEY + slot as u8,
+                    velocity: CLIP_LED_EMPTY_VELOCITY,
+                });
+            }
+        }
+    }
+
+    /// Handles the message if it comes from a pad of the
+    /// launcher. Returns false if the message should be processed
+    /// further.
+    pub fn handle_message<NoteTracker: MidiNoteTracker>(&mut self,
+                                                        looper: &mut Looper<NoteTracker>,
+                                                        message: &TypedMidiMessage) -> bool {
+        if !self.active {
+            return false;
+        }
+
+        match *message {
+            TypedMidiMessage::NoteOn { channel, key, velocity } => {
+                slot_of_key(channel, key).map(|slot| {
+                    if velocity > 0 {
+                        looper.toggle_clip(slot);
+                    }
+                }).is_some()
+            },
+
+            TypedMidiMessage::NoteOff { channel, key, .. } => slot_of_key(channel, key).is_some(),
+
+            _ => false,
+        }
+    }
+
+    /// Sends the LED velocities of the slots whose state changed since
+    /// the last update
+    pub fn update<NoteTracker: MidiNoteTracker>(&mut self, looper: &mut Looper<NoteTracker>) {
+        if !self.active {
+            return;
+        }
+
+        let clip_states = looper.clip_states();
+
+        for slot in 0..self.led_velocities.len() {
+            let velocity = led_velocity(clip_states.get(slot));
+            if self.led_velocities[slot] != Some(velocity) {
+                looper.send_feedback(TypedMidiMessage::NoteOn {
+                    channel: CLIP_LAUNCHER_CHANNEL,
+                    key: CLIP_LAUNCHER_FIRST_KEY + slot as u8,
+                    velocity: velocity,
+                });
+                self.led_velocities[slot] = Some(velocity);
+            }
+        }
+    }
+}
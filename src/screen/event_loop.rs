@@ -12,14 +12,15 @@ use std;
 pub struct EventLoop<'a> {
     timer_subsystem: TimerSubsystem,
     sdl_event_pump: EventPump,
-    midi_input_port: InputPort,
+    /// Absent if the MIDI input is read by the engine thread
+    midi_input_port: Option<InputPort>,
     renderer: Renderer<'a>,
 }
 
 impl<'a> EventLoop<'a> {
     pub fn new(timer_subsystem: TimerSubsystem,
            sdl_event_pump: EventPump,
-           midi_input_port: InputPort,
+           midi_input_port: Option<InputPort>,
            renderer: Renderer<'a>) -> EventLoop<'a> {
         EventLoop {
             timer_subsystem: timer_subsystem,
@@ -42,12 +43,14 @@ impl<'a> EventLoop<'a> {
             screen.handle_sdl_events(&sdl_events);
 
 
-            if let Ok(Some(raw_midi_events)) = self.midi_input_port.read_n(1024) {
-                let midi_events: Vec<AbsMidiEvent> = raw_midi_events
-                    .iter()
-                    .filter_map(|e| midi::parse_midi_event(e))
-                    .collect();
-                screen.handle_midi_events(&midi_events);
+            if let Some(ref midi_input_port) = self.midi_input_port {
+                if let Ok(Some(raw_midi_events)) = midi_input_port.read_n(1024) {
+                    let midi_events: Vec<AbsMidiEvent> = raw_midi_events
+                        .iter()
+                        .filter_map(|e| midi::parse_midi_event(e))
+                        .collect();
+                    screen.handle_midi_events(&midi_events);
+                }
             }
 
             if let Some(result) = screen.update(delta_time) {
@@ -4,7 +4,6 @@ use num::ToPrimitive;
 
 use midi::*;
 use hardcode::*;
-use looper::Looper;
 use config::Config;
 
 pub struct KeyboardLayout {
@@ -18,42 +17,35 @@ impl KeyboardLayout {
         }
     }
 
-    pub fn key_down<NoteTracker: MidiNoteTracker>(&self,
-                                                  looper: &mut Looper<NoteTracker>,
-                                                  keycode: &Keycode,
-                                                  timestamp: u32) {
+    pub fn key_down(&self, keycode: &Keycode, timestamp: u32) -> Option<AbsMidiEvent> {
         keycode
             .to_u64()
             .and_then(|keyvalue| self.layout.get(&keyvalue))
             .map(|midi_key| {
-                looper.on_midi_event(&AbsMidiEvent {
+                AbsMidiEvent {
                     message: TypedMidiMessage::NoteOn {
                         key: *midi_key,
                         channel: KEYBOARD_MESSAGE_CHANNEL,
                         velocity: KEYBOARD_MESSAGE_VELOCITY,
                     },
                     timestamp: timestamp
-                });
-            });
-
+                }
+            })
     }
 
-    pub fn key_up<NoteTracker: MidiNoteTracker>(&self,
-                                                looper: &mut Looper<NoteTracker>,
-                                                keycode: &Keycode,
-                                                timestamp: u32) {
+    pub fn key_up(&self, keycode: &Keycode, timestamp: u32) -> Option<AbsMidiEvent> {
         keycode
             .to_u64()
             .and_then(|keyvalue| self.layout.get(&keyvalue))
             .map(|midi_key| {
-                looper.on_midi_event(&AbsMidiEvent {
+                AbsMidiEvent {
                     message: TypedMidiMessage::NoteOff {
                         key: *midi_key,
                         channel: KEYBOARD_MESSAGE_CHANNEL,
                         velocity: KEYBOARD_MESSAGE_VELOCITY,
                     },
                     timestamp: timestamp
-                });
-            });
+                }
+            })
     }
 }
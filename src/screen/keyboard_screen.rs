@@ -7,7 +7,7 @@ use screen::Screen;
 use midi::*;
 use config::Config;
 use ui::VirtualKeyboard;
-use traits::*;
+use view::Renderable;
 
 pub struct KeyboardScreen<NoteTracker: MidiNoteTracker> {
     config: Config,
@@ -0,0 +1,198 @@
+use std::marker::PhantomData;
+use std::collections::HashMap;
+
+use midi::*;
+use looper::{Looper, ArpeggiatorParameter, StepPattern, CompositionData};
+use engine::Controller;
+use hardcode::*;
+use screen::ClipLauncher;
+use config::{Config, TempoControl};
+use measure::TapTempo;
+
+pub enum Feedback {
+    /// Digits to show in the popup
+    Popup(String),
+    /// The note that taps the tempo was learned
+    TapTempoNote(u8, u8),
+    /// A control of the arpeggiator was learned
+    ArpeggiatorControls(u8, HashMap<u8, ArpeggiatorParameter>),
+    /// The index of the sample to open in the step sequencer and its
+    /// pattern
+    StepPattern(usize, StepPattern),
+    /// The composition to save to the state file
+    Composition(CompositionData),
+}
+
+/// Handles the MIDI input of the looper screen on the engine thread
+pub struct LooperController<NoteTracker: MidiNoteTracker> {
+    timestamp: u32,
+    clip_launcher: ClipLauncher,
+    tap_tempo: TapTempo,
+    tap_tempo_note: Option<(u8, u8)>,
+    /// The next note is going to be bound to the tap tempo
+    learning_tap_tempo_note: bool,
+    tempo_control: TempoControl,
+    arpeggiator_controls: HashMap<u8, ArpeggiatorParameter>,
+    /// The next control change is going to be bound to the parameter
+    learning_arpeggiator_control: Option<ArpeggiatorParameter>,
+    note_tracker: PhantomData<NoteTracker>,
+}
+
+impl<NoteTracker: MidiNoteTracker> LooperController<NoteTracker> {
+    pub fn new(config: &Config) -> LooperController<NoteTracker> {
+        LooperController {
+            timestamp: 0,
+            clip_launcher: ClipLauncher::new(),
+            tap_tempo: TapTempo::new(),
+            tap_tempo_note: config.tap_tempo_note,
+            learning_tap_tempo_note: false,
+            tempo_control: config.tempo_control.clone(),
+            arpeggiator_controls: config.arpeggiator_controls.clone(),
+            learning_arpeggiator_control: None,
+            note_tracker: PhantomData,
+        }
+    }
+
+    pub fn tap_tempo(&mut self, looper: &mut Looper<NoteTracker>) -> Option<Feedback> {
+        self.tap_tempo.tap(self.timestamp).map(|beat_size_millis| {
+            let bpm = looper.tap_tempo(beat_size_millis);
+            Feedback::Popup(format!("{:03}", bpm))
+        })
+    }
+
+    pub fn learn_tap_tempo_note(&mut self) {
+        self.learning_tap_tempo_note = true;
+    }
+
+    pub fn learn_arpeggiator_control(&mut self, parameter: ArpeggiatorParameter) {
+        self.learning_arpeggiator_control = Some(parameter);
+    }
+
+    pub fn toggle_clip_launcher(&mut self, looper: &mut Looper<NoteTracker>) {
+        self.clip_launcher.toggle(looper);
+    }
+
+    fn is_tap_tempo_note(&self, channel: u8, key: u8) -> bool {
+        self.tap_tempo_note == Some((channel, key))
+    }
+}
+
+impl<NoteTracker> Controller for LooperController<NoteTracker>
+    where NoteTracker: MidiNoteTracker + Send + 'static {
+    type NoteTracker = NoteTracker;
+    type Reply = Feedback;
+
+    fn handle_midi_event(&mut self,
+                         looper: &mut Looper<NoteTracker>,
+                         event: &AbsMidiEvent) -> Option<Feedback> {
+        if self.clip_launcher.handle_message(looper, &event.message) {
+            return None;
+        }
+
+        match *event {
+            AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn { channel, key, .. },
+                ..
+            } if self.learning_tap_tempo_note => {
+                self.learning_tap_tempo_note = false;
+                self.tap_tempo_note = Some((channel, key));
+                Some(Feedback::TapTempoNote(channel, key))
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn { channel, key, .. },
+                ..
+            } if self.is_tap_tempo_note(channel, key) => {
+                self.tap_tempo(looper)
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::NoteOff { channel, key, .. },
+                ..
+            } if self.is_tap_tempo_note(channel, key) => None,
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::ControlChange { number, .. },
+                ..
+            } if self.learning_arpeggiator_control.is_some() => {
+                let parameter = self.learning_arpeggiator_control.take().unwrap();
+                // A parameter is controlled by one control at a time
+                self.arpeggiator_controls.retain(|_, learned_parameter| *learned_parameter != parameter);
+                self.arpeggiator_controls.insert(number, parameter);
+                Some(Feedback::ArpeggiatorControls(number, self.arpeggiator_controls.clone()))
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::ControlChange {
+                    number,
+                    value,
+                    ..
+                },
+                ..
+            } if self.arpeggiator_controls.contains_key(&number) => {
+                let parameter = self.arpeggiator_controls[&number];
+                let value = looper.set_arpeggiator_parameter(parameter, value);
+                Some(Feedback::Popup(format!("{:03}", value)))
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::ControlChange {
+                    number,
+                    value,
+                    ..
+                },
+                ..
+            } if number == self.tempo_control.number => {
+                let bpm = self.tempo_control.tempo_bpm(value);
+                looper.change_tempo(bpm);
+                Some(Feedback::Popup(format!("{:03}", bpm)))
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::ControlChange {
+                    number: GRID_CHANGE_CONTROL_NUMBER,
+                    value,
+                    ..
+                },
+                ..
+            } => {
+                let index = value as usize * BEAT_DIVISIONS.len() / 128;
+                let beat_division = looper.set_beat_division(BEAT_DIVISIONS[index]);
+                Some(Feedback::Popup(format!("{:02}", beat_division)))
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::NoteOn {
+                    key: CONTROL_KEY_NUMBER,
+                    channel: CONTROL_CHANNEL_NUMBER,
+                    ..
+                },
+                ..
+            } => {
+                looper.toggle_recording();
+                None
+            },
+
+            AbsMidiEvent {
+                message: TypedMidiMessage::NoteOff {
+                    key: CONTROL_KEY_NUMBER,
+                    channel: CONTROL_CHANNEL_NUMBER,
+                    ..
+                },
+                ..
+            } => None,
+
+            _ => {
+                looper.on_midi_event(event);
+                None
+            },
+        }
+    }
+
+    fn update(&mut self, looper: &mut Looper<NoteTracker>, delta_time: u32) -> Option<Feedback> {
+        // TODO(#217): calculate current timestamp with PortMidi mechanisms
+        self.timestamp += delta_time;
+        self.clip_launcher.update(looper);
+        None
+    }
+}
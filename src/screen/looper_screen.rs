@@ -1,5 +1,6 @@
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, LSHIFTMOD, RSHIFTMOD, LCTRLMOD, RCTRLMOD};
+use sdl2::mouse::Mouse;
 use sdl2::render::Renderer;
 use sdl2::pixels::Color;
 
@@ -8,79 +9,533 @@ use std::path::Path;
 use midi::*;
 use screen::Screen;
 use ui::Popup;
-use looper::Looper;
+use looper::{Looper, Action, LaunchQuantization, ArpeggiatorParameter, CompositionData};
+use engine::Engine;
 use hardcode::*;
-use traits::*;
+use traits::Updatable;
+use view::{Renderable, LooperView, ClipLauncherView};
 use path;
-use screen::KeyboardLayout;
+use screen::{KeyboardLayout, LooperController, Feedback, StepSequencerScreen};
 use config::Config;
 
-pub struct LooperScreen<NoteTracker: MidiNoteTracker> {
+/// Shows the state of the looper and sends the commands to it. The
+/// looper itself runs on the engine thread.
+pub struct LooperScreen<NoteTracker: MidiNoteTracker + Send + 'static> {
     timestamp: u32,
-    looper: Looper<NoteTracker>,
+    engine: Engine<LooperController<NoteTracker>>,
     bpm_popup: Popup,
     quit: bool,
+    config: Config,
     keyboard_layout: KeyboardLayout,
+    clip_launcher_active: bool,
+    /// The left mouse button went down in the automation lane and
+    /// has not been released yet
+    drawing_automation: bool,
+    /// Takes over the window and the computer keyboard while it is
+    /// open
+    step_sequencer: Option<StepSequencerScreen>,
 }
 
-impl<NoteTracker: MidiNoteTracker> LooperScreen<NoteTracker> {
-    pub fn new(looper: Looper<NoteTracker>,
+impl<NoteTracker: MidiNoteTracker + Send + 'static> LooperScreen<NoteTracker> {
+    pub fn new(engine: Engine<LooperController<NoteTracker>>,
                bpm_popup: Popup,
                config: &Config) -> LooperScreen<NoteTracker> {
         LooperScreen {
-            looper: looper,
+            engine: engine,
             bpm_popup: bpm_popup,
             quit: false,
+            config: config.clone(),
             keyboard_layout: KeyboardLayout::from_config(config),
+            clip_launcher_active: false,
+            drawing_automation: false,
+            step_sequencer: None,
             timestamp: 0,
         }
     }
 
+    /// Performs the command on the engine thread. The returned
+    /// string is shown in the popup.
+    fn execute<F>(&self, mut command: F)
+        where F: FnMut(&mut Looper<NoteTracker>) -> Option<String> + Send + 'static {
+        self.engine.execute(move |_, looper| command(looper).map(Feedback::Popup));
+    }
+
+    /// Makes the next control change control the parameter
+    fn learn_arpeggiator_control(&self, parameter: ArpeggiatorParameter) {
+        self.engine.execute(move |controller, _| {
+            controller.learn_arpeggiator_control(parameter);
+            None
+        });
+    }
+
+    /// Draws the value at the window coordinates into the automation
+    /// lane at the bottom of the window
+    fn draw_automation(&self, x: i32, y: i32, new_stroke: bool) {
+        let window_width = (RATIO_WIDTH * RATIO_FACTOR) as f32;
+        let lane_top = (RATIO_HEIGHT * RATIO_FACTOR - AUTOMATION_LANE_HEIGHT) as f32;
+        let position = ((x as f32 - 5.0) / (window_width - 10.0)).max(0.0).min(1.0);
+        let value = (1.0 - (y as f32 - lane_top) / (AUTOMATION_LANE_HEIGHT - 1) as f32).max(0.0).min(1.0);
+        self.execute(move |looper| {
+            looper.draw_last_sample_automation(position, value, new_stroke);
+            None
+        });
+    }
+
+    /// Selects the note of the last sample at the window coordinates
+    /// in the piano roll above the automation lane
+    fn select_note(&self, x: i32, y: i32) {
+        let window_width = (RATIO_WIDTH * RATIO_FACTOR) as f32;
+        let row_height = (RATIO_HEIGHT * RATIO_FACTOR - AUTOMATION_LANE_HEIGHT) as f32 / 128.0;
+        let position = ((x as f32 - 5.0) / (window_width - 10.0)).max(0.0).min(1.0);
+        let key = 127 - (y as f32 / row_height).max(0.0).min(127.0) as u8;
+        self.execute(move |looper| {
+            looper.select_last_sample_note(position, key)
+                .map(|condition| format!("{:03}", condition.number()))
+        });
+    }
+
+    fn send_midi_event(&self, event: Option<AbsMidiEvent>) {
+        if let Some(event) = event {
+            self.execute(move |looper| {
+                looper.on_midi_event(&event);
+                None
+            });
+        }
+    }
+}
+
+fn scene_number(keycode: Keycode) -> Option<usize> {
+    match keycode {
+        Keycode::Num1 => Some(0),
+        Keycode::Num2 => Some(1),
+        Keycode::Num3 => Some(2),
+        Keycode::Num4 => Some(3),
+        Keycode::Num5 => Some(4),
+        Keycode::Num6 => Some(5),
+        Keycode::Num7 => Some(6),
+        Keycode::Num8 => Some(7),
+        Keycode::Num9 => Some(8),
+        _ => None,
+    }
 }
 
-impl<NoteTracker: MidiNoteTracker> Screen<()> for LooperScreen<NoteTracker> {
+impl<NoteTracker: MidiNoteTracker + Send + 'static> Screen<Config> for LooperScreen<NoteTracker> {
     fn handle_sdl_events(&mut self, events: &[Event]) {
+        if let Some(ref mut step_sequencer) = self.step_sequencer {
+            step_sequencer.handle_sdl_events(events);
+            return;
+        }
+
         for event in events {
             // TODO(#249): Hardcoded key bindings in looper mode collide with the key bound via the keyboard mode
             match *event {
                 Event::Quit { .. } |
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     self.quit = true;
-                    self.looper.reset();
+                    self.execute(|looper| { looper.reset(); None });
                 }
 
                 Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
-                    self.looper.toggle_recording();
+                    self.execute(|looper| { looper.toggle_recording(); None });
                 }
 
-                Event::KeyDown { keycode: Some(Keycode::Z), .. } => {
-                    self.looper.reset();
+                Event::KeyDown { keycode: Some(Keycode::Z), keymod, .. } => {
+                    // Ctrl undoes only the last edit of the samples
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.execute(|looper| { looper.undo_last_edit(); None });
+                    } else {
+                        self.execute(|looper| { looper.reset(); None });
+                    }
                 }
 
                 Event::KeyDown { keycode: Some(Keycode::Q), .. } => {
-                    self.looper.undo_last_recording();
+                    self.execute(|looper| { looper.schedule(Action::UndoLastRecording); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Semicolon), .. } => {
+                    self.execute(|looper| { looper.schedule(Action::ToggleLastSampleMute); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Backquote), .. } => {
+                    self.execute(|looper| { looper.switch_launch_quantization(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::T), keymod, .. } => {
+                    // Shift makes the next incoming note tap the tempo
+                    if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.engine.execute(|controller, _| { controller.learn_tap_tempo_note(); None });
+                    } else {
+                        self.engine.execute(|controller, looper| controller.tap_tempo(looper));
+                    }
                 }
 
                 Event::KeyDown { keycode: Some(Keycode::P), .. } => {
-                    self.looper.toggle_pause();
+                    self.execute(|looper| { looper.toggle_pause(); None });
                 }
 
-                Event::KeyDown { keycode: Some(Keycode::S), .. } => {
-                    let state_file_path = Path::new(STATE_FILE_PATH);
-                    let absolute_path = path::display_absolute_path(state_file_path);
-                    match self.looper.save_state_to_file(state_file_path) {
-                        Ok(_) => println!("Saved looper state to {}", absolute_path.display()),
-                        Err(e) => println!("[ERROR] Could not save state to {}. Reason: {}",
-                                           absolute_path.display(),
-                                           e),
+                Event::KeyDown { keycode: Some(Keycode::Minus), .. } => {
+                    self.execute(|looper| {
+                        looper.change_last_sample_playback_rate(false)
+                            .map(|playback_rate| format!("{:03}", playback_rate.as_percent()))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Equals), .. } => {
+                    self.execute(|looper| {
+                        looper.change_last_sample_playback_rate(true)
+                            .map(|playback_rate| format!("{:03}", playback_rate.as_percent()))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::LeftBracket), .. } => {
+                    self.execute(|looper| {
+                        looper.change_last_sample_swing(false).map(|swing| format!("{:02}", swing))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::RightBracket), .. } => {
+                    self.execute(|looper| {
+                        looper.change_last_sample_swing(true).map(|swing| format!("{:02}", swing))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+                    self.execute(|looper| {
+                        looper.change_last_sample_quantize_strength(true)
+                            .map(|quantize_strength| format!("{:03}", quantize_strength))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+                    self.execute(|looper| {
+                        looper.change_last_sample_quantize_strength(false)
+                            .map(|quantize_strength| format!("{:03}", quantize_strength))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Slash), .. } => {
+                    self.execute(|looper| { looper.requantize_last_sample(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Comma), .. } => {
+                    self.execute(|looper| { looper.extract_groove_from_last_sample(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Period), .. } => {
+                    self.execute(|looper| { looper.toggle_last_sample_groove(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Backslash), .. } => {
+                    self.execute(|looper| { looper.toggle_last_sample_reverse(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Left), keymod, .. } => {
+                    // Shift nudges by whole measures
+                    if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.execute(|looper| { looper.nudge_last_sample_measures(-1); None });
+                    } else {
+                        self.execute(|looper| { looper.nudge_last_sample(-1); None });
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Right), keymod, .. } => {
+                    if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.execute(|looper| { looper.nudge_last_sample_measures(1); None });
+                    } else {
+                        self.execute(|looper| { looper.nudge_last_sample(1); None });
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(keycode @ Keycode::Home), keymod, .. } |
+                Event::KeyDown { keycode: Some(keycode @ Keycode::End), keymod, .. } => {
+                    let extend = keycode == Keycode::End;
+                    // Shift resizes by beats, so the sample does not
+                    // have to fill the measures
+                    if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.execute(move |looper| {
+                            looper.resize_last_sample_by_beat(extend).map(|amount_of_beats| format!("{:02}", amount_of_beats))
+                        });
+                    } else {
+                        self.execute(move |looper| {
+                            looper.resize_last_sample(extend).map(|amount_of_measures| format!("{:02}", amount_of_measures))
+                        });
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Quote), keymod, .. } => {
+                    // Shift goes back to the previous channel. The popup
+                    // counts the channels from 1, 0 stands for the
+                    // recorded ones.
+                    let next = !keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                    self.execute(move |looper| {
+                        looper.change_last_sample_output_channel(next)
+                            .map(|output_channel| format!("{:02}", output_channel.map_or(0, |channel| channel + 1)))
+                    });
+                }
+
+                // Edits the note selected in the piano roll, or the notes
+                // of the last sample with the key of the last played note,
+                // Shift goes back to the previous condition. The popup
+                // shows the percent of the probability or the cycle
+                // followed by the period.
+                Event::KeyDown { keycode: Some(Keycode::N), keymod, .. } => {
+                    let forward = !keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                    self.execute(move |looper| {
+                        looper.switch_last_sample_note_condition(forward)
+                            .map(|condition| format!("{:03}", condition.number()))
+                    });
+                }
+
+                Event::MouseWheel { y, .. } if y != 0 => {
+                    let forward = y > 0;
+                    self.execute(move |looper| {
+                        looper.switch_last_sample_note_condition(forward)
+                            .map(|condition| format!("{:03}", condition.number()))
+                    });
+                }
+
+                // Shows the next controller recorded in the current scene
+                Event::KeyDown { keycode: Some(Keycode::C), .. } => {
+                    self.execute(|looper| Some(format!("{:03}", looper.switch_automation_lane().number())));
+                }
+
+                // Goes through all the controllers, the pitch bend
+                // being shown as 128
+                Event::KeyDown { keycode: Some(Keycode::V), keymod, .. } => {
+                    let next = !keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                    self.execute(move |looper| Some(format!("{:03}", looper.change_automation_lane(next).number())));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::X), .. } => {
+                    self.execute(|looper| { looper.clear_last_sample_automation(); None });
+                }
+
+                Event::MouseButtonDown { mouse_btn: Mouse::Left, x, y, .. } => {
+                    if y >= (RATIO_HEIGHT * RATIO_FACTOR - AUTOMATION_LANE_HEIGHT) as i32 {
+                        self.drawing_automation = true;
+                        self.draw_automation(x, y, true);
+                    } else {
+                        self.select_note(x, y);
+                    }
+                }
+
+                Event::MouseMotion { x, y, .. } if self.drawing_automation => {
+                    self.draw_automation(x, y, false);
+                }
+
+                Event::MouseButtonUp { mouse_btn: Mouse::Left, .. } => {
+                    self.drawing_automation = false;
+                }
+
+                // Opens the step sequencer for the last sample, Shift
+                // for a new one
+                Event::KeyDown { keycode: Some(Keycode::G), keymod, .. } => {
+                    let new_sample = keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                    self.engine.execute(move |_, looper| {
+                        let (index, pattern) = looper.last_sample_step_pattern(new_sample);
+                        Some(Feedback::StepPattern(index, pattern))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Insert), .. } => {
+                    self.execute(|looper| { looper.duplicate_last_sample(); None });
+                }
+
+                // Shift adds the last sample to the current scene or
+                // removes it from there
+                Event::KeyDown { keycode: Some(Keycode::Return), keymod, .. } if keymod.intersects(LSHIFTMOD | RSHIFTMOD) => {
+                    self.execute(|looper| {
+                        looper.toggle_last_sample_in_current_scene().map(|assigned| format!("{:02}", assigned as u32))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                    self.execute(|looper| {
+                        let scene = looper.add_scene();
+                        looper.schedule(Action::SwitchScene(scene));
+                        None
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F1), .. } => {
+                    self.clip_launcher_active = !self.clip_launcher_active;
+                    self.engine.execute(|controller, looper| { controller.toggle_clip_launcher(looper); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F2), .. } => {
+                    self.execute(|looper| { looper.toggle_metronome(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F3), .. } => {
+                    self.execute(|looper| {
+                        looper.switch_time_signature()
+                            .map(|(measure_size_bpm, beat_unit)| format!("{}{}", measure_size_bpm, beat_unit))
+                    });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F4), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.switch_beat_division())));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F5), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.switch_tempo_ramp_bars())));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F6), keymod, .. } => {
+                    // Shift enables or suspends the recorded tempo
+                    // automation, Ctrl clears it
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.execute(|looper| { looper.clear_tempo_automation(); None });
+                    } else if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.execute(|looper| Some(format!("{:02}", looper.toggle_tempo_automation() as u32)));
+                    } else {
+                        self.execute(|looper| { looper.toggle_tempo_recording(); None });
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                    self.execute(|looper| { looper.toggle_midi_clock(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F8), .. } => {
+                    self.execute(|looper| { looper.double_last_sample(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F9), .. } => {
+                    self.execute(|looper| { looper.bounce_current_scene(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F10), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.split_last_sample_by_channel())));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::F11), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.split_last_sample_by_keys())));
+                }
+
+                // Ctrl with the keys of the arpeggiator makes the next
+                // control change control the corresponding parameter
+                Event::KeyDown { keycode: Some(Keycode::KpEnter), keymod, .. } => {
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.learn_arpeggiator_control(ArpeggiatorParameter::Enabled);
+                    } else {
+                        self.execute(|looper| Some(format!("{:02}", looper.toggle_arpeggiator() as u32)));
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::KpDivide), keymod, .. } => {
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.learn_arpeggiator_control(ArpeggiatorParameter::Mode);
+                    } else {
+                        self.execute(|looper| Some(format!("{:02}", looper.switch_arpeggiator_mode().number())));
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::KpMultiply), keymod, .. } => {
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.learn_arpeggiator_control(ArpeggiatorParameter::Rate);
+                    } else {
+                        self.execute(|looper| Some(format!("{:02}", looper.switch_arpeggiator_rate())));
+                    }
+                }
+
+                // Shift changes the gate instead of the octaves
+                Event::KeyDown { keycode: Some(keycode @ Keycode::KpMinus), keymod, .. } |
+                Event::KeyDown { keycode: Some(keycode @ Keycode::KpPlus), keymod, .. } => {
+                    let increase = keycode == Keycode::KpPlus;
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.learn_arpeggiator_control(if increase {
+                            ArpeggiatorParameter::Gate
+                        } else {
+                            ArpeggiatorParameter::Octaves
+                        });
+                    } else if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.execute(move |looper| Some(format!("{:03}", looper.change_arpeggiator_gate(increase))));
+                    } else {
+                        self.execute(move |looper| Some(format!("{:02}", looper.change_arpeggiator_octaves(increase))));
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Kp0), keymod, .. } => {
+                    if keymod.intersects(LCTRLMOD | RCTRLMOD) {
+                        self.learn_arpeggiator_control(ArpeggiatorParameter::Latch);
+                    } else {
+                        self.execute(|looper| Some(format!("{:02}", looper.toggle_arpeggiator_latch() as u32)));
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Kp1), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.switch_chord_mode().number())));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Kp2), .. } => {
+                    self.execute(|looper| { looper.learn_chord_shape(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Kp3), keymod, .. } => {
+                    let increase = !keymod.intersects(LSHIFTMOD | RSHIFTMOD);
+                    self.execute(move |looper| Some(format!("{:02}", looper.change_harmonizer_key(increase))));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Kp4), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.switch_harmonizer_scale().number())));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Kp5), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.toggle_note_repeat() as u32)));
+                }
+
+                // The rate is shown as the note value, e.g. 16 for
+                // the sixteenths and 24 for the sixteenth triplets
+                Event::KeyDown { keycode: Some(Keycode::Kp6), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.switch_note_repeat_rate() * 4)));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
+                    self.execute(|looper| { looper.toggle_song_mode(); None });
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.change_current_scene_cycles(true))));
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
+                    self.execute(|looper| Some(format!("{:02}", looper.change_current_scene_cycles(false))));
+                }
+
+                Event::KeyDown { keycode: Some(keycode), keymod, .. } if scene_number(keycode).is_some() => {
+                    let action = Action::SwitchScene(scene_number(keycode).unwrap());
+                    // Shift defers the switch until the end of the current cycle
+                    if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        self.execute(move |looper| {
+                            looper.schedule_with_quantization(action.clone(), LaunchQuantization::AfterLoops(1));
+                            None
+                        });
+                    } else {
+                        self.execute(move |looper| { looper.schedule(action.clone()); None });
                     }
                 }
 
+                // The engine thread only hands out the composition, the
+                // file is written here
+                Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+                    self.engine.execute(|_, looper| Some(Feedback::Composition(looper.composition_data())));
+                }
+
                 Event::KeyDown { keycode: Some(Keycode::L), .. } => {
                     let state_file_path = Path::new(STATE_FILE_PATH);
                     let absolute_path = path::display_absolute_path(state_file_path);
-                    match self.looper.load_state_from_file(state_file_path) {
-                        Ok(_) => println!("Loaded looper state from {}", absolute_path.display()),
+                    match CompositionData::load(state_file_path) {
+                        Ok(composition) => {
+                            let mut composition = Some(composition);
+                            self.execute(move |looper| {
+                                if let Some(composition) = composition.take() {
+                                    looper.schedule(Action::LoadComposition(composition));
+                                }
+                                None
+                            });
+                            println!("Loaded looper state from {}", absolute_path.display());
+                        },
                         Err(e) => println!("[ERROR] Could not load state from {}. Reason: {}",
                                            absolute_path.display(),
                                            e),
@@ -88,15 +543,13 @@ impl<NoteTracker: MidiNoteTracker> Screen<()> for LooperScreen<NoteTracker> {
                 }
 
                 Event::KeyDown { keycode: Some(keycode), .. } => {
-                    self.keyboard_layout.key_down::<NoteTracker>(&mut self.looper,
-                                                                 &keycode,
-                                                                 self.timestamp);
+                    let event = self.keyboard_layout.key_down(&keycode, self.timestamp);
+                    self.send_midi_event(event);
                 }
 
                 Event::KeyUp { keycode: Some(keycode), .. } => {
-                    self.keyboard_layout.key_up::<NoteTracker>(&mut self.looper,
-                                                               &keycode,
-                                                               self.timestamp);
+                    let event = self.keyboard_layout.key_up(&keycode, self.timestamp);
+                    self.send_midi_event(event);
                 }
 
                 _ => {}
@@ -104,68 +557,82 @@ impl<NoteTracker: MidiNoteTracker> Screen<()> for LooperScreen<NoteTracker> {
         }
     }
 
-    fn handle_midi_events(&mut self, events: &[AbsMidiEvent]) {
-        for event in events {
-            // FIXME(#149): Extract MIDI logging into a separate entity
-            println!("{:?}", event.message);
+    fn handle_midi_events(&mut self, _: &[AbsMidiEvent]) {
+        // The MIDI input is handled by the controller on the engine
+        // thread
+    }
 
-            match *event {
-                AbsMidiEvent {
-                    message: TypedMidiMessage::ControlChange {
-                        number: TEMPO_CHANGE_CONTROL_NUMBER,
-                        value,
-                        ..
-                    },
-                    ..
-                } => {
-                    let bpm = value as u32 + 90;
-                    self.looper.update_tempo_bpm(bpm);
-                    self.bpm_popup.bump(format!("{:03}", bpm).as_str());
-                },
+    fn update(&mut self, delta_time: u32) -> Option<Config> {
+        // TODO(#217): calculate current timestamp with PortMidi mechanisms
+        self.timestamp += delta_time;
+        self.engine.update_snapshot();
 
-                AbsMidiEvent {
-                    message: TypedMidiMessage::NoteOn {
-                        key: CONTROL_KEY_NUMBER,
-                        channel: CONTROL_CHANNEL_NUMBER,
-                        ..
-                    },
-                    ..
-                } => {
-                    self.looper.toggle_recording();
+        while let Some(feedback) = self.engine.take_reply() {
+            match feedback {
+                Feedback::Popup(text) => self.bpm_popup.bump(text.as_str()),
+                Feedback::TapTempoNote(channel, key) => {
+                    self.config.tap_tempo_note = Some((channel, key));
+                    self.bpm_popup.bump(format!("{:03}", key).as_str());
+                },
+                Feedback::ArpeggiatorControls(number, arpeggiator_controls) => {
+                    self.config.arpeggiator_controls = arpeggiator_controls;
+                    self.bpm_popup.bump(format!("{:03}", number).as_str());
+                },
+                Feedback::StepPattern(index, pattern) => self.step_sequencer = Some(StepSequencerScreen::new(index, pattern)),
+                Feedback::Composition(composition) => {
+                    let state_file_path = Path::new(STATE_FILE_PATH);
+                    let absolute_path = path::display_absolute_path(state_file_path);
+                    match composition.save(state_file_path) {
+                        Ok(_) => println!("Saved looper state to {}", absolute_path.display()),
+                        Err(e) => println!("[ERROR] Could not save state to {}. Reason: {}",
+                                           absolute_path.display(),
+                                           e),
+                    }
                 },
-
-                AbsMidiEvent {
-                    message: TypedMidiMessage::NoteOff {
-                        key: CONTROL_KEY_NUMBER,
-                        channel: CONTROL_CHANNEL_NUMBER,
-                        ..
-                    },
-                    ..
-                } => {},
-
-                _ => self.looper.on_midi_event(&event),
-
             }
         }
-    }
 
-    fn update(&mut self, delta_time: u32) -> Option<()> {
-        // TODO(#217): calculate current timestamp with PortMidi mechanisms
-        self.timestamp += delta_time;
-        self.looper.update(delta_time);
+        let (pattern, popup_text, closed) = match self.step_sequencer {
+            Some(ref mut step_sequencer) => {
+                let index = step_sequencer.sample_index();
+                (step_sequencer.take_changed_pattern().map(|pattern| (index, pattern)),
+                 step_sequencer.take_popup_text(),
+                 step_sequencer.update(delta_time).is_some())
+            },
+            None => (None, None, false),
+        };
+        if let Some((index, pattern)) = pattern {
+            self.execute(move |looper| { looper.write_sample_step_pattern(index, &pattern); None });
+        }
+        if let Some(text) = popup_text {
+            self.bpm_popup.bump(text.as_str());
+        }
+        if closed {
+            self.step_sequencer = None;
+        }
+
         self.bpm_popup.update(delta_time);
 
         if self.quit {
-            Some({})
+            Some(self.config.clone())
         } else {
             None
         }
     }
 
     fn render(&self, renderer: &mut Renderer) {
+        let snapshot = self.engine.snapshot();
+
         renderer.set_draw_color(Color::RGB(24, 24, 24));
         renderer.clear();
-        self.looper.render(renderer);
+        if let Some(ref step_sequencer) = self.step_sequencer {
+            step_sequencer.render(renderer);
+        } else {
+            LooperView::new(snapshot, self.bpm_popup.font()).render(renderer);
+            if self.clip_launcher_active {
+                ClipLauncherView::new(&snapshot.clip_states).render(renderer);
+            }
+        }
         self.bpm_popup.render(renderer);
     }
 }
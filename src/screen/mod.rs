@@ -4,9 +4,15 @@ mod port_selection_screen;
 mod event_loop;
 mod keyboard_layout;
 mod keyboard_screen;
+mod clip_launcher;
+mod looper_controller;
+mod step_sequencer_screen;
 pub use self::screen::Screen;
 pub use self::looper_screen::LooperScreen;
 pub use self::port_selection_screen::PortSelectionScreen;
 pub use self::event_loop::EventLoop;
 pub use self::keyboard_layout::KeyboardLayout;
 pub use self::keyboard_screen::KeyboardScreen;
+pub use self::clip_launcher::ClipLauncher;
+pub use self::looper_controller::{LooperController, Feedback};
+pub use self::step_sequencer_screen::StepSequencerScreen;
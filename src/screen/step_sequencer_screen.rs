@@ -0,0 +1,248 @@
+use std::cmp;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::Mouse;
+use sdl2::render::Renderer;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use screen::Screen;
+use midi::AbsMidiEvent;
+use looper::StepPattern;
+use hardcode::*;
+
+fn is_black_key(key: u8) -> bool {
+    match key % 12 {
+        1 | 3 | 6 | 8 | 10 => true,
+        _ => false,
+    }
+}
+
+/// Grid of the steps of the last sample opened from the looper
+/// screen. Every change of the steps is written into the sample right
+/// away, so it is heard on the next loop while the looper keeps
+/// playing.
+pub struct StepSequencerScreen {
+    /// Index of the sample the pattern is written into
+    sample_index: usize,
+    pattern: StepPattern,
+    /// Row and step edited from the computer keyboard
+    cursor: (usize, usize),
+    /// The pattern has changed since it was last taken
+    changed: bool,
+    /// Digits to show in the popup of the looper screen
+    popup_text: Option<String>,
+    quit: bool,
+}
+
+impl StepSequencerScreen {
+    pub fn new(sample_index: usize, pattern: StepPattern) -> StepSequencerScreen {
+        StepSequencerScreen {
+            sample_index: sample_index,
+            pattern: pattern,
+            cursor: (0, 0),
+            changed: false,
+            popup_text: None,
+            quit: false,
+        }
+    }
+
+    pub fn sample_index(&self) -> usize {
+        self.sample_index
+    }
+
+    /// The pattern to write into the sample if it has changed. Only
+    /// the changes since it was last taken are written.
+    pub fn take_changed_pattern(&mut self) -> Option<StepPattern> {
+        if self.changed {
+            self.changed = false;
+            let pattern = self.pattern.clone();
+            self.pattern.mark_written();
+            Some(pattern)
+        } else {
+            None
+        }
+    }
+
+    pub fn take_popup_text(&mut self) -> Option<String> {
+        self.popup_text.take()
+    }
+
+    /// The first step and the amount of steps of the page with the
+    /// cursor
+    fn visible_steps(&self, width: u32) -> (usize, usize) {
+        let grid_width = width.saturating_sub(STEP_SEQUENCER_KEY_WIDTH);
+        let page_size = cmp::max(grid_width / STEP_SEQUENCER_MIN_STEP_WIDTH, 1) as usize;
+        let page_size = cmp::max(cmp::min(page_size, self.pattern.amount_of_steps()), 1);
+        let (_, step) = self.cursor;
+        (step / page_size * page_size, page_size)
+    }
+
+    fn cell_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let (_, page_size) = self.visible_steps(width);
+        let amount_of_rows = self.pattern.rows.len() as u32;
+        (cmp::max(width.saturating_sub(STEP_SEQUENCER_KEY_WIDTH) / page_size as u32, 1),
+         cmp::max(height / amount_of_rows, 1))
+    }
+
+    /// Row and step under the window coordinates
+    fn cell_at(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let width = RATIO_WIDTH * RATIO_FACTOR;
+        let (cell_width, cell_height) = self.cell_size(width, RATIO_HEIGHT * RATIO_FACTOR);
+        let (first_step, page_size) = self.visible_steps(width);
+        if x < STEP_SEQUENCER_KEY_WIDTH as i32 || y < 0 {
+            return None;
+        }
+
+        let row = y as usize / cell_height as usize;
+        let step = (x as usize - STEP_SEQUENCER_KEY_WIDTH as usize) / cell_width as usize;
+        if row < self.pattern.rows.len() && step < page_size && first_step + step < self.pattern.amount_of_steps() {
+            Some((row, first_step + step))
+        } else {
+            None
+        }
+    }
+
+    fn toggle_step(&mut self, row: usize, step: usize) {
+        let velocity = self.pattern.toggle_step(row, step);
+        self.popup_text = Some(format!("{:03}", velocity.unwrap_or(0)));
+        self.changed = true;
+    }
+
+    fn move_cursor(&mut self, rows: i32, steps: i32) {
+        let amount_of_rows = self.pattern.rows.len() as i32;
+        let amount_of_steps = self.pattern.amount_of_steps() as i32;
+        let (row, step) = self.cursor;
+        self.cursor = (((row as i32 + rows + amount_of_rows) % amount_of_rows) as usize,
+                       ((step as i32 + steps + amount_of_steps) % amount_of_steps) as usize);
+    }
+}
+
+impl Screen<()> for StepSequencerScreen {
+    fn handle_sdl_events(&mut self, events: &[Event]) {
+        for event in events {
+            let (row, step) = self.cursor;
+
+            match *event {
+                Event::Quit { .. } |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    self.quit = true;
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Left), .. } => self.move_cursor(0, -1),
+                Event::KeyDown { keycode: Some(Keycode::Right), .. } => self.move_cursor(0, 1),
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } => self.move_cursor(-1, 0),
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } => self.move_cursor(1, 0),
+
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } |
+                Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                    self.toggle_step(row, step);
+                }
+
+                Event::KeyDown { keycode: Some(keycode @ Keycode::Minus), .. } |
+                Event::KeyDown { keycode: Some(keycode @ Keycode::Equals), .. } => {
+                    let increase = keycode == Keycode::Equals;
+                    if let Some(velocity) = self.pattern.change_velocity(row, step, increase) {
+                        self.popup_text = Some(format!("{:03}", velocity));
+                        self.changed = true;
+                    }
+                }
+
+                Event::KeyDown { keycode: Some(keycode @ Keycode::PageUp), .. } |
+                Event::KeyDown { keycode: Some(keycode @ Keycode::PageDown), .. } => {
+                    let increase = keycode == Keycode::PageUp;
+                    let key = self.pattern.change_key(row, increase);
+                    self.popup_text = Some(format!("{:03}", key));
+                    self.changed = true;
+                }
+
+                Event::MouseButtonDown { mouse_btn: Mouse::Left, x, y, .. } => {
+                    if let Some((row, step)) = self.cell_at(x, y) {
+                        self.cursor = (row, step);
+                        self.toggle_step(row, step);
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+
+    fn handle_midi_events(&mut self, _: &[AbsMidiEvent]) {
+        // The MIDI input keeps going to the looper
+    }
+
+    fn update(&mut self, _: u32) -> Option<()> {
+        if self.quit {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn render(&self, renderer: &mut Renderer) {
+        let viewport = renderer.viewport();
+        let (cell_width, cell_height) = self.cell_size(viewport.width(), viewport.height());
+        let (first_step, page_size) = self.visible_steps(viewport.width());
+        let steps_per_beat = (QUANTS_PER_BEAT / self.pattern.step_quants) as usize;
+
+        for (row_index, row) in self.pattern.rows.iter().enumerate() {
+            let y = (row_index as u32 * cell_height) as i32;
+
+            renderer.set_draw_color(if is_black_key(row.key) {
+                Color::RGB(60, 60, 60)
+            } else {
+                Color::RGB(200, 200, 200)
+            });
+            renderer.fill_rect(Rect::new(0, y + 1, STEP_SEQUENCER_KEY_WIDTH - 2, cell_height.saturating_sub(2))).unwrap();
+
+            for (step, velocity) in row.steps.iter().enumerate().skip(first_step).take(page_size) {
+                let x = (STEP_SEQUENCER_KEY_WIDTH + (step - first_step) as u32 * cell_width) as i32;
+
+                renderer.set_draw_color(if step % steps_per_beat == 0 {
+                    Color::RGB(90, 90, 90)
+                } else {
+                    Color::RGB(50, 50, 50)
+                });
+                renderer.draw_rect(Rect::new(x + 1, y + 1, cell_width.saturating_sub(2), cell_height.saturating_sub(2))).unwrap();
+
+                // The louder the step the higher it is filled
+                if let Some(velocity) = *velocity {
+                    let height = cell_height.saturating_sub(4) * velocity as u32 / 127;
+                    renderer.set_draw_color(Color::RGB(255, 153, 102));
+                    renderer.fill_rect(Rect::new(x + 2, y + cell_height.saturating_sub(2 + height) as i32, cell_width.saturating_sub(4), height)).unwrap();
+                }
+            }
+        }
+
+        let (row, step) = self.cursor;
+        renderer.set_draw_color(Color::RGB(255, 255, 255));
+        renderer.draw_rect(Rect::new((STEP_SEQUENCER_KEY_WIDTH + (step - first_step) as u32 * cell_width) as i32,
+                                     (row as u32 * cell_height) as i32,
+                                     cell_width,
+                                     cell_height)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StepSequencerScreen;
+    use looper::StepPattern;
+    use hardcode::*;
+
+    #[test]
+    fn test_long_patterns_are_paged() {
+        // 64 measures of the 1/8 beat steps
+        let amount_of_steps = 64 * 4 * 8;
+        let mut screen = StepSequencerScreen::new(0, StepPattern::from_notes(&[], 9, 3, amount_of_steps));
+        let x = STEP_SEQUENCER_KEY_WIDTH as i32 + 1;
+        assert_eq!(Some((0, 0)), screen.cell_at(x, 0));
+
+        screen.move_cursor(0, -1);
+        let (first_step, page_size) = screen.visible_steps(RATIO_WIDTH * RATIO_FACTOR);
+        assert!(page_size < amount_of_steps);
+        assert_eq!(Some((0, first_step)), screen.cell_at(x, 0));
+        assert_eq!(None, screen.cell_at((RATIO_WIDTH * RATIO_FACTOR) as i32 + 1, 0));
+    }
+}
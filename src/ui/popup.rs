@@ -5,7 +5,8 @@ use sdl2::rect::Rect;
 use sdl2::pixels::Color;
 use sdl2_ttf::Font;
 
-use traits::{Updatable, Renderable};
+use traits::Updatable;
+use view::Renderable;
 
 use hardcode::{POPUP_FADEOUT_TIME, POPUP_STAY_TIME};
 
@@ -46,6 +47,12 @@ impl Popup {
         }
     }
 
+    /// Font the popup is drawn with, the other labels of the screen
+    /// share it
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
     /// Sets the text of popup and brings opacity to maximum.
     ///
     /// Once the popup is bumped it's gonna become visible and slowly
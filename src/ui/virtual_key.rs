@@ -3,7 +3,7 @@ use sdl2::keyboard::Keycode;
 use sdl2::rect::Rect;
 use sdl2::pixels::Color;
 
-use traits::*;
+use view::Renderable;
 use hardcode::*;
 use fundamental::option::*;
 
@@ -6,7 +6,7 @@ use num::*;
 
 use std::collections::HashMap;
 
-use traits::*;
+use view::Renderable;
 use config::Config;
 use hardcode::*;
 
@@ -0,0 +1,49 @@
+use sdl2::render::Renderer;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+
+use looper::{SampleSnapshot, AutomationLane};
+use measure::Quant;
+use view::Renderable;
+
+/// Draws the values of the controller of the sample within its
+/// measure as a line graph. Every value holds until the next one, the
+/// last one until the end of the measure.
+pub struct AutomationView<'a> {
+    sample: &'a SampleSnapshot,
+    lane: AutomationLane,
+}
+
+impl<'a> AutomationView<'a> {
+    pub fn new(sample: &'a SampleSnapshot, lane: AutomationLane) -> AutomationView<'a> {
+        AutomationView {
+            sample: sample,
+            lane: lane,
+        }
+    }
+}
+
+impl<'a> Renderable for AutomationView<'a> {
+    fn render(&self, renderer: &mut Renderer) {
+        let window_width = renderer.viewport().width() as f32;
+        let window_height = renderer.viewport().height() as f32;
+        let Quant(window_size) = self.sample.window_size;
+        let max_value = self.lane.max_value() as f32;
+
+        let points: Vec<Point> = self.sample.automation.iter().map(|point| {
+            let Quant(quant) = point.quant;
+            let x = (quant as f32 / window_size as f32 * (window_width - 10.0) + 5.0) as i32;
+            let y = ((1.0 - point.value as f32 / max_value) * (window_height - 1.0)) as i32;
+            Point::from((x, y))
+        }).collect();
+
+        renderer.set_draw_color(Color::RGB(45, 149, 191));
+        for (point, next_point) in points.iter().zip(points.iter().skip(1)) {
+            renderer.draw_line(*point, Point::from((next_point.x(), point.y()))).unwrap();
+            renderer.draw_line(Point::from((next_point.x(), point.y())), *next_point).unwrap();
+        }
+        if let Some(last_point) = points.last() {
+            renderer.draw_line(*last_point, Point::from((window_width as i32 - 5, last_point.y()))).unwrap();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use sdl2::render::Renderer;
+use sdl2::rect::Rect;
+use sdl2::pixels::Color;
+
+use hardcode::*;
+use looper::ClipState;
+use view::Renderable;
+
+const CLIP_SLOT_SIZE: u32 = 30;
+const CLIP_SLOT_SPACING: i32 = 5;
+
+/// Draws the slots of the clip launcher
+pub struct ClipLauncherView<'a> {
+    clip_states: &'a [ClipState],
+}
+
+impl<'a> ClipLauncherView<'a> {
+    pub fn new(clip_states: &'a [ClipState]) -> ClipLauncherView<'a> {
+        ClipLauncherView {
+            clip_states: clip_states,
+        }
+    }
+}
+
+impl<'a> Renderable for ClipLauncherView<'a> {
+    fn render(&self, renderer: &mut Renderer) {
+        let viewport = renderer.viewport();
+        let step = CLIP_SLOT_SIZE as i32 + CLIP_SLOT_SPACING;
+        let x0 = viewport.width() as i32 - step * CLIP_LAUNCHER_COLUMNS as i32;
+        let y0 = viewport.height() as i32 - step * CLIP_LAUNCHER_ROWS as i32;
+
+        for slot in 0..CLIP_LAUNCHER_COLUMNS * CLIP_LAUNCHER_ROWS {
+            // The first row of pads is the bottom one
+            let column = (slot % CLIP_LAUNCHER_COLUMNS) as i32;
+            let row = (CLIP_LAUNCHER_ROWS - 1 - slot / CLIP_LAUNCHER_COLUMNS) as i32;
+            let rect = Rect::new(x0 + column * step, y0 + row * step, CLIP_SLOT_SIZE, CLIP_SLOT_SIZE);
+
+            match self.clip_states.get(slot) {
+                None => {
+                    renderer.set_draw_color(Color::RGB(50, 50, 50));
+                    renderer.draw_rect(rect).unwrap();
+                },
+
+                Some(clip_state) => {
+                    renderer.set_draw_color(match *clip_state {
+                        ClipState::Stopped => Color::RGB(100, 100, 100),
+                        ClipState::Playing => Color::RGB(100, 200, 100),
+                        ClipState::Launching | ClipState::Stopping => Color::RGB(200, 200, 100),
+                    });
+                    renderer.fill_rect(rect).unwrap();
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use sdl2::render::{Renderer, TextureQuery};
+use sdl2::pixels::Color;
+use sdl2::rect::{Point, Rect};
+use sdl2_ttf::Font;
+
+use hardcode::*;
+use looper::{LooperSnapshot, ActionKind, LaunchQuantization};
+use graphics_primitives::CircleRenderer;
+use view::{Renderable, SampleView, AutomationView};
+
+/// Draws the snapshot of the looper: the notes of the samples of the
+/// current scene with the automation lane under them, the time cursor,
+/// the scenes and the pending actions
+pub struct LooperView<'a> {
+    looper: &'a LooperSnapshot,
+    font: &'a Font,
+}
+
+impl<'a> LooperView<'a> {
+    pub fn new(looper: &'a LooperSnapshot, font: &'a Font) -> LooperView<'a> {
+        LooperView {
+            looper: looper,
+            font: font,
+        }
+    }
+
+    fn render_scenes(&self, renderer: &mut Renderer) {
+        let size = 20;
+        let spacing = 10;
+
+        for index in 0..self.looper.amount_of_scenes {
+            let x = spacing + index as i32 * (size + spacing);
+            let y = spacing;
+            let rect = Rect::new(x, y, size as u32, size as u32);
+
+            let queued = self.looper.pending_actions.iter().any(|kind| *kind == ActionKind::SwitchScene(index));
+            renderer.set_draw_color(if queued {
+                Color::RGB(200, 200, 100)
+            } else {
+                Color::RGB(100, 200, 100)
+            });
+
+            if index == self.looper.current_scene {
+                renderer.fill_rect(rect).unwrap();
+            } else {
+                renderer.draw_rect(rect).unwrap();
+            }
+        }
+
+        // The name of the current scene follows the scenes
+        let name = &self.looper.current_scene_name;
+        if !name.is_empty() {
+            let surface = self.font.render(name.as_str()).blended(Color::RGB(100, 200, 100)).unwrap();
+            let texture = renderer.create_texture_from_surface(surface).unwrap();
+            let TextureQuery { width, height, .. } = texture.query();
+            let x = spacing + self.looper.amount_of_scenes as i32 * (size + spacing);
+            let label_width = (size as f32 / height as f32 * width as f32) as u32;
+            renderer.copy(&texture, None, Some(Rect::new(x, spacing, label_width, size as u32)));
+        }
+    }
+
+    /// Draws the piano roll in the upper part of the window and the
+    /// automation lane in the lower one
+    fn render_samples(&self, renderer: &mut Renderer) {
+        let viewport = renderer.viewport();
+        let piano_roll_height = viewport.height() - AUTOMATION_LANE_HEIGHT;
+
+        renderer.set_viewport(Some(Rect::new(viewport.x(), viewport.y(), viewport.width(), piano_roll_height)));
+        for sample in &self.looper.samples {
+            SampleView::new(sample).render(renderer);
+        }
+
+        renderer.set_viewport(Some(Rect::new(viewport.x(),
+                                             viewport.y() + piano_roll_height as i32,
+                                             viewport.width(),
+                                             AUTOMATION_LANE_HEIGHT)));
+        renderer.set_draw_color(Color::RGB(35, 35, 35));
+        renderer.draw_line(Point::from((0, 0)), Point::from((viewport.width() as i32, 0))).unwrap();
+        for sample in &self.looper.samples {
+            AutomationView::new(sample, self.looper.automation_lane).render(renderer);
+        }
+
+        renderer.set_viewport(Some(viewport));
+    }
+
+    fn render_pending_actions(&self, renderer: &mut Renderer) {
+        let size = 10;
+        let spacing = 10;
+        let window_width = renderer.viewport().width() as i32;
+
+        for (index, kind) in self.looper.pending_actions.iter().enumerate() {
+            let x = spacing + index as i32 * (size + spacing);
+            let y = 40;
+
+            renderer.set_draw_color(match *kind {
+                ActionKind::StartRecording | ActionKind::StopRecording => Color::RGB(255, 0, 0),
+                ActionKind::UndoLastRecording => Color::RGB(150, 150, 150),
+                ActionKind::ToggleLastSampleMute => Color::RGB(45, 149, 191),
+                ActionKind::ChangeTempo | ActionKind::RampTempo => Color::RGB(255, 255, 255),
+                ActionKind::SwitchScene(_) => Color::RGB(100, 200, 100),
+                ActionKind::LoadComposition => Color::RGB(240, 196, 25),
+                ActionKind::LaunchSample(_) => Color::RGB(78, 186, 111),
+                ActionKind::StopSample(_) => Color::RGB(241, 90, 90),
+            });
+            renderer.fill_rect(Rect::new(x, y, size as u32, size as u32)).unwrap();
+        }
+
+        // Launch quantization: the more segments are filled the later
+        // the scheduled actions are launched
+        let filled_segments = match self.looper.launch_quantization {
+            LaunchQuantization::Immediate => 0,
+            LaunchQuantization::NextBeat => 1,
+            LaunchQuantization::NextBar | LaunchQuantization::AfterBars(_) => 2,
+            LaunchQuantization::AfterLoops(_) => 3,
+        };
+        for segment in 0..3 {
+            let rect = Rect::new(window_width - 95 + segment * (size + spacing), 80, size as u32, size as u32);
+            renderer.set_draw_color(Color::RGB(255, 0, 0));
+            if segment < filled_segments {
+                renderer.fill_rect(rect).unwrap();
+            } else {
+                renderer.draw_rect(rect).unwrap();
+            }
+        }
+    }
+}
+
+impl<'a> Renderable for LooperView<'a> {
+    fn render(&self, renderer: &mut Renderer) {
+        let window_width = renderer.viewport().width();
+        let window_height = renderer.viewport().height();
+        let looper = self.looper;
+        let measure = &looper.measure;
+        let ticks_per_measure = measure.ticks_per_measure();
+
+        self.render_samples(renderer);
+
+        let draw_time_cursor = |tick_cursor: u32, renderer: &mut Renderer| {
+            let x = ((tick_cursor as f32) /
+                     ticks_per_measure as f32 *
+                     (window_width as f32 - 10.0) + 5.0) as i32;
+            renderer.draw_line(Point::from((x, 0)),
+                               Point::from((x, window_height as i32))).unwrap();
+        };
+
+        // FIXME(#148): Separate LooperView::render into several functions.
+        // If you need separate comments like this, you need separate
+        // functions
+
+        // Time Cursor
+        renderer.set_draw_color(Color::RGB(255, 255, 255));
+        draw_time_cursor(looper.tick_cursor % ticks_per_measure, renderer);
+
+        // Measure Beats
+        for i in 0 .. measure.measure_size_bpm {
+            if measure.is_group_start(i) {
+                renderer.set_draw_color(Color::RGB(50, 50, 50));
+            } else {
+                renderer.set_draw_color(Color::RGB(35, 35, 35));
+            }
+            draw_time_cursor(i * TICKS_PER_BEAT, renderer);
+        }
+
+        // Recording Grid
+        renderer.set_draw_color(Color::RGB(70, 70, 70));
+        let measure_size_millis = measure.measure_size_millis();
+        let grid_size_millis = measure.grid_size_millis();
+        for i in 0 .. measure.measure_size_bpm * measure.beat_division() {
+            let x = ((i * grid_size_millis) as f32 /
+                     measure_size_millis as f32 *
+                     (window_width as f32 - 10.0) + 5.0) as i32;
+            renderer.draw_line(Point::from((x, window_height as i32 - 10)),
+                               Point::from((x, window_height as i32))).unwrap();
+        }
+
+        { // Circle
+            let r = 15;
+            let p = 25;
+            let x = window_width as i32 - r - 2 * p;
+            let y = r + p;
+            renderer.set_draw_color(Color::RGB(255, 0, 0));
+
+            if looper.recording {
+                renderer.fill_circle(x, y, r);
+            } else {
+                renderer.draw_circle(x, y, r);
+            }
+        }
+
+        self.render_scenes(renderer);
+        self.render_pending_actions(renderer);
+    }
+}
@@ -0,0 +1,13 @@
+mod renderable;
+mod note_view;
+mod sample_view;
+mod automation_view;
+mod looper_view;
+mod clip_launcher_view;
+
+pub use self::renderable::Renderable;
+pub use self::note_view::NoteView;
+pub use self::sample_view::SampleView;
+pub use self::automation_view::AutomationView;
+pub use self::looper_view::LooperView;
+pub use self::clip_launcher_view::ClipLauncherView;
@@ -0,0 +1,92 @@
+use sdl2::render::Renderer;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+
+use midi::Note;
+use measure::Quant;
+use view::Renderable;
+
+macro_rules! colors {
+    ($($hex:expr),*) => {
+        &[$(
+            Color::RGB((($hex & 0xFF0000) >> 16) as u8,
+                       (($hex & 0xFF00) >> 8) as u8,
+                       ($hex & 0xFF) as u8)
+        ),*]
+    }
+}
+
+const CHANNEL_PALETTE: &'static [Color; 5] = colors![0xF15A5A, 0xF0C419, 0x4EBA6F, 0x2D95BF,
+                                                     0x955BA5];
+
+fn multiply_color_vector(color: Color, factor: f32) -> Color {
+    match color {
+        Color::RGB(r, g, b) | Color::RGBA(r, g, b, _) => {
+            Color::RGB((r as f32 * factor) as u8,
+                       (g as f32 * factor) as u8,
+                       (b as f32 * factor) as u8)
+        }
+    }
+}
+
+/// Draws the note within the window of `window_size` quants. The
+/// quants of the note are relative to the window.
+pub struct NoteView<'a> {
+    note: &'a Note,
+    window_size: Quant,
+    selected: bool,
+}
+
+impl<'a> NoteView<'a> {
+    pub fn new(note: &'a Note, window_size: Quant) -> NoteView<'a> {
+        NoteView {
+            note: note,
+            window_size: window_size,
+            selected: false,
+        }
+    }
+
+    /// Draws the note selected in the piano roll framed
+    pub fn selected(note: &'a Note, window_size: Quant) -> NoteView<'a> {
+        NoteView {
+            selected: true,
+            .. NoteView::new(note, window_size)
+        }
+    }
+}
+
+impl<'a> Renderable for NoteView<'a> {
+    fn render(&self, renderer: &mut Renderer) {
+        let note = self.note;
+        let Quant(window_size) = self.window_size;
+
+        let window_width = renderer.viewport().width();
+        let window_height = renderer.viewport().height();
+        let row_height = window_height as f32 / 128.0;
+
+        let brightness_factor =  note.velocity as f32 / 127.0;
+        let base_color = CHANNEL_PALETTE[note.channel as usize % CHANNEL_PALETTE.len()];
+        let color = multiply_color_vector(base_color, brightness_factor);
+
+        let Quant(start) = note.start_quant;
+        let Quant(end) = note.end_quant;
+        let x1 = (start as f32 / window_size as f32 * (window_width as f32 - 10.0) + 5.0) as i32;
+        let x2 = (end as f32 / window_size as f32 * (window_width as f32 - 10.0) + 5.0) as i32;
+        let y = (row_height * (127 - note.key) as f32) as i32;
+
+        let note_rect = Rect::new(x1, y, (x2 - x1 + 1) as u32, row_height as u32);
+
+        renderer.set_draw_color(color);
+        // The notes that are not always played are outlined
+        if note.condition.is_always() {
+            renderer.fill_rect(note_rect).unwrap();
+        } else {
+            renderer.draw_rect(note_rect).unwrap();
+        }
+
+        if self.selected {
+            renderer.set_draw_color(Color::RGB(255, 255, 255));
+            renderer.draw_rect(Rect::new(x1 - 1, y - 1, (x2 - x1 + 3) as u32, row_height as u32 + 2)).unwrap();
+        }
+    }
+}
@@ -0,0 +1,6 @@
+use sdl2::render::Renderer;
+
+pub trait Renderable {
+    // TODO: make Renderable::render() return Result iso ()
+    fn render(&self, renderer: &mut Renderer);
+}
@@ -0,0 +1,29 @@
+use sdl2::render::Renderer;
+
+use looper::SampleSnapshot;
+use view::{Renderable, NoteView};
+
+/// Draws the notes of the sample played in the current measure of the
+/// looper
+pub struct SampleView<'a> {
+    sample: &'a SampleSnapshot,
+}
+
+impl<'a> SampleView<'a> {
+    pub fn new(sample: &'a SampleSnapshot) -> SampleView<'a> {
+        SampleView {
+            sample: sample,
+        }
+    }
+}
+
+impl<'a> Renderable for SampleView<'a> {
+    fn render(&self, renderer: &mut Renderer) {
+        for note in &self.sample.notes {
+            NoteView::new(note, self.sample.window_size).render(renderer);
+        }
+        for note in &self.sample.selected_notes {
+            NoteView::selected(note, self.sample.window_size).render(renderer);
+        }
+    }
+}